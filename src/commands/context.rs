@@ -0,0 +1,223 @@
+// Process-wide session settings that commands consult when they execute.
+// Set once by the driver (interactive loop or command mode) before dispatching.
+
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static SHOWN: AtomicBool = AtomicBool::new(false);
+static TERMINAL: AtomicBool = AtomicBool::new(false);
+
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+// Whether commands may prompt the user for decisions
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed) && !is_background()
+}
+
+thread_local! {
+    static BACKGROUND: Cell<bool> = const { Cell::new(false) };
+}
+
+// Set on the thread of a background job, which can't use the terminal
+pub fn set_background(background: bool) {
+    BACKGROUND.with(|cell| cell.set(background));
+}
+
+pub fn is_background() -> bool {
+    BACKGROUND.with(Cell::get)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+// Whether informational messages ("Creating file: x") should be suppressed.
+// Command output and errors are always printed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+// Whether commands should report each step (`ui.verbose_mode`), as `copy -v` does
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+// Set when Ctrl-C is pressed while an interactive command runs, and cleared
+// before the next one starts
+pub fn set_interrupted(interrupted: bool) {
+    INTERRUPTED.store(interrupted, Ordering::Relaxed);
+}
+
+// Whether long-running built-ins (find, grep) should stop early
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// Set by the driver, which shows command output to the user, with whether it
+// goes to a terminal. Without a driver, as when rustshell is used as a
+// library, output is only ever collected.
+pub fn set_output(terminal: bool) {
+    SHOWN.store(true, Ordering::Relaxed);
+    TERMINAL.store(terminal, Ordering::Relaxed);
+}
+
+// Whether a command may write straight to the user as it runs
+pub fn output_is_shown() -> bool {
+    SHOWN.load(Ordering::Relaxed) && !is_capturing() && !is_background()
+}
+
+// How `capture` treats the output written while it runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureMode {
+    // Collect it, to be shown wherever the enclosing output is shown
+    Collect,
+    // Collect it to be read rather than shown, so without tables or color
+    Plain,
+    // Let it through as it is written, for commands that need the terminal;
+    // only the exit code is collected
+    Live,
+}
+
+// What the commands run by `capture` on this thread have written, and the exit
+// code of the program `run` started, if any
+#[derive(Debug, Default)]
+pub struct Captured {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: Option<i32>,
+    live: bool,
+    // Whether the output ends up on a terminal, and so may use tables and color
+    styled: bool,
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+// Run `f`, collecting its command output as `mode` says. Only output written on
+// this thread is collected: warnings from the threads of a parallel walk, and
+// confirmation prompts, still go to the terminal.
+pub fn capture<T>(mode: CaptureMode, f: impl FnOnce() -> T) -> (T, Captured) {
+    let styled = match mode {
+        CaptureMode::Plain => false,
+        _ => CAPTURE.with(|capture| capture.borrow().as_ref().map(|outer| outer.styled))
+            .unwrap_or_else(|| TERMINAL.load(Ordering::Relaxed)),
+    };
+    let captured = Captured { live: mode == CaptureMode::Live, styled, ..Default::default() };
+    let outer = CAPTURE.with(|capture| capture.replace(Some(captured)));
+    let result = f();
+    let captured = CAPTURE.with(|capture| capture.replace(outer)).unwrap_or_default();
+    (result, captured)
+}
+
+// Whether output written now is collected rather than shown
+pub fn is_capturing() -> bool {
+    CAPTURE.with(|capture| capture.borrow().as_ref().is_some_and(|captured| !captured.live))
+}
+
+// Record the exit code of a program the running command started
+pub fn set_exit_code(code: i32) {
+    CAPTURE.with(|capture| {
+        if let Some(captured) = capture.borrow_mut().as_mut() {
+            captured.exit_code = Some(code);
+        }
+    });
+}
+
+// Whether output ends up on a terminal, and so may use tables and color
+pub fn stdout_is_terminal() -> bool {
+    use std::io::IsTerminal;
+    match CAPTURE.with(|capture| capture.borrow().as_ref().map(|captured| captured.styled)) {
+        Some(styled) => styled,
+        None => io::stdout().is_terminal(),
+    }
+}
+
+// Where commands write their output: the terminal, or the active capture
+pub struct Output {
+    stderr: bool,
+}
+
+pub fn stdout() -> Output {
+    Output { stderr: false }
+}
+
+pub fn stderr() -> Output {
+    Output { stderr: true }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURE.with(|capture| {
+            let mut capture = capture.borrow_mut();
+            let Some(captured) = capture.as_mut().filter(|captured| !captured.live) else {
+                return false;
+            };
+            let buffer = if self.stderr { &mut captured.stderr } else { &mut captured.stdout };
+            buffer.extend_from_slice(buf);
+            true
+        });
+        if captured {
+            Ok(buf.len())
+        } else if self.stderr {
+            io::stderr().write(buf)
+        } else {
+            io::stdout().write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if is_capturing() {
+            Ok(())
+        } else if self.stderr {
+            io::stderr().flush()
+        } else {
+            io::stdout().flush()
+        }
+    }
+}
+
+// `print!`, `println!` and `eprintln!` for command output, so that `capture`
+// can collect it. A closed pipe ends the output instead of panicking.
+macro_rules! out {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = write!($crate::commands::context::stdout(), $($arg)*);
+    }};
+}
+
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!($crate::commands::context::stdout(), $($arg)*);
+    }};
+}
+
+macro_rules! errln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!($crate::commands::context::stderr(), $($arg)*);
+    }};
+}
+
+// `outln!` for informational messages, silenced by `--quiet`/`ui.quiet`
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !$crate::commands::context::is_quiet() {
+            $crate::commands::context::outln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {errln, info, out, outln};
@@ -0,0 +1,154 @@
+// Frecency-ranked record of visited directories, used by `j` to jump to a
+// directory from a fragment of its path (like `z`/`autojump`).
+//
+// Stored in ~/.rustshell/dirhistory as one `count<TAB>last_visit<TAB>path` line
+// per directory, with `last_visit` in seconds since the Unix epoch.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::utils;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirVisit {
+    pub path: PathBuf,
+    pub count: u64,
+    pub last_visit: u64,
+}
+
+impl DirVisit {
+    // Visit count weighted by how recently the directory was used, as in `z`
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_visit);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < DAY {
+            2.0
+        } else if age < WEEK {
+            0.5
+        } else {
+            0.25
+        };
+        self.count as f64 * weight
+    }
+}
+
+pub struct DirHistory {
+    visits: Vec<DirVisit>,
+    file: PathBuf,
+}
+
+impl DirHistory {
+    pub fn load() -> Self {
+        let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+        Self::with_file(home_dir.join(".rustshell").join("dirhistory"))
+    }
+
+    pub fn with_file(file: PathBuf) -> Self {
+        let visits = fs::read_to_string(&file)
+            .map(|contents| contents.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+        DirHistory { visits, file }
+    }
+
+    pub fn record(&mut self, dir: &Path, now: u64) {
+        match self.visits.iter_mut().find(|visit| visit.path == dir) {
+            Some(visit) => {
+                visit.count += 1;
+                visit.last_visit = now;
+            }
+            None => self.visits.push(DirVisit {
+                path: dir.to_path_buf(),
+                count: 1,
+                last_visit: now,
+            }),
+        }
+    }
+
+    // The highest-ranked directory whose path contains `query` (case-insensitive).
+    // Matches on the final path component win over matches elsewhere in the path.
+    pub fn best_match(&self, query: &str, now: u64) -> Option<&Path> {
+        let query = query.to_lowercase();
+        self.visits
+            .iter()
+            .filter(|visit| visit.path.to_string_lossy().to_lowercase().contains(&query))
+            .map(|visit| {
+                let in_name = visit
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase().contains(&query))
+                    .unwrap_or(false);
+                (in_name, visit.frecency(now), visit)
+            })
+            .max_by(|a, b| (a.0, a.1).partial_cmp(&(b.0, b.1)).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, _, visit)| visit.path.as_path())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = String::new();
+        for visit in &self.visits {
+            content.push_str(&format!("{}\t{}\t{}\n", visit.count, visit.last_visit, visit.path.display()));
+        }
+        utils::atomic_write(&self.file, content.as_bytes())
+    }
+}
+
+fn parse_line(line: &str) -> Option<DirVisit> {
+    let mut fields = line.splitn(3, '\t');
+    let count = fields.next()?.parse().ok()?;
+    let last_visit = fields.next()?.parse().ok()?;
+    let path = PathBuf::from(fields.next()?);
+    Some(DirVisit { path, count, last_visit })
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Remember that the user changed into `dir`. History is a convenience, so
+// failing to update it never fails the `cd` itself.
+pub fn record_visit(dir: &Path) {
+    let mut history = DirHistory::load();
+    history.record(dir, now());
+    let _ = history.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frecency_prefers_recent_and_frequent() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("dirhistory");
+        let now = 10 * WEEK;
+
+        let mut history = DirHistory::with_file(file.clone());
+        // Visited often, but a month ago
+        for _ in 0..5 {
+            history.record(Path::new("/work/old-project"), now - 4 * WEEK);
+        }
+        // Visited twice in the last hour
+        history.record(Path::new("/work/new-project"), now - 60);
+        history.record(Path::new("/work/new-project"), now - 30);
+        history.record(Path::new("/home/me/projects-notes/readme"), now);
+        history.save().unwrap();
+
+        let history = DirHistory::with_file(file);
+        assert_eq!(history.best_match("project", now), Some(Path::new("/work/new-project")));
+        assert_eq!(history.best_match("OLD", now), Some(Path::new("/work/old-project")));
+        assert_eq!(history.best_match("nowhere", now), None);
+    }
+}
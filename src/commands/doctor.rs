@@ -0,0 +1,193 @@
+// Environment checks behind the `doctor` command. Each check reports a status
+// and a short detail; none of them modify user data.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::RustShellConfig;
+use crate::llm::LLMProvider;
+use crate::utils::find_in_path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Status::Pass => "[ OK ]",
+            Status::Warn => "[WARN]",
+            Status::Fail => "[FAIL]",
+        }
+    }
+}
+
+pub struct Check {
+    pub status: Status,
+    pub label: &'static str,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: Status, label: &'static str, detail: impl Into<String>) -> Self {
+        Check { status, label, detail: detail.into() }
+    }
+}
+
+pub fn run_checks() -> Vec<Check> {
+    let home = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    let data_dir = home.join(".rustshell");
+
+    let config_path = RustShellConfig::config_file_path().unwrap_or_else(|_| data_dir.join("config.toml"));
+    let (config_check, config) = check_config(&config_path);
+    let mut checks = vec![config_check, check_api_key(&config)];
+    checks.push(check_writable(&data_dir));
+    checks.push(check_alias_file(&crate::AliasManager::default_file()));
+    checks.extend(check_tools());
+    checks
+}
+
+// Parse the config file, returning the config to use for the remaining checks
+fn check_config(path: &Path) -> (Check, RustShellConfig) {
+    const LABEL: &str = "Config file";
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) if !path.exists() => {
+            let check = Check::new(Status::Warn, LABEL, format!("{} not found; defaults are used", path.display()));
+            return (check, RustShellConfig::default());
+        }
+        Err(e) => return (Check::new(Status::Fail, LABEL, format!("{}: {}", path.display(), e)), RustShellConfig::default()),
+    };
+
+    match toml::from_str::<RustShellConfig>(&content) {
+        Ok(config) => match config.validate() {
+            Ok(()) => (Check::new(Status::Pass, LABEL, path.display().to_string()), config),
+            Err(e) => (
+                Check::new(Status::Fail, LABEL, format!("{} has invalid settings:\n{}", path.display(), e)),
+                RustShellConfig::default(),
+            ),
+        },
+        Err(e) => (
+            Check::new(Status::Fail, LABEL, format!("{} is invalid: {}", path.display(), e.message())),
+            RustShellConfig::default(),
+        ),
+    }
+}
+
+fn check_api_key(config: &RustShellConfig) -> Check {
+    const LABEL: &str = "API key";
+
+    if !config.features.enable_llm || config.features.offline_mode {
+        return Check::new(Status::Pass, LABEL, "not needed, LLM features are disabled");
+    }
+
+    match config.to_llm_config() {
+        Ok(llm) => match (&llm.provider, &llm.api_key) {
+            (LLMProvider::OpenAI | LLMProvider::Anthropic, None) => Check::new(
+                Status::Warn,
+                LABEL,
+                format!("no key for {}; natural-language commands will not work", llm.provider),
+            ),
+            (provider, Some(_)) => Check::new(Status::Pass, LABEL, format!("found for {}", provider)),
+            (provider, None) => Check::new(Status::Pass, LABEL, format!("not required for {}", provider)),
+        },
+        Err(e) => Check::new(Status::Fail, LABEL, e.to_string()),
+    }
+}
+
+// Aliases, history and other state live here, so it must be writable
+fn check_writable(dir: &Path) -> Check {
+    const LABEL: &str = "Data directory";
+
+    let probe = dir.join(format!(".doctor{}", std::process::id()));
+    let result = fs::create_dir_all(dir).and_then(|_| fs::write(&probe, b"probe"));
+    let _ = fs::remove_file(&probe);
+
+    match result {
+        Ok(()) => Check::new(Status::Pass, LABEL, format!("{} is writable", dir.display())),
+        Err(e) => Check::new(Status::Fail, LABEL, format!("{} is not writable: {}", dir.display(), e)),
+    }
+}
+
+// Lines the alias loader would silently skip point at a damaged file
+fn check_alias_file(path: &Path) -> Check {
+    const LABEL: &str = "Alias file";
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) if !path.exists() => return Check::new(Status::Pass, LABEL, "no aliases defined yet"),
+        Err(e) => return Check::new(Status::Fail, LABEL, format!("{}: {}", path.display(), e)),
+    };
+
+    let bad_lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#') && !line.contains('=')
+        })
+        .map(|(number, _)| (number + 1).to_string())
+        .collect();
+
+    if bad_lines.is_empty() {
+        Check::new(Status::Pass, LABEL, path.display().to_string())
+    } else {
+        Check::new(
+            Status::Warn,
+            LABEL,
+            format!("{}: unreadable line(s) {} are ignored", path.display(), bad_lines.join(", ")),
+        )
+    }
+}
+
+// External programs some commands still shell out to
+fn check_tools() -> Vec<Check> {
+    let tools: &[(&str, &str)] = if cfg!(windows) {
+        &[("powershell", "compress")]
+    } else if cfg!(target_os = "macos") {
+        &[("zip", "compress")]
+    } else {
+        &[("zip", "compress"), ("xdg-open", "open")]
+    };
+
+    tools
+        .iter()
+        .map(|(tool, used_by)| match find_in_path(tool) {
+            Some(path) => Check::new(Status::Pass, "External tool", format!("{} ({})", tool, path.display())),
+            None => Check::new(Status::Warn, "External tool", format!("{} not found; `{}` will not work", tool, used_by)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_and_alias_checks() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = dir.path().join("config.toml");
+        assert_eq!(check_config(&config).0.status, Status::Warn);
+        fs::write(&config, "[llm]\nprovider = 3\n").unwrap();
+        assert_eq!(check_config(&config).0.status, Status::Fail);
+        fs::write(&config, toml::to_string(&RustShellConfig::default()).unwrap()).unwrap();
+        assert_eq!(check_config(&config).0.status, Status::Pass);
+        fs::write(&config, toml::to_string(&RustShellConfig::default()).unwrap().replace("temperature = 0.1", "temperature = 5.0")).unwrap();
+        let check = check_config(&config).0;
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.detail.contains("llm.temperature is 5"));
+
+        let aliases = dir.path().join("aliases");
+        fs::write(&aliases, "# RustShell aliases\nll=list -la\ngarbage\n").unwrap();
+        let check = check_alias_file(&aliases);
+        assert_eq!(check.status, Status::Warn);
+        assert!(check.detail.contains("line(s) 3"));
+
+        assert_eq!(check_writable(dir.path()).status, Status::Pass);
+    }
+}
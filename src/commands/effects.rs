@@ -0,0 +1,166 @@
+// A plain-language account of what a translated command will do, shown when
+// asking for confirmation, e.g. "will delete 3 files matching *.tmp in /home/me".
+// Only the common file operations are understood; anything else gets no
+// description rather than a guess.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::utils;
+
+pub fn describe(command: &str) -> Vec<String> {
+    command
+        .split("&&")
+        .flat_map(|part| part.split(';'))
+        .flat_map(|part| describe_simple(&part.split_whitespace().collect::<Vec<_>>()))
+        .collect()
+}
+
+fn describe_simple(words: &[&str]) -> Vec<String> {
+    let Some((&program, args)) = words.split_first() else {
+        return Vec::new();
+    };
+    let (flags, operands): (Vec<&str>, Vec<&str>) = args.iter().partition(|arg| arg.starts_with('-') && arg.len() > 1);
+    let recursive = flags.iter().any(|flag| !flag.starts_with("--") && flag.contains(['r', 'R']) || *flag == "--recursive");
+
+    match program {
+        "rm" | "del" | "delete_file" | "delete_dir" | "rmdir" | "Remove-Item" => {
+            operands.iter().map(|operand| describe_removal(operand, recursive)).collect()
+        }
+        "mv" | "move" | "Move-Item" => describe_transfer("move", &operands),
+        "cp" | "copy" | "Copy-Item" => describe_transfer("copy", &operands),
+        _ => Vec::new(),
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+// Entries matching a wildcard in the last path component, e.g. `logs/*.tmp`
+fn expand_glob(pattern: &str) -> (PathBuf, Vec<PathBuf>) {
+    let path = Path::new(pattern);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => absolute(parent),
+        _ => absolute(Path::new(".")),
+    };
+    let name_pattern = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut matches: Vec<PathBuf> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| utils::wildcard_match(&name_pattern, &entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    matches.sort();
+    (dir, matches)
+}
+
+fn count_entries(dir: &Path) -> usize {
+    walkdir::WalkDir::new(dir).min_depth(1).into_iter().filter_map(Result::ok).count()
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+fn describe_removal(operand: &str, recursive: bool) -> String {
+    if has_wildcards(operand) {
+        let (dir, matches) = expand_glob(operand);
+        let dirs = matches.iter().filter(|path| path.is_dir()).count();
+        let files = matches.len() - dirs;
+        let pattern = Path::new(operand).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        return match (files, dirs) {
+            (0, 0) => format!("nothing matches {} in {}", pattern, dir.display()),
+            (files, 0) => format!("will delete {} matching {} in {}", plural(files, "file", "files"), pattern, dir.display()),
+            (files, dirs) => format!(
+                "will delete {} and {} matching {} in {}",
+                plural(files, "file", "files"),
+                plural(dirs, "directory", "directories"),
+                pattern,
+                dir.display()
+            ),
+        };
+    }
+
+    let path = absolute(Path::new(operand));
+    match fs::symlink_metadata(&path) {
+        Err(_) => format!("{} does not exist", path.display()),
+        Ok(metadata) if metadata.is_dir() && recursive => {
+            let entries = plural(count_entries(&path), "entry", "entries");
+            format!("will delete directory {} and everything in it ({})", path.display(), entries)
+        }
+        Ok(metadata) if metadata.is_dir() => format!("will delete directory {}", path.display()),
+        Ok(_) => format!("will delete file {}", path.display()),
+    }
+}
+
+fn describe_transfer(verb: &str, operands: &[&str]) -> Vec<String> {
+    let Some((destination, sources)) = operands.split_last() else {
+        return Vec::new();
+    };
+    let destination = absolute(Path::new(destination));
+    let into_dir = destination.is_dir();
+
+    let sources: Vec<PathBuf> = sources
+        .iter()
+        .flat_map(|source| {
+            if has_wildcards(source) {
+                expand_glob(source).1
+            } else {
+                vec![absolute(Path::new(source))]
+            }
+        })
+        .collect();
+
+    sources
+        .iter()
+        .map(|source| {
+            let target = match (into_dir, source.file_name()) {
+                (true, Some(name)) => destination.join(name),
+                _ => destination.clone(),
+            };
+            let overwrite = if target.exists() && &target != source { " (replacing it)" } else { "" };
+            format!("will {} {} -> {}{}", verb, source.display(), target.display(), overwrite)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_removals_and_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().display().to_string();
+        for name in ["a.tmp", "b.tmp", "c.tmp", "keep.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir_all(dir.path().join("cache/sub")).unwrap();
+
+        assert_eq!(
+            describe(&format!("rm -f {}/*.tmp", root)),
+            vec![format!("will delete 3 files matching *.tmp in {}", root)]
+        );
+        assert_eq!(
+            describe(&format!("rm -rf {}/cache", root)),
+            vec![format!("will delete directory {}/cache and everything in it (1 entry)", root)]
+        );
+        assert_eq!(describe(&format!("rm {}/*.log", root)), vec![format!("nothing matches *.log in {}", root)]);
+
+        assert_eq!(
+            describe(&format!("mkdir -p {0}/old && mv {0}/keep.txt {0}/cache", root)),
+            vec![format!("will move {0}/keep.txt -> {0}/cache/keep.txt", root)]
+        );
+        assert!(describe("ls -la").is_empty());
+    }
+}
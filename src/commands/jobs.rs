@@ -0,0 +1,156 @@
+// Background jobs for interactive mode: `command &` runs the command on a
+// thread of its own and returns to the prompt at once, `jobs` lists what is
+// running and `fg [n]` waits for a job to finish. A job's output is shown
+// when it finishes.
+
+use std::io::{self, Write};
+use std::thread::{self, JoinHandle};
+
+use super::{context, report, ShellCommand};
+
+struct Job {
+    id: usize,
+    command: String,
+    // The exit code the job's command ended with
+    handle: JoinHandle<i32>,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+// The command before a trailing `&`, if the line asks to run in the background.
+// `&&` is not a background marker.
+pub fn background_command(line: &str) -> Option<&str> {
+    let command = line.trim_end().strip_suffix('&')?;
+    if command.ends_with('&') {
+        return None;
+    }
+    Some(command.trim_end()).filter(|command| !command.is_empty())
+}
+
+fn describe(exit_code: Option<i32>) -> String {
+    match exit_code {
+        Some(0) => "Done".to_string(),
+        Some(code) => format!("Exit {}", code),
+        None => "Crashed".to_string(),
+    }
+}
+
+fn finish(job: Job) -> String {
+    format!("[{}] {:<8} {}", job.id, describe(job.handle.join().ok()), job.command)
+}
+
+impl Jobs {
+    // Start `command`, already parsed from `line` with aliases expanded to
+    // `args`, without waiting for it. It cannot read the terminal, so
+    // confirmation prompts in it fail or take their default answer.
+    pub fn spawn(&mut self, line: &str, args: Vec<String>, command: Box<dyn ShellCommand>, colored: bool) -> io::Result<String> {
+        let handle = thread::Builder::new().name(format!("job: {}", line)).spawn(move || {
+            context::set_background(true);
+            let result = command.execute();
+            let _ = io::stdout().write_all(result.stdout.as_bytes()).and_then(|()| io::stdout().flush());
+            let _ = io::stderr().write_all(result.stderr.as_bytes());
+            if let Some(e) = &result.error {
+                report::report(&args, e, colored);
+            }
+            result.exit_code
+        })?;
+        // Numbers are reused once every job has finished, as in bash
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job { id, command: line.to_string(), handle });
+        Ok(format!("[{}] {}", id, line))
+    }
+
+    pub fn running(&self) -> usize {
+        self.jobs.len()
+    }
+
+    // Jobs that have finished since the last call, e.g. `[1] Done  make`,
+    // which are then forgotten. Shown before each prompt.
+    pub fn finished(&mut self) -> Vec<String> {
+        let (done, running) = std::mem::take(&mut self.jobs).into_iter().partition(|job| job.handle.is_finished());
+        self.jobs = running;
+        done.into_iter().map(finish).collect()
+    }
+
+    // `jobs`: every job still running. Finished ones are reported (and
+    // forgotten) first.
+    pub fn list(&mut self) -> Vec<String> {
+        let mut lines = self.finished();
+        lines.extend(self.jobs.iter().map(|job| format!("[{}] {:<8} {}", job.id, "Running", job.command)));
+        lines
+    }
+
+    // `fg [n]`: wait for job `n` (the most recent one by default) to finish
+    pub fn wait(&mut self, id: Option<usize>) -> io::Result<String> {
+        let index = match id {
+            Some(id) => self.jobs.iter().position(|job| job.id == id),
+            None => self.jobs.len().checked_sub(1),
+        };
+        let Some(index) = index else {
+            let which = id.map(|id| format!("job {}", id)).unwrap_or_else(|| "any job".to_string());
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("fg: no such job: {}", which)));
+        };
+
+        let job = self.jobs.remove(index);
+        println!("{}", job.command);
+        Ok(finish(job))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_marker() {
+        assert_eq!(background_command("sleep 10 &"), Some("sleep 10"));
+        assert_eq!(background_command("sleep 10&"), Some("sleep 10"));
+        assert_eq!(background_command("make && make test"), None);
+        assert_eq!(background_command("make &&"), None);
+        assert_eq!(background_command("&"), None);
+        assert_eq!(background_command("list"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_jobs_are_tracked_until_they_finish() {
+        use crate::commands::{ExecuteCommand, ShowFile};
+
+        let mut jobs = Jobs::default();
+        let mut spawn = |line: &str, command: Box<dyn ShellCommand>| {
+            let args = line.split_whitespace().map(String::from).collect();
+            jobs.spawn(line, args, command, false).unwrap()
+        };
+        let program = |program: &str, args: &[&str]| {
+            Box::new(ExecuteCommand {
+                command: program.to_string(),
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                ..Default::default()
+            })
+        };
+        assert_eq!(spawn("sleep 1", program("sleep", &["1"])), "[1] sleep 1");
+        spawn("sh -c exit 3", program("sh", &["-c", "exit 3"]));
+        // A built-in that fails is reported as failed, too
+        spawn("show absent", Box::new(ShowFile { paths: vec!["/nonexistent/absent".to_string()] }));
+
+        assert_eq!(jobs.wait(Some(2)).unwrap(), "[2] Exit 3   sh -c exit 3");
+        assert_eq!(jobs.wait(Some(3)).unwrap(), "[3] Exit 1   show absent");
+        assert_eq!(jobs.list(), ["[1] Running  sleep 1"]);
+        assert_eq!(jobs.wait(Some(7)).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        assert_eq!(jobs.wait(None).unwrap(), "[1] Done     sleep 1");
+        assert_eq!(jobs.running(), 0);
+    }
+
+    #[test]
+    fn test_jobs_cannot_prompt() {
+        let handle = thread::spawn(|| {
+            context::set_background(true);
+            context::is_interactive()
+        });
+        assert!(!handle.join().unwrap());
+    }
+}
@@ -0,0 +1,4266 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use crate::utils;
+use crate::GLOBAL_FLAGS;
+use context::{errln, info, out, outln};
+
+pub mod context;
+pub mod dirhistory;
+pub mod doctor;
+pub mod effects;
+pub mod jobs;
+pub mod nlhistory;
+pub mod overwrite;
+pub mod registry;
+pub mod report;
+pub mod sanitize;
+pub mod usage;
+pub mod walk;
+
+pub use registry::registry;
+
+// How a command ended and what it printed. Commands return it rather than
+// print, so the driver (or a program using rustshell as a library) decides
+// where the output goes.
+#[derive(Debug, Default)]
+pub struct CommandResult {
+    // 0 on success and 1 on failure, or the exit code of the program `run` started
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    // Why the command failed, if it did; not part of `stderr`
+    pub error: Option<io::Error>,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    // Pass on a nested command's result as the output and outcome of the
+    // command running it
+    fn forward(self) -> io::Result<()> {
+        use std::io::Write;
+
+        context::stdout().write_all(self.stdout.as_bytes())?;
+        context::stderr().write_all(self.stderr.as_bytes())?;
+        match self.error {
+            Some(e) => Err(e),
+            None => {
+                if self.exit_code != 0 {
+                    context::set_exit_code(self.exit_code);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Define the Command trait which will implement platform-specific commands
+pub trait ShellCommand: Send {
+    // The command's work, writing its output with `out!`, `outln!` and
+    // `errln!`. Call `execute` to run a command.
+    fn run(&self) -> io::Result<()>;
+    fn help(&self) -> String;
+
+    // Whether the command needs the terminal while it runs, as a program
+    // started by `run` may. Its output is then shown as it is written, and
+    // only the exit code is returned.
+    fn needs_terminal(&self) -> bool {
+        false
+    }
+
+    // Run the command and return its output for the caller to show
+    fn execute(&self) -> CommandResult {
+        let mode = if self.needs_terminal() && context::output_is_shown() {
+            context::CaptureMode::Live
+        } else {
+            context::CaptureMode::Collect
+        };
+        let (result, captured) = context::capture(mode, || self.run());
+        CommandResult {
+            exit_code: match &result {
+                Ok(()) => captured.exit_code.unwrap_or(0),
+                Err(_) => 1,
+            },
+            stdout: String::from_utf8_lossy(&captured.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&captured.stderr).into_owned(),
+            error: result.err(),
+        }
+    }
+}
+
+// OS-specific command implementations
+#[derive(Default)]
+pub struct MakeDir {
+    pub paths: Vec<String>,
+    pub parents: bool,
+    // Succeed when the directory already exists (implied by `parents`)
+    pub exists_ok: bool,
+}
+
+impl ShellCommand for MakeDir {
+    fn run(&self) -> io::Result<()> {
+        // Use Rust's native functions for cross-platform compatibility
+        for path in &self.paths {
+            let path = Path::new(path);
+            if self.parents {
+                fs::create_dir_all(path)?;
+            } else {
+                match fs::create_dir(path) {
+                    // An existing file of the same name is still an error
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists && self.exists_ok && path.is_dir() => {}
+                    result => result?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Create one or more directories. Usage: make_dir [-p] [--exists-ok] <directory>...".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct MakeFile {
+    pub paths: Vec<String>,
+}
+
+impl ShellCommand for MakeFile {
+    fn run(&self) -> io::Result<()> {
+        // Create multiple files
+        for path in &self.paths {
+            info!("Creating file: {}", path);
+            File::create(path)?;
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Create one or more empty files. Usage: create_file <file1> <file2> ...".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct CopyFile {
+    pub src: String,
+    pub dst: String,
+    pub recursive: bool,
+    // -v: print each file copied
+    pub verbose: bool,
+}
+
+// `'src' -> 'dst'` for each file `copy`/`move` handles, with -v or `ui.verbose_mode`
+fn report_transfer(verbose: bool, src: &Path, dst: &Path) {
+    if verbose || context::is_verbose() {
+        info!("'{}' -> '{}'", src.display(), dst.display());
+    }
+}
+
+impl CopyFile {
+    fn copy_tree(&self, src: &Path, target: &Path) -> io::Result<()> {
+        // A destination inside the source would be copied into itself forever
+        if utils::is_within(target, src)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot copy '{}' into itself ('{}')", src.display(), target.display()),
+            ));
+        }
+
+        for entry in walkdir::WalkDir::new(src) {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(src).expect("walkdir yields paths under its root");
+            let destination = target.join(relative);
+
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&destination)?;
+            } else if let Some(destination) = overwrite::resolve_destination(entry.path(), &destination)? {
+                fs::copy(entry.path(), &destination)?;
+                report_transfer(self.verbose, entry.path(), &destination);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ShellCommand for CopyFile {
+    fn run(&self) -> io::Result<()> {
+        let src = Path::new(&self.src);
+        let dst = Path::new(&self.dst);
+
+        if src.is_dir() {
+            if !self.recursive {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}' is a directory (use copy -r)", self.src),
+                ));
+            }
+            // Like `cp -r`, copying into an existing directory nests the source inside it
+            let target = match (dst.is_dir(), src.file_name()) {
+                (true, Some(name)) => dst.join(name),
+                _ => dst.to_path_buf(),
+            };
+            return self.copy_tree(src, &target);
+        }
+
+        // Use native Rust for basic file operations
+        if let Some(dst) = overwrite::resolve_destination(src, dst)? {
+            fs::copy(src, &dst)?;
+            report_transfer(self.verbose, src, &dst);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Copy a file, or a directory with -r; -v prints each file copied. Usage: copy [-r] [-v] <source> <destination>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct MoveFile {
+    pub src: String,
+    pub dst: String,
+    // -v: print what was moved where
+    pub verbose: bool,
+}
+
+impl ShellCommand for MoveFile {
+    fn run(&self) -> io::Result<()> {
+        let src = Path::new(&self.src);
+        if let Some(dst) = overwrite::resolve_destination(src, Path::new(&self.dst))? {
+            fs::rename(src, &dst)?;
+            report_transfer(self.verbose, src, &dst);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Move a file or directory; -v prints what was moved. Usage: move [-v] <source> <destination>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct RemoveFile {
+    pub paths: Vec<String>,
+}
+
+impl ShellCommand for RemoveFile {
+    fn run(&self) -> io::Result<()> {
+        // Remove multiple files
+        for path in &self.paths {
+            info!("Removing file: {}", path);
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Remove one or more files. Usage: delete_file <file1> <file2> ...".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct RemoveDir {
+    pub path: String,
+    pub recursive: bool,
+    pub force_dangerous: bool,
+    // --yes / -y: skip the confirmation before a recursive delete
+    pub yes: bool,
+}
+
+impl RemoveDir {
+    // What a recursive delete would destroy: files (including symlinks),
+    // subdirectories and their total size in bytes
+    fn contents(&self) -> (usize, usize, u64) {
+        let (mut files, mut dirs, mut size) = (0, 0, 0);
+        for entry in walkdir::WalkDir::new(&self.path).min_depth(1).into_iter().filter_map(Result::ok) {
+            if entry.file_type().is_dir() {
+                dirs += 1;
+            } else {
+                files += 1;
+                size += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            }
+        }
+        (files, dirs, size)
+    }
+
+    // Show what would be deleted and ask before going ahead, if the config asks
+    // for it. Without a user at the prompt to answer, the delete fails.
+    fn confirm(&self, config: &crate::config::RustShellConfig) -> io::Result<bool> {
+        if !config.ui.confirm_destructive {
+            return Ok(true);
+        }
+        if !context::is_interactive() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("refusing to delete {} recursively without confirmation (pass --yes)", self.path),
+            ));
+        }
+
+        // The summary is part of the prompt, as output is only shown once the
+        // command has finished
+        let (files, dirs, size) = self.contents();
+        let question = format!(
+            "'{}' contains {} file{} and {} subdirector{} ({} in total).\nDelete all of it?",
+            self.path,
+            files,
+            if files == 1 { "" } else { "s" },
+            dirs,
+            if dirs == 1 { "y" } else { "ies" },
+            if size < 1024 { format!("{} bytes", size) } else { utils::format_size(size, true) }
+        );
+        utils::confirm(&question, config.confirm_default_yes())
+    }
+}
+
+// Returns why recursively deleting `path` would be catastrophic, if it would be.
+// Refuses the filesystem root, the home directory and the current directory
+// (including any directory that contains it).
+pub fn dangerous_removal_reason(path: &Path) -> Option<String> {
+    let target = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(_) => return None,
+    };
+
+    if target.parent().is_none() {
+        return Some("refusing to remove the filesystem root".to_string());
+    }
+
+    if let Some(home) = dirs_next::home_dir().and_then(|h| fs::canonicalize(h).ok()) {
+        if target == home {
+            return Some("refusing to remove the home directory".to_string());
+        }
+    }
+
+    if let Ok(cwd) = env::current_dir().and_then(fs::canonicalize) {
+        if cwd.starts_with(&target) {
+            return Some("refusing to remove the current directory or one of its parents".to_string());
+        }
+    }
+
+    None
+}
+
+impl ShellCommand for RemoveDir {
+    fn run(&self) -> io::Result<()> {
+        if self.recursive {
+            if !self.force_dangerous {
+                if let Some(reason) = dangerous_removal_reason(Path::new(&self.path)) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("{}: {} (use --force-dangerous to override)", reason, self.path),
+                    ));
+                }
+            }
+            if !fs::metadata(&self.path)?.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not a directory: {}", self.path)));
+            }
+            if !self.yes && !self.confirm(&crate::current_config())? {
+                outln!("Nothing was deleted");
+                return Ok(());
+            }
+            fs::remove_dir_all(&self.path)?;
+        } else {
+            fs::remove_dir(&self.path)?;
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Remove a directory. With -r, shows how much would be deleted and asks first (unless --yes or ui.confirm_destructive is off). Usage: delete_dir [-r] [--yes] [--force-dangerous] <directory>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct ChangeDir {
+    pub path: String,
+}
+
+impl ChangeDir {
+    // Say why the target can't be entered, rather than the OS's terse error
+    fn check_target(&self) -> io::Result<()> {
+        match fs::metadata(&self.path) {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cd: not a directory: {}", self.path),
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cd: no such directory: {}", self.path),
+            )),
+            Err(e) => Err(io::Error::new(e.kind(), format!("cd: {}: {}", self.path, e))),
+        }
+    }
+}
+
+impl ShellCommand for ChangeDir {
+    fn run(&self) -> io::Result<()> {
+        self.check_target()?;
+        env::set_current_dir(&self.path)
+            .map_err(|e| io::Error::new(e.kind(), format!("cd: {}: {}", self.path, e)))?;
+        if let Ok(dir) = env::current_dir() {
+            dirhistory::record_visit(&dir);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Change current directory. Usage: change_dir <directory>".to_string()
+    }
+}
+
+// Jump to the most frecent previously visited directory matching a fragment
+#[derive(Default)]
+pub struct JumpDir {
+    pub query: String,
+}
+
+impl ShellCommand for JumpDir {
+    fn run(&self) -> io::Result<()> {
+        // A real path behaves like a normal `cd`
+        if Path::new(&self.query).is_dir() {
+            return ChangeDir { path: self.query.clone() }.run();
+        }
+
+        let history = dirhistory::DirHistory::load();
+        match history.best_match(&self.query, dirhistory::now()) {
+            Some(dir) => {
+                outln!("{}", dir.display());
+                ChangeDir { path: dir.display().to_string() }.run()
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no visited directory matches '{}'", self.query),
+            )),
+        }
+    }
+
+    fn help(&self) -> String {
+        "Jump to a frequently used directory by part of its name. Usage: j <partial>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct ListDir {
+    pub path: Option<String>,
+    // -l: permissions, size and modification time
+    pub long: bool,
+    // -1: one entry per line instead of columns
+    pub one_per_line: bool,
+    // -a: include entries starting with a dot
+    pub all: bool,
+    // -h: human-readable sizes in long format
+    pub human: bool,
+    // --table: Name/Size/Modified columns with headers, on a terminal
+    pub table: bool,
+}
+
+// Width to lay columns out in when stdout is not a terminal
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+// Header for the Name/Size/Modified tables of `list --table` and `find --table`
+const FILE_TABLE_HEADERS: [(&str, Align); 3] = [("Name", Align::Left), ("Size", Align::Right), ("Modified", Align::Left)];
+
+impl ListDir {
+    fn entries(&self, dir: &Path) -> io::Result<Vec<(String, fs::Metadata)>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !self.all && name.starts_with('.') {
+                continue;
+            }
+            // Don't follow symlinks, so they are listed as links
+            let metadata = fs::symlink_metadata(entry.path())?;
+            entries.push((name, metadata));
+        }
+        entries.sort_by_key(|(name, _)| name.to_lowercase());
+        Ok(entries)
+    }
+
+    fn long_line(&self, dir: &Path, name: &str, metadata: &fs::Metadata, size_width: usize) -> String {
+        let mut line = format!(
+            "{} {:>width$} {} {}",
+            mode_string(metadata),
+            utils::format_size(metadata.len(), self.human),
+            format_modified(metadata),
+            name,
+            width = size_width
+        );
+        if metadata.file_type().is_symlink() {
+            if let Ok(target) = fs::read_link(dir.join(name)) {
+                line.push_str(&format!(" -> {}", target.display()));
+            }
+        }
+        line
+    }
+}
+
+fn format_modified(metadata: &fs::Metadata) -> String {
+    metadata
+        .modified()
+        .map(|time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| "-".repeat(16))
+}
+
+// One row of a file table; directories have no meaningful size
+fn file_table_row(name: String, metadata: &fs::Metadata) -> Vec<String> {
+    let size = if metadata.is_dir() { "-".to_string() } else { utils::format_size(metadata.len(), true) };
+    vec![name, size, format_modified(metadata)]
+}
+
+// Tables are for reading; when output goes to a file or pipe, plain output
+// is easier to process
+fn wants_table(table: bool) -> bool {
+    table && context::stdout_is_terminal()
+}
+
+#[derive(Clone, Copy)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+// Align `rows` under a header and a dashed rule, sizing each column to its widest cell
+pub fn format_table(headers: &[(&str, Align)], rows: &[Vec<String>]) -> Vec<String> {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, (header, _))| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .chain([header.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let format_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(headers)
+            .zip(&widths)
+            .map(|((cell, (_, align)), &width)| match align {
+                Align::Left => format!("{:<width$}", cell, width = width),
+                Align::Right => format!("{:>width$}", cell, width = width),
+            })
+            .collect();
+        line.join("  ").trim_end().to_string()
+    };
+
+    let mut lines = vec![
+        format_row(headers.iter().map(|(header, _)| *header).collect()),
+        widths.iter().map(|&width| "-".repeat(width)).collect::<Vec<_>>().join("  "),
+    ];
+    lines.extend(rows.iter().map(|row| format_row(row.iter().map(String::as_str).collect())));
+    lines
+}
+
+// Lay `names` out in columns that fit `width`, filled top to bottom like `ls`
+pub fn format_columns(names: &[String], width: usize) -> Vec<String> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    let column_width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0) + 2;
+    let columns = (width / column_width).clamp(1, names.len());
+    let rows = names.len().div_ceil(columns);
+
+    (0..rows)
+        .map(|row| {
+            let mut line = String::new();
+            for column in 0..columns {
+                if let Some(name) = names.get(column * rows + row) {
+                    line.push_str(&format!("{:<width$}", name, width = column_width));
+                }
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+// `ls -l` style permission string, e.g. "drwxr-xr-x"
+#[cfg(unix)]
+fn mode_string(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let kind = if metadata.file_type().is_symlink() {
+        'l'
+    } else if metadata.is_dir() {
+        'd'
+    } else {
+        '-'
+    };
+    let mode = metadata.permissions().mode();
+    let mut result = String::from(kind);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    result
+}
+
+// Windows only exposes the read-only attribute
+#[cfg(not(unix))]
+fn mode_string(metadata: &fs::Metadata) -> String {
+    let kind = if metadata.is_dir() { 'd' } else { '-' };
+    let write = if metadata.permissions().readonly() { '-' } else { 'w' };
+    format!("{}r{}", kind, write)
+}
+
+impl ShellCommand for ListDir {
+    fn run(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(p) => Path::new(p),
+            None => Path::new("."),
+        };
+
+        let metadata = fs::symlink_metadata(path)?;
+        let (dir, entries) = if metadata.is_dir() {
+            info!("Contents of {}:", path.display());
+            (path, self.entries(path)?)
+        } else {
+            // A single file is listed on its own, like `ls file`
+            let name = path.display().to_string();
+            (Path::new(""), vec![(name, metadata)])
+        };
+
+        if wants_table(self.table) {
+            let rows: Vec<Vec<String>> = entries
+                .iter()
+                .map(|(name, metadata)| file_table_row(name.clone(), metadata))
+                .collect();
+            for line in format_table(&FILE_TABLE_HEADERS, &rows) {
+                outln!("{}", line);
+            }
+            return Ok(());
+        }
+
+        if self.long {
+            let size_width = entries
+                .iter()
+                .map(|(_, m)| utils::format_size(m.len(), self.human).len())
+                .max()
+                .unwrap_or(1);
+            for (name, metadata) in &entries {
+                outln!("{}", self.long_line(dir, name, metadata, size_width));
+            }
+            return Ok(());
+        }
+
+        let names: Vec<String> = entries.into_iter().map(|(name, _)| name).collect();
+        if self.one_per_line {
+            for name in &names {
+                outln!("{}", name);
+            }
+            return Ok(());
+        }
+
+        let width = terminal_size::terminal_size()
+            .map(|(terminal_size::Width(w), _)| w as usize)
+            .unwrap_or(DEFAULT_TERMINAL_WIDTH);
+        for line in format_columns(&names, width) {
+            outln!("{}", line);
+        }
+
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "List directory contents, in columns by default. Usage: list [-l] [-h] [-1] [-a] [--table] [directory]".to_string()
+    }
+}
+
+// Execute OS command with arguments
+#[derive(Default)]
+pub struct ExecuteCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    // Also write standard output to this file, like `tee`
+    pub log: Option<String>,
+    // Append to the log instead of truncating it
+    pub append: bool,
+}
+
+impl ExecuteCommand {
+    fn command(&self, via_cmd: bool) -> io::Result<Command> {
+        if via_cmd {
+            let mut cmd_line = vec![self.command.clone()];
+            cmd_line.extend(self.args.iter().cloned());
+            sanitize::validate_cmd_args(&cmd_line)?;
+            let mut command = Command::new("cmd");
+            command.args(["/C", &sanitize::cmd_join(&cmd_line)]);
+            Ok(command)
+        } else {
+            let mut command = Command::new(&self.command);
+            command.args(&self.args);
+            Ok(command)
+        }
+    }
+
+    // Stream the child's stdout to both the terminal and the log as it arrives
+    fn execute_logged(&self, log: &str) -> io::Result<()> {
+        use std::io::{Read, Write};
+
+        let mut log_file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(log)?;
+
+        let spawn = |via_cmd: bool| self.command(via_cmd)?.stdout(Stdio::piped()).spawn();
+        let mut child = match spawn(false) {
+            Err(e) if cfg!(windows) && e.kind() == io::ErrorKind::NotFound => spawn(true)?,
+            result => result?,
+        };
+
+        let mut child_stdout = child.stdout.take().expect("stdout is piped");
+        let mut stdout = context::stdout();
+        let mut buffer = [0u8; 8192];
+        loop {
+            let read = match child_stdout.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            stdout.write_all(&buffer[..read])?;
+            stdout.flush()?;
+            log_file.write_all(&buffer[..read])?;
+        }
+
+        Self::finished(child.wait()?);
+        Ok(())
+    }
+
+    // Collect the child's output when it is captured, without a terminal to read from
+    fn execute_captured(&self) -> io::Result<()> {
+        use std::io::Write;
+
+        let output = |via_cmd: bool| self.command(via_cmd)?.stdin(Stdio::null()).output();
+        let output = match output(false) {
+            Err(e) if cfg!(windows) && e.kind() == io::ErrorKind::NotFound => output(true)?,
+            result => result?,
+        };
+        context::stdout().write_all(&output.stdout)?;
+        context::stderr().write_all(&output.stderr)?;
+        Self::finished(output.status);
+        Ok(())
+    }
+
+    // The exit code is returned in the command's result; on the terminal a
+    // failure is also pointed out
+    fn finished(status: std::process::ExitStatus) {
+        context::set_exit_code(status.code().unwrap_or(1));
+        if !context::is_capturing() && !status.success() {
+            errln!("Command failed with exit code: {:?}", status.code());
+        }
+    }
+}
+
+impl ShellCommand for ExecuteCommand {
+    fn run(&self) -> io::Result<()> {
+        // Output only has to be captured when it is also logged. Otherwise the
+        // child shares our terminal, so interactive programs (python, vim, ...)
+        // can read input and draw as usual.
+        if let Some(log) = &self.log {
+            return self.execute_logged(log);
+        }
+        if context::is_capturing() {
+            return self.execute_captured();
+        }
+
+        let status = if cfg!(windows) {
+            // Spawn programs directly; only cmd.exe built-ins (dir, echo, ...) need cmd /C
+            match self.command(false)?.status() {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => self.command(true)?.status()?,
+                result => result?,
+            }
+        } else {
+            self.command(false)?.status()?
+        };
+
+        Self::finished(status);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Run a system command, optionally saving its output. Usage: run [--log <file>] <command> [args...]".to_string()
+    }
+
+    // Interactive programs (python, vim, ...) read input and draw as usual
+    fn needs_terminal(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Default)]
+pub struct CurrentPath {}
+
+impl ShellCommand for CurrentPath {
+    fn run(&self) -> io::Result<()> {
+        let current_dir = env::current_dir()?;
+        outln!("Current directory: {}", current_dir.display());
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Print current working directory. Usage: where_am_i".to_string()
+    }
+}
+
+// New command to show file contents
+#[derive(Default)]
+pub struct ShowFile {
+    pub paths: Vec<String>,
+}
+
+impl ShowFile {
+    // Read a file and transcode it to UTF-8 for display
+    fn read_text(path: &str) -> io::Result<String> {
+        let decoded = utils::decode_text(&fs::read(path)?);
+        if !decoded.reliable {
+            errln!(
+                "Warning: could not reliably detect the encoding of {}; showing it as {}",
+                path, decoded.encoding
+            );
+        } else if decoded.encoding != "UTF-8" {
+            info!("(decoded from {})", decoded.encoding);
+        }
+        Ok(decoded.text)
+    }
+
+    fn print_text(text: &str) {
+        out!("{}", text);
+        if !text.is_empty() && !text.ends_with('\n') {
+            outln!();
+        }
+    }
+}
+
+impl ShellCommand for ShowFile {
+    fn run(&self) -> io::Result<()> {
+        if let [path] = self.paths.as_slice() {
+            let text = Self::read_text(path)?;
+            outln!("--- Contents of {} ---", path);
+            Self::print_text(&text);
+            outln!("--- End of file ---");
+            return Ok(());
+        }
+        
+        // Several files: separate them with headers, like `head` does
+        for (i, path) in self.paths.iter().enumerate() {
+            if i > 0 {
+                outln!();
+            }
+            outln!("==> {} <==", path);
+            match Self::read_text(path) {
+                Ok(text) => Self::print_text(&text),
+                Err(e) => errln!("show: {}: {}", path, e),
+            }
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Display the contents of one or more files. Usage: show <file1> [file2 ...]".to_string()
+    }
+}
+
+// First and last lines of a file, for eyeballing large logs
+pub const PEEK_DEFAULT_LINES: usize = 5;
+
+#[derive(Default)]
+pub struct PeekFile {
+    pub path: String,
+    pub head: Option<usize>,
+    pub tail: Option<usize>,
+}
+
+pub struct Peek {
+    pub head: Vec<String>,
+    pub omitted: usize,
+    pub tail: Vec<String>,
+}
+
+impl PeekFile {
+    // Reads the head forwards and the tail backwards from the end, so only the
+    // middle of a large file is scanned, and only to count its lines
+    fn peek<R: io::Read + io::Seek>(reader: R, head: usize, tail: usize) -> io::Result<Peek> {
+        use io::{BufRead, Read, Seek, SeekFrom};
+
+        let mut reader = io::BufReader::new(reader);
+        let mut head_lines = Vec::new();
+        let mut line = Vec::new();
+        while head_lines.len() < head {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            head_lines.push(String::from_utf8_lossy(&line).trim_end_matches(['\n', '\r']).to_string());
+        }
+        let head_end = reader.stream_position()?;
+        let len = reader.seek(SeekFrom::End(0))?;
+
+        // Walk back over `tail` line breaks, not counting one that ends the file
+        let mut tail_start = if tail == 0 { len } else { head_end };
+        let mut remaining = tail;
+        let mut pos = len;
+        let mut chunk = vec![0u8; 8192];
+        'scan: while remaining > 0 && pos > head_end {
+            let size = (pos - head_end).min(chunk.len() as u64) as usize;
+            pos -= size as u64;
+            reader.seek(SeekFrom::Start(pos))?;
+            reader.read_exact(&mut chunk[..size])?;
+            for i in (0..size).rev() {
+                let offset = pos + i as u64;
+                if chunk[i] == b'\n' && offset + 1 != len {
+                    remaining -= 1;
+                    if remaining == 0 {
+                        tail_start = offset + 1;
+                        break 'scan;
+                    }
+                }
+            }
+        }
+
+        reader.seek(SeekFrom::Start(head_end))?;
+        let mut middle = (&mut reader).take(tail_start - head_end);
+        let mut omitted = 0;
+        loop {
+            let buffer = middle.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            omitted += buffer.iter().filter(|&&byte| byte == b'\n').count();
+            let consumed = buffer.len();
+            middle.consume(consumed);
+        }
+
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        let tail_lines = String::from_utf8_lossy(&rest).lines().map(str::to_string).collect();
+
+        Ok(Peek { head: head_lines, omitted, tail: tail_lines })
+    }
+}
+
+impl ShellCommand for PeekFile {
+    fn run(&self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        let peek = Self::peek(
+            file,
+            self.head.unwrap_or(PEEK_DEFAULT_LINES),
+            self.tail.unwrap_or(PEEK_DEFAULT_LINES),
+        )?;
+
+        for line in &peek.head {
+            outln!("{}", line);
+        }
+        if peek.omitted > 0 {
+            outln!("... ({} lines omitted) ...", peek.omitted);
+        }
+        for line in &peek.tail {
+            outln!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "Show the first and last lines of a file (default {} each). Usage: peek [--head N] [--tail N] [-n N] <file>",
+            PEEK_DEFAULT_LINES
+        )
+    }
+}
+
+// Number the lines of a file (or stdin) like GNU `nl`
+#[derive(Default)]
+pub struct NumberLines {
+    pub path: Option<String>,
+    // -ba: number blank lines too (the default is -bt, non-blank only)
+    pub all: bool,
+}
+
+impl NumberLines {
+    // Numbers are right-aligned in 6 columns and followed by a tab; unnumbered
+    // lines get the same indent, as GNU nl does
+    fn number<R: io::BufRead, W: io::Write>(reader: R, out: &mut W, all: bool) -> io::Result<()> {
+        let mut number = 0;
+        for line in reader.split(b'\n') {
+            let line = line?;
+            let text = String::from_utf8_lossy(&line);
+            let text = text.strip_suffix('\r').unwrap_or(&text);
+            if all || !text.trim().is_empty() {
+                number += 1;
+                writeln!(out, "{:>6}\t{}", number, text)?;
+            } else {
+                writeln!(out, "{:7}{}", "", text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ShellCommand for NumberLines {
+    fn run(&self) -> io::Result<()> {
+        let mut out = io::BufWriter::new(context::stdout());
+        let result = match self.path.as_deref() {
+            None | Some("-") => Self::number(io::stdin().lock(), &mut out, self.all),
+            Some(path) => Self::number(io::BufReader::new(File::open(path)?), &mut out, self.all),
+        };
+        match result.and_then(|_| io::Write::flush(&mut out)) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+
+    fn help(&self) -> String {
+        "Number the non-blank lines of a file, or all lines with -ba; reads stdin without a file. Usage: nl [-ba|-bt] [file]".to_string()
+    }
+}
+
+// Strip trailing whitespace and normalize the end of a text file
+#[derive(Default)]
+pub struct TrimFile {
+    pub path: String,
+    pub stdout: bool,
+    // Expand tabs to this tab width
+    pub tabs_to_spaces: Option<usize>,
+}
+
+impl TrimFile {
+    // Tabs advance to the next multiple of `width` columns
+    fn expand_tabs(line: &str, width: usize) -> String {
+        let mut expanded = String::with_capacity(line.len());
+        let mut column = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = width - column % width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            } else {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+        expanded
+    }
+
+    // Trailing whitespace goes from every line and blank lines from the end,
+    // leaving exactly one final newline. CRLF files keep their line endings.
+    fn normalize<R: io::BufRead>(mut reader: R, tabs_to_spaces: Option<usize>) -> io::Result<String> {
+        let mut lines = Vec::new();
+        let mut crlf = None;
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            crlf.get_or_insert(line.ends_with("\r\n"));
+            let trimmed = line.trim_end();
+            lines.push(match tabs_to_spaces {
+                Some(width) if width > 0 => Self::expand_tabs(trimmed, width).trim_end().to_string(),
+                _ => trimmed.to_string(),
+            });
+            line.clear();
+        }
+
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        if lines.is_empty() {
+            return Ok(String::new());
+        }
+        let newline = if crlf == Some(true) { "\r\n" } else { "\n" };
+        Ok(lines.join(newline) + newline)
+    }
+}
+
+impl ShellCommand for TrimFile {
+    fn run(&self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        let permissions = file.metadata()?.permissions();
+        let normalized = Self::normalize(io::BufReader::new(file), self.tabs_to_spaces)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => io::Error::new(e.kind(), format!("{} is not UTF-8 text", self.path)),
+                _ => e,
+            })?;
+
+        if self.stdout {
+            let mut out = io::BufWriter::new(context::stdout());
+            io::Write::write_all(&mut out, normalized.as_bytes())?;
+            return io::Write::flush(&mut out);
+        }
+
+        if fs::read(&self.path)? == normalized.as_bytes() {
+            info!("{} is already clean", self.path);
+            return Ok(());
+        }
+        utils::atomic_write(&self.path, normalized.as_bytes())?;
+        fs::set_permissions(&self.path, permissions)?;
+        info!("Trimmed {}", self.path);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Remove trailing whitespace and extra blank lines at the end of a file, in place or to stdout. Usage: trim [--stdout] [--tabs-to-spaces N] <file>".to_string()
+    }
+}
+
+// Report changes under a directory as they happen, until Ctrl-C
+#[derive(Default)]
+pub struct WatchDir {
+    pub path: String,
+    // --exec <command>: run after each burst of changes, as parsed (with
+    // aliases expanded) when watch-dir started
+    pub exec: Vec<String>,
+    pub exec_command: Option<Box<dyn ShellCommand>>,
+}
+
+// A burst of changes (e.g. an editor saving through a temporary file) ends
+// once nothing has changed for this long; --exec runs once per burst
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
+
+impl WatchDir {
+    // One line per path, e.g. `12:04:31 modified  src/main.rs`. The
+    // backends report different detail, so events are reduced to created,
+    // modified, removed and renamed; access events are ignored. Paths are
+    // shown relative to the watched directory.
+    fn describe(root: &Path, event: &notify::Event) -> Vec<String> {
+        use notify::event::{ModifyKind, RenameMode};
+        use notify::EventKind;
+
+        let kind = match event.kind {
+            EventKind::Create(_) => "created",
+            // Half of a rename whose other half is outside the watched tree
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => "removed",
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => "created",
+            EventKind::Modify(ModifyKind::Name(_)) => "renamed",
+            EventKind::Modify(_) => "modified",
+            EventKind::Remove(_) => "removed",
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => return Vec::new(),
+        };
+        let time = chrono::Local::now().format("%H:%M:%S");
+        let relative = |path: &PathBuf| path.strip_prefix(root).unwrap_or(path).display().to_string();
+
+        if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) = (event.kind, event.paths.as_slice()) {
+            return vec![format!("{} {:<9} {} -> {}", time, kind, relative(from), relative(to))];
+        }
+        event.paths.iter().map(|path| format!("{} {:<9} {}", time, kind, relative(path))).collect()
+    }
+
+    async fn watch(&self) -> io::Result<()> {
+        use notify::Watcher;
+
+        // Backends like FSEvents report resolved paths, so compare against the resolved root
+        let root = fs::canonicalize(&self.path)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("not a directory: {}", self.path)));
+        }
+
+        let (sender, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })
+        .map_err(io::Error::other)?;
+        watcher.watch(&root, notify::RecursiveMode::Recursive).map_err(io::Error::other)?;
+        info!("Watching {} for changes (Ctrl-C to stop)...", self.path);
+
+        loop {
+            let event = tokio::select! {
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+                event = events.recv() => event,
+            };
+            let Some(event) = event else {
+                return Ok(());
+            };
+
+            let mut renames = Vec::new();
+            let mut changed = self.report(&root, event, &mut renames);
+            // Collect the rest of the burst before running --exec
+            while let Ok(Some(event)) = tokio::time::timeout(WATCH_DEBOUNCE, events.recv()).await {
+                changed |= self.report(&root, event, &mut renames);
+            }
+            for event in renames {
+                for line in Self::describe(&root, &event) {
+                    outln!("{}", line);
+                }
+            }
+            if changed {
+                if let Some(command) = &self.exec_command {
+                    self.run_exec(command.as_ref());
+                }
+            }
+        }
+    }
+
+    // Print an event, returning whether it was a change worth reacting to.
+    // inotify reports a rename as its two halves and then as a whole, so the
+    // halves are held in `renames` until the burst ends and dropped if the
+    // whole arrives.
+    fn report(&self, root: &Path, event: notify::Result<notify::Event>, renames: &mut Vec<notify::Event>) -> bool {
+        use notify::event::{ModifyKind, RenameMode};
+        use notify::EventKind;
+
+        match event {
+            Ok(event) => {
+                match (event.kind, event.tracker()) {
+                    (EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::To)), Some(_)) => {
+                        renames.push(event);
+                        return true;
+                    }
+                    (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), Some(tracker)) => {
+                        renames.retain(|half| half.tracker() != Some(tracker));
+                    }
+                    _ => {}
+                }
+                let lines = Self::describe(root, &event);
+                for line in &lines {
+                    outln!("{}", line);
+                }
+                !lines.is_empty()
+            }
+            Err(e) => {
+                errln!("watch-dir: {}", e);
+                false
+            }
+        }
+    }
+
+    // --exec can be a built-in, an alias or a program; it is run as typed,
+    // never translated. A failure is reported and watching goes on.
+    fn run_exec(&self, command: &dyn ShellCommand) {
+        let result = command.execute();
+        let exit_code = result.exit_code;
+        if let Err(e) = result.forward() {
+            errln!("watch-dir: '{}' failed: {}", self.exec.join(" "), e);
+        } else if exit_code != 0 {
+            errln!("watch-dir: '{}' failed (exit code {})", self.exec.join(" "), exit_code);
+        }
+    }
+}
+
+impl ShellCommand for WatchDir {
+    fn run(&self) -> io::Result<()> {
+        block_on(self.watch())
+    }
+
+    fn help(&self) -> String {
+        "Print files created, modified, removed or renamed under a directory as it happens, until Ctrl-C; --exec runs a command after each burst of changes. Usage: watch-dir [path] [--exec <command>...]"
+            .to_string()
+    }
+
+    // Events are printed as they happen, until Ctrl-C
+    fn needs_terminal(&self) -> bool {
+        true
+    }
+}
+
+// Regex find-and-replace across a file, like `sed -i 's/pattern/replacement/g'`
+#[derive(Default)]
+pub struct ReplaceInFile {
+    pub path: String,
+    pub pattern: String,
+    pub replacement: String,
+    // --backup: keep the original as <file>.bak
+    pub backup: bool,
+    pub stdout: bool,
+    // --count: report how many substitutions were made
+    pub count: bool,
+    // --dry-run: show a diff of the changes instead of making them
+    pub dry_run: bool,
+}
+
+impl ReplaceInFile {
+    // The replacement in the regex crate's syntax. sed-style `\1` group
+    // references become `${1}`; `$1` and `${name}` are already understood.
+    fn replacement(&self) -> String {
+        let mut converted = String::with_capacity(self.replacement.len());
+        let mut chars = self.replacement.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some(digit)) if digit.is_ascii_digit() => {
+                    converted.push_str(&format!("${{{}}}", digit));
+                    chars.next();
+                }
+                ('\\', Some('\\')) => {
+                    converted.push('\\');
+                    chars.next();
+                }
+                _ => converted.push(c),
+            }
+        }
+        converted
+    }
+
+    // The new content and the number of substitutions made
+    fn substitute(&self, content: &str) -> io::Result<(String, usize)> {
+        let regex = regex::Regex::new(&self.pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid pattern: {}", e)))?;
+        let count = regex.find_iter(content).count();
+        Ok((regex.replace_all(content, self.replacement().as_str()).into_owned(), count))
+    }
+
+    // A unified diff of the change, with removed lines in red and added
+    // ones in green when `colored`
+    fn preview(&self, before: &str, after: &str, colored: bool) -> Vec<String> {
+        let paint = |color: &str, text: String| if colored { format!("{}{}\x1b[0m", color, text) } else { text };
+        let diff = similar::TextDiff::from_lines(before, after);
+
+        let mut lines = vec![
+            paint("\x1b[1m", format!("--- {}", self.path)),
+            paint("\x1b[1m", format!("+++ {} (after replace)", self.path)),
+        ];
+        for hunk in diff.unified_diff().context_radius(2).iter_hunks() {
+            lines.push(paint("\x1b[36m", hunk.header().to_string()));
+            for change in hunk.iter_changes() {
+                let text = change.value().trim_end_matches(['\n', '\r']);
+                lines.push(match change.tag() {
+                    similar::ChangeTag::Delete => paint("\x1b[31m", format!("-{}", text)),
+                    similar::ChangeTag::Insert => paint("\x1b[32m", format!("+{}", text)),
+                    similar::ChangeTag::Equal => format!(" {}", text),
+                });
+            }
+        }
+        lines
+    }
+}
+
+impl ShellCommand for ReplaceInFile {
+    fn run(&self) -> io::Result<()> {
+        let content = fs::read_to_string(&self.path).map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => io::Error::new(e.kind(), format!("{} is not UTF-8 text", self.path)),
+            _ => e,
+        })?;
+        let (replaced, count) = self.substitute(&content)?;
+        let report = format!("{} replacement{} in {}", count, if count == 1 { "" } else { "s" }, self.path);
+
+        if self.dry_run {
+            let colored = crate::current_config().ui.colored_output && context::stdout_is_terminal();
+            for line in self.preview(&content, &replaced, colored) {
+                outln!("{}", line);
+            }
+            outln!("{} (dry run; nothing was changed)", report);
+            return Ok(());
+        }
+
+        if self.stdout {
+            let mut out = io::BufWriter::new(context::stdout());
+            io::Write::write_all(&mut out, replaced.as_bytes())?;
+            io::Write::flush(&mut out)?;
+            // Kept off stdout so the output can be redirected to a file
+            if self.count {
+                errln!("{}", report);
+            }
+            return Ok(());
+        }
+
+        if replaced != content {
+            if self.backup {
+                fs::copy(&self.path, format!("{}.bak", self.path))?;
+            }
+            let permissions = fs::metadata(&self.path)?.permissions();
+            utils::atomic_write(&self.path, replaced.as_bytes())?;
+            fs::set_permissions(&self.path, permissions)?;
+        }
+        if self.count {
+            outln!("{}", report);
+        } else if count == 0 {
+            info!("No matches for '{}' in {}", self.pattern, self.path);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Replace every match of a regular expression in a file, in place or to stdout; --dry-run shows the changes as a diff instead. The replacement may refer to groups as $1, ${name} or \\1. Usage: replace [--dry-run] [--backup] [--stdout] [--count] <file> <pattern> <replacement>"
+            .to_string()
+    }
+}
+
+// New command to find files
+#[derive(Default)]
+pub struct FindFiles {
+    pub pattern: String,
+    pub path: Option<String>,
+    // --table: Name/Size/Modified columns with headers, on a terminal
+    pub table: bool,
+    // -0 / --null: end each path with a NUL byte instead of a newline, for `xargs -0`
+    pub null: bool,
+    // -L / --follow-symlinks: descend into symlinked directories
+    pub follow_symlinks: bool,
+    // --max-depth N: how many directory levels below the root to search
+    pub max_depth: Option<usize>,
+    // --exclude GLOB (repeatable): paths not searched, e.g. `target` or `.git`
+    pub excludes: Vec<String>,
+    // --no-ignore: also search paths that .gitignore files ignore
+    pub no_ignore: bool,
+    // -x / --one-file-system: don't descend into directories on other file systems
+    pub one_file_system: bool,
+    // --parallel / --jobs N: walk with this many threads
+    pub jobs: Option<usize>,
+    // --newer-than 2d / --older-than 1w: only files last modified less / more
+    // than this long ago
+    pub newer_than: Option<std::time::Duration>,
+    pub older_than: Option<std::time::Duration>,
+    // --larger-than 10M / --smaller-than 1K: only files above / below this many bytes
+    pub larger_than: Option<u64>,
+    pub smaller_than: Option<u64>,
+    // The pattern compiled on first use
+    pub matcher: std::sync::OnceLock<Result<globset::GlobMatcher, String>>,
+}
+
+impl FindFiles {
+    // The pattern as a glob (`*`, `?`, `[a-z]`, `{rs,toml}` and `**`). A
+    // pattern containing `/` must match the whole path below the search
+    // root, e.g. `src/**/*.rs`, and any other glob the whole file name; plain
+    // text without glob characters only has to occur somewhere in the name.
+    // Windows paths are matched case-insensitively.
+    fn matcher(&self) -> io::Result<&globset::GlobMatcher> {
+        let matcher = self.matcher.get_or_init(|| {
+            let glob = if self.matches_path() {
+                self.pattern.trim_start_matches("./").to_string()
+            } else if self.pattern.contains(['*', '?', '[', '{']) {
+                self.pattern.clone()
+            } else {
+                format!("*{}*", self.pattern)
+            };
+            globset::GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .case_insensitive(cfg!(windows))
+                .build()
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| e.kind().to_string())
+        });
+        matcher
+            .as_ref()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid pattern '{}': {}", self.pattern, e)))
+    }
+
+    fn matches_path(&self) -> bool {
+        self.pattern.contains('/')
+    }
+
+    // `relative` is the file's path below the search root
+    fn matches(&self, relative: impl AsRef<Path>) -> bool {
+        let relative = relative.as_ref();
+        let Ok(matcher) = self.matcher() else {
+            return false;
+        };
+        if self.matches_path() {
+            matcher.is_match(relative)
+        } else {
+            relative.file_name().is_some_and(|name| matcher.is_match(name))
+        }
+    }
+
+    // Whether a file passes the time and size filters, reading its metadata
+    // only if one of them is given. A file whose metadata can't be read never
+    // matches them.
+    fn metadata_matches(&self, now: std::time::SystemTime, metadata: impl FnOnce() -> Option<fs::Metadata>) -> bool {
+        let filters = [self.newer_than.is_some(), self.older_than.is_some(), self.larger_than.is_some(), self.smaller_than.is_some()];
+        if !filters.contains(&true) {
+            return true;
+        }
+        let Some(metadata) = metadata() else {
+            return false;
+        };
+        self.age_matches(now, metadata.modified().ok()) && self.size_matches(metadata.len())
+    }
+
+    // --newer-than and --older-than; a modification time in the future counts as now
+    fn age_matches(&self, now: std::time::SystemTime, modified: Option<std::time::SystemTime>) -> bool {
+        if self.newer_than.is_none() && self.older_than.is_none() {
+            return true;
+        }
+        let Some(modified) = modified else {
+            return false;
+        };
+        let age = now.duration_since(modified).unwrap_or_default();
+        self.newer_than.is_none_or(|limit| age < limit) && self.older_than.is_none_or(|limit| age > limit)
+    }
+
+    fn size_matches(&self, size: u64) -> bool {
+        self.larger_than.is_none_or(|limit| size > limit) && self.smaller_than.is_none_or(|limit| size < limit)
+    }
+
+    // Matching files under `root` as the walk finds them; unreadable entries
+    // are reported and skipped. walkdir reports a link back to an ancestor as
+    // an error instead of following it, so a loop ends its branch, not the search.
+    // An excluded directory is not descended into at all.
+    fn walk<'a>(&'a self, root: &'a Path) -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+        let now = std::time::SystemTime::now();
+        walkdir::WalkDir::new(root)
+            .follow_links(self.follow_symlinks)
+            .max_depth(self.max_depth.unwrap_or(usize::MAX))
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry({
+                let filter = walk::WalkFilter::new(root, &self.excludes, !self.no_ignore).one_file_system(self.one_file_system);
+                move |entry| !filter.skips(entry)
+            })
+            .take_while(|_| !context::is_interrupted())
+            .filter_map(|entry| entry.map_err(|e| errln!("find: {}", e)).ok())
+            .filter(move |entry| entry.file_type().is_file() && self.matches(entry.path().strip_prefix(root).unwrap_or(entry.path())))
+            .filter(move |entry| self.metadata_matches(now, || entry.metadata().ok()))
+    }
+
+    // One result as printed: the path and a newline, or with -0 the path's
+    // bytes exactly as the OS gave them and a NUL, so no file name can break it up
+    fn write_path(&self, out: &mut impl io::Write, path: &Path) -> io::Result<()> {
+        if self.null {
+            out.write_all(path.as_os_str().as_encoded_bytes())?;
+            out.write_all(b"\0")
+        } else {
+            writeln!(out, "{}", path.display())
+        }
+    }
+
+    fn walk_parallel(&self, root: &Path, jobs: usize) -> Vec<PathBuf> {
+        let filter = walk::WalkFilter::new(root, &self.excludes, !self.no_ignore).one_file_system(self.one_file_system);
+        let walk = walk::ParallelWalk { jobs, max_depth: self.max_depth, follow_links: self.follow_symlinks, filter: &filter };
+        let now = std::time::SystemTime::now();
+        let mut files = walk.files(root, "find");
+        files.retain(|path| {
+            self.matches(path.strip_prefix(root).unwrap_or(path)) && self.metadata_matches(now, || fs::metadata(path).ok())
+        });
+        files
+    }
+}
+
+impl ShellCommand for FindFiles {
+    fn run(&self) -> io::Result<()> {
+        let root = match &self.path {
+            Some(p) => PathBuf::from(p),
+            None => env::current_dir()?,
+        };
+        // A malformed pattern such as `[a-` is reported before searching
+        self.matcher()?;
+        
+        // With -0 the output is meant for another program, so it is only results
+        if !self.null {
+            info!("Searching for files matching '{}' in {}...", self.pattern, root.display());
+        }
+
+        let table = wants_table(self.table);
+        let mut rows = Vec::new();
+        // Plain results are written as the walk finds them, so a long search
+        // shows progress at once; a parallel walk prints them sorted when it
+        // is done. A closed pipe (e.g. `| head`) ends the search.
+        let files: Box<dyn Iterator<Item = PathBuf>> = match self.jobs {
+            Some(jobs) => Box::new(self.walk_parallel(&root, jobs).into_iter()),
+            None => Box::new(self.walk(&root).map(walkdir::DirEntry::into_path)),
+        };
+        let mut out = context::stdout();
+        for path in files {
+            if !table {
+                match self.write_path(&mut out, &path) {
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                    result => result?,
+                }
+                continue;
+            }
+            match fs::metadata(&path) {
+                Ok(metadata) => rows.push(file_table_row(path.display().to_string(), &metadata)),
+                Err(e) => errln!("find: {}", e),
+            }
+        }
+
+        if table {
+            for line in format_table(&FILE_TABLE_HEADERS, &rows) {
+                outln!("{}", line);
+            }
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Find files whose name matches a glob pattern (`*`, `?`, `[a-z]`, `{a,b}`), or contains a pattern without glob characters; a pattern with `/` such as `src/**/*.rs` must match the whole path. Symlinked directories are only searched with -L; --exclude skips matching paths, and paths ignored by .gitignore are skipped unless --no-ignore is given; -x stays on the directory's file system. --newer-than and --older-than keep files modified less or more than a duration such as 30m, 2d or 1w ago, and --larger-than and --smaller-than files above or below a size such as 10M. -0 ends each result with a NUL byte instead of a newline, for `xargs -0`. --parallel or --jobs N walks with several threads and prints the results sorted. Usage: find [--table|-0] [-L|--follow-symlinks] [--max-depth N] [--newer-than 2d] [--older-than 1w] [--larger-than 10M] [--smaller-than 1K] [--exclude GLOB]... [--no-ignore] [-x|--one-file-system] [--parallel|--jobs N] <pattern> [directory]"
+            .to_string()
+    }
+}
+
+// Profile a directory tree: file count and total size per extension
+#[derive(Default)]
+pub struct FileTypes {
+    pub path: Option<String>,
+    // --sort size: largest total size first instead of most files
+    pub by_size: bool,
+    // --top N: only the first N extensions
+    pub top: Option<usize>,
+}
+
+// Files without an extension are grouped under this name
+const NO_EXTENSION: &str = "(none)";
+
+impl FileTypes {
+    // Extensions are compared case-insensitively, so `JPG` and `jpg` count together
+    fn tally(root: &Path) -> HashMap<String, (usize, u64)> {
+        let mut types: HashMap<String, (usize, u64)> = HashMap::new();
+        let files = walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_map(|entry| entry.map_err(|e| errln!("filetypes: {}", e)).ok())
+            .filter(|entry| entry.file_type().is_file());
+        for entry in files {
+            let extension = entry
+                .path()
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| NO_EXTENSION.to_string());
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            let totals = types.entry(extension).or_default();
+            totals.0 += 1;
+            totals.1 += size;
+        }
+        types
+    }
+
+    // One line per extension in the requested order; ties go alphabetically
+    fn report(&self, types: HashMap<String, (usize, u64)>) -> Vec<String> {
+        let mut types: Vec<_> = types.into_iter().collect();
+        types.sort_by(|(a_ext, (a_count, a_size)), (b_ext, (b_count, b_size))| {
+            let order = if self.by_size {
+                b_size.cmp(a_size).then(b_count.cmp(a_count))
+            } else {
+                b_count.cmp(a_count).then(b_size.cmp(a_size))
+            };
+            order.then(a_ext.cmp(b_ext))
+        });
+        types
+            .into_iter()
+            .take(self.top.unwrap_or(usize::MAX))
+            .map(|(extension, (count, size))| {
+                let files = if count == 1 { "file" } else { "files" };
+                format!("{}: {} {}, {}", extension, count, files, utils::format_size(size, true))
+            })
+            .collect()
+    }
+}
+
+impl ShellCommand for FileTypes {
+    fn run(&self) -> io::Result<()> {
+        let root = match &self.path {
+            Some(p) => PathBuf::from(p),
+            None => env::current_dir()?,
+        };
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("not a directory: {}", root.display())));
+        }
+
+        let types = Self::tally(&root);
+        if types.is_empty() {
+            outln!("No files in {}", root.display());
+        }
+        for line in self.report(types) {
+            outln!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Count files and total sizes by extension, most files first (or largest with --sort size). Usage: filetypes [--sort count|size] [--top N] [directory]"
+            .to_string()
+    }
+}
+
+// Total size of each path, like `du -sh`
+#[derive(Default)]
+pub struct DiskUsage {
+    pub paths: Vec<String>,
+    // --exclude GLOB (repeatable): paths left out of the totals
+    pub excludes: Vec<String>,
+    // --no-ignore: also count paths that .gitignore files ignore
+    pub no_ignore: bool,
+    // -x / --one-file-system: leave out directories on other file systems
+    pub one_file_system: bool,
+    // --parallel / --jobs N: walk with this many threads
+    pub jobs: Option<usize>,
+}
+
+impl DiskUsage {
+    // Apparent size of the files under `root`; symlinks are not followed
+    fn total(&self, root: &Path) -> u64 {
+        let filter = walk::WalkFilter::new(root, &self.excludes, !self.no_ignore).one_file_system(self.one_file_system);
+        if let Some(jobs) = self.jobs {
+            let walk = walk::ParallelWalk { jobs, max_depth: None, follow_links: false, filter: &filter };
+            let files = walk.files(root, "du");
+            return walk::parallel_map(&files, jobs, |file| fs::symlink_metadata(file).map(|m| m.len()).unwrap_or(0)).into_iter().sum();
+        }
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| !filter.skips(entry))
+            .filter_map(|entry| entry.map_err(|e| errln!("du: {}", e)).ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.metadata().map(|metadata| metadata.len()).unwrap_or(0))
+            .sum()
+    }
+}
+
+impl ShellCommand for DiskUsage {
+    fn run(&self) -> io::Result<()> {
+        let paths = if self.paths.is_empty() { vec![".".to_string()] } else { self.paths.clone() };
+        for path in paths {
+            if !Path::new(&path).exists() {
+                errln!("du: {}: No such file or directory", path);
+                continue;
+            }
+            outln!("{}\t{}", utils::format_size(self.total(Path::new(&path)), true), path);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Show the total size of files and directories; --exclude leaves out matching paths, and paths ignored by .gitignore are left out unless --no-ignore is given; -x counts only the path's own file system. --parallel or --jobs N walks with several threads. Usage: du [--exclude GLOB]... [--no-ignore] [-x|--one-file-system] [--parallel|--jobs N] [path...]".to_string()
+    }
+}
+
+// The most recently modified files under a directory, newest first
+#[derive(Default)]
+pub struct RecentFiles {
+    pub path: Option<String>,
+    // --count N: how many files to list (RECENT_DEFAULT_COUNT if unset)
+    pub count: Option<usize>,
+    // --since 1h: only files changed within this long
+    pub since: Option<std::time::Duration>,
+    // -a / --all: include hidden files and directories such as .git
+    pub all: bool,
+}
+
+pub const RECENT_DEFAULT_COUNT: usize = 10;
+
+impl RecentFiles {
+    fn recent(&self, root: &Path, now: std::time::SystemTime) -> Vec<(PathBuf, fs::Metadata)> {
+        let cutoff = self.since.and_then(|since| now.checked_sub(since));
+        let mut files: Vec<_> = walkdir::WalkDir::new(root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| self.all || !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| entry.map_err(|e| errln!("recent: {}", e)).ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| Some((entry.path().to_path_buf(), entry.metadata().ok()?)))
+            .filter(|(_, metadata)| match (cutoff, metadata.modified()) {
+                (Some(cutoff), Ok(modified)) => modified >= cutoff,
+                _ => true,
+            })
+            .collect();
+
+        files.sort_by(|(a_path, a), (b_path, b)| {
+            let modified = |metadata: &fs::Metadata| metadata.modified().ok();
+            modified(b).cmp(&modified(a)).then(a_path.cmp(b_path))
+        });
+        files.truncate(self.count.unwrap_or(RECENT_DEFAULT_COUNT));
+        files
+    }
+}
+
+impl ShellCommand for RecentFiles {
+    fn run(&self) -> io::Result<()> {
+        let root = match &self.path {
+            Some(p) => PathBuf::from(p),
+            None => env::current_dir()?,
+        };
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("not a directory: {}", root.display())));
+        }
+
+        let files = self.recent(&root, std::time::SystemTime::now());
+        if files.is_empty() {
+            outln!("No files changed in {}", root.display());
+        }
+        for (path, metadata) in files {
+            let shown = path.strip_prefix(&root).unwrap_or(&path);
+            outln!("{}  {}", format_modified(&metadata), shown.display());
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "List the most recently modified files, newest first; hidden files only with -a. Usage: recent [--count N] [--since 1h] [-a] [directory]"
+            .to_string()
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Sha1,
+    Md5,
+}
+
+impl HashAlgo {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha256" => Some(HashAlgo::Sha256),
+            "sha1" => Some(HashAlgo::Sha1),
+            "md5" => Some(HashAlgo::Md5),
+            _ => None,
+        }
+    }
+
+    // Manifests don't name their algorithm, so it is told by the checksum's length
+    fn for_checksum(checksum: &str) -> Option<Self> {
+        match checksum.len() {
+            64 => Some(HashAlgo::Sha256),
+            40 => Some(HashAlgo::Sha1),
+            32 => Some(HashAlgo::Md5),
+            _ => None,
+        }
+    }
+
+    // Hex digest of everything `reader` yields, read in chunks so large files
+    // never have to fit in memory
+    fn digest(&self, reader: impl io::Read) -> io::Result<String> {
+        fn hex<D: sha2::Digest>(mut reader: impl io::Read) -> io::Result<String> {
+            let mut hasher = D::new();
+            let mut buffer = vec![0u8; 64 * 1024];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => hasher.update(&buffer[..read]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+        }
+
+        match self {
+            HashAlgo::Sha256 => hex::<sha2::Sha256>(reader),
+            HashAlgo::Sha1 => hex::<sha1::Sha1>(reader),
+            HashAlgo::Md5 => hex::<md5::Md5>(reader),
+        }
+    }
+}
+
+// Print file checksums like `sha256sum`, or verify them against a manifest
+#[derive(Default)]
+pub struct HashFiles {
+    pub paths: Vec<String>,
+    pub algo: HashAlgo,
+    // --check <manifest>: verify the `<checksum>  <file>` lines in it
+    pub check: Option<String>,
+    // --parallel / --jobs N: hash this many files at once
+    pub jobs: Option<usize>,
+}
+
+impl HashFiles {
+    // Check each manifest line, printing `file: OK` or `file: FAILED`; returns
+    // how many lines failed
+    fn verify(manifest: &str, base: &Path) -> io::Result<usize> {
+        let mut failed = 0;
+        for line in manifest.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let not_a_checksum = || io::Error::new(io::ErrorKind::InvalidData, format!("not a checksum line: {}", line));
+            let (checksum, file) = line.split_once(char::is_whitespace).ok_or_else(not_a_checksum)?;
+            let algo = HashAlgo::for_checksum(checksum).ok_or_else(not_a_checksum)?;
+            // `sha256sum` marks files hashed in binary mode with a `*`
+            let file = file.trim_start().trim_start_matches('*');
+
+            let result = File::open(base.join(file)).and_then(|contents| algo.digest(contents));
+            match result {
+                Ok(actual) if actual.eq_ignore_ascii_case(checksum) => outln!("{}: OK", file),
+                Ok(_) => {
+                    outln!("{}: FAILED", file);
+                    failed += 1;
+                }
+                Err(e) => {
+                    outln!("{}: FAILED ({})", file, e);
+                    failed += 1;
+                }
+            }
+        }
+        Ok(failed)
+    }
+}
+
+impl ShellCommand for HashFiles {
+    fn run(&self) -> io::Result<()> {
+        if let Some(manifest) = &self.check {
+            // Paths in a manifest are relative to the manifest itself
+            let base = Path::new(manifest).parent().unwrap_or(Path::new(""));
+            let failed = Self::verify(&fs::read_to_string(manifest)?, base)?;
+            if failed > 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} file(s) did not match", failed)));
+            }
+            return Ok(());
+        }
+
+        let Some(jobs) = self.jobs else {
+            for path in &self.paths {
+                let checksum = self.algo.digest(File::open(path)?)?;
+                outln!("{}  {}", checksum, path);
+            }
+            return Ok(());
+        };
+
+        // Files are hashed in parallel but printed in the order given
+        let checksums = walk::parallel_map(&self.paths, jobs, |path| self.algo.digest(File::open(path)?));
+        for (path, checksum) in self.paths.iter().zip(checksums) {
+            outln!("{}  {}", checksum?, path);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Print file checksums (SHA-256 unless --algo says sha1 or md5), or verify a `sha256sum`-style manifest with --check. --parallel or --jobs N hashes several files at once. Usage: hash [--algo sha256|sha1|md5] [--parallel|--jobs N] <file>... | hash --check <manifest>"
+            .to_string()
+    }
+}
+
+// The name of part `number` (counting from 1) of a file split by `split`
+fn part_name(file: &str, number: usize) -> String {
+    format!("{}.part{:03}", file, number)
+}
+
+// Split a file into numbered parts of at most `chunk_size` bytes
+#[derive(Default)]
+pub struct SplitFile {
+    pub path: String,
+    pub chunk_size: u64,
+}
+
+impl SplitFile {
+    // Returns the number of parts written. An empty file still gets one
+    // (empty) part, so joining always gives the file back.
+    fn split(&self) -> io::Result<usize> {
+        use std::io::{BufRead, Read, Write};
+
+        let first = part_name(&self.path, 1);
+        if Path::new(&first).exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", first)));
+        }
+
+        let mut input = io::BufReader::new(File::open(&self.path)?);
+        let mut parts = 0;
+        while parts == 0 || !input.fill_buf()?.is_empty() {
+            parts += 1;
+            let mut part = io::BufWriter::new(File::create(part_name(&self.path, parts))?);
+            io::copy(&mut (&mut input).take(self.chunk_size), &mut part)?;
+            part.flush()?;
+        }
+        Ok(parts)
+    }
+}
+
+impl ShellCommand for SplitFile {
+    fn run(&self) -> io::Result<()> {
+        let parts = self.split()?;
+        info!("Split {} into {} part(s): {} ...", self.path, parts, part_name(&self.path, 1));
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Split a file into numbered parts (file.part001, ...) of at most the given size, e.g. 100M. Usage: split <file> <chunk_size>".to_string()
+    }
+}
+
+// Put the parts written by `split` back together
+#[derive(Default)]
+pub struct JoinFiles {
+    pub prefix: String,
+    pub output: String,
+}
+
+impl JoinFiles {
+    // The parts of `prefix` in order, stopping at the first gap
+    fn parts(&self) -> Vec<String> {
+        (1..).map(|number| part_name(&self.prefix, number)).take_while(|part| Path::new(part).is_file()).collect()
+    }
+}
+
+impl ShellCommand for JoinFiles {
+    fn run(&self) -> io::Result<()> {
+        use std::io::Write;
+
+        let parts = self.parts();
+        if parts.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no parts found ({})", part_name(&self.prefix, 1))));
+        }
+        let Some(output) = overwrite::resolve_destination(Path::new(&parts[0]), Path::new(&self.output))? else {
+            return Ok(());
+        };
+
+        let mut joined = io::BufWriter::new(File::create(&output)?);
+        for part in &parts {
+            io::copy(&mut File::open(part)?, &mut joined)?;
+        }
+        joined.flush()?;
+        info!("Joined {} part(s) into {}", parts.len(), output.display());
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Join the parts written by split back into one file. Usage: join <original_file_name> <output>".to_string()
+    }
+}
+
+// How `grep` treats files that look binary
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum BinaryMode {
+    // Skip binary files entirely
+    #[default]
+    Skip,
+    // Search them, but only report whether they match
+    Include,
+    // Treat every file as text and print matching lines
+    Text,
+}
+
+// Search file contents for a literal pattern
+#[derive(Default)]
+pub struct GrepFiles {
+    pub pattern: String,
+    pub paths: Vec<String>,
+    pub ignore_case: bool,
+    pub binary: BinaryMode,
+    // --parallel / --jobs N: search files on this many threads
+    pub jobs: Option<usize>,
+    // -B N / -A N (-C N sets both): lines of context before and after each match
+    pub before: usize,
+    pub after: usize,
+}
+
+// Separates groups of matches that are not next to each other when context is shown
+const GREP_GROUP_SEPARATOR: &str = "--";
+
+impl GrepFiles {
+    // The output lines for the matches in `path`. As in GNU grep, matching
+    // lines read `file:12:text` and context lines `file-11-text`, dimmed
+    // when `colored`.
+    fn matching_lines(&self, path: &Path, show_name: bool, colored: bool) -> io::Result<Vec<String>> {
+        let bytes = fs::read(path)?;
+        let is_binary = utils::looks_binary(&bytes);
+
+        if is_binary && self.binary == BinaryMode::Skip {
+            return Ok(Vec::new());
+        }
+
+        let pattern = if self.ignore_case { self.pattern.to_lowercase() } else { self.pattern.clone() };
+        let contents = String::from_utf8_lossy(&bytes);
+        let file_lines: Vec<&str> = contents.lines().collect();
+        let format = |number: usize, separator: char| {
+            let text = file_lines[number];
+            let line = if show_name {
+                format!("{}{}{}{}{}", path.display(), separator, number + 1, separator, text)
+            } else {
+                format!("{}{}{}", number + 1, separator, text)
+            };
+            if colored && separator == '-' { format!("\x1b[2m{}\x1b[0m", line) } else { line }
+        };
+
+        let mut lines = Vec::new();
+        // The last line printed, and how many lines of after-context are still due
+        let mut last_printed: Option<usize> = None;
+        let mut after_due = 0;
+
+        for (number, line) in file_lines.iter().enumerate() {
+            let found = if self.ignore_case {
+                line.to_lowercase().contains(&pattern)
+            } else {
+                line.contains(&pattern)
+            };
+            if !found {
+                if after_due > 0 {
+                    lines.push(format(number, '-'));
+                    last_printed = Some(number);
+                    after_due -= 1;
+                }
+                continue;
+            }
+
+            if is_binary && self.binary == BinaryMode::Include {
+                return Ok(vec![format!("Binary file {} matches", path.display())]);
+            }
+
+            let first = number.saturating_sub(self.before).max(last_printed.map_or(0, |last| last + 1));
+            if last_printed.is_some_and(|last| first > last + 1) {
+                lines.push(self.separator(colored));
+            }
+            lines.extend((first..number).map(|context| format(context, '-')));
+            lines.push(format(number, ':'));
+            last_printed = Some(number);
+            after_due = self.after;
+        }
+
+        Ok(lines)
+    }
+
+    fn separator(&self, colored: bool) -> String {
+        if colored { format!("\x1b[2m{}\x1b[0m", GREP_GROUP_SEPARATOR) } else { GREP_GROUP_SEPARATOR.to_string() }
+    }
+
+    // Print one file's matches, returning whether there were any. With
+    // context, groups from different files are separated too.
+    fn print_matches(&self, lines: Vec<String>, printed: &mut bool, colored: bool) -> bool {
+        if lines.is_empty() {
+            return false;
+        }
+        if *printed && self.before + self.after > 0 {
+            outln!("{}", self.separator(colored));
+        }
+        for line in lines {
+            outln!("{}", line);
+        }
+        *printed = true;
+        true
+    }
+
+    // Search in parallel, then print each file's matches in walk order
+    fn search_parallel(&self, root: &Path, jobs: usize, show_name: bool, printed: &mut bool, colored: bool) -> bool {
+        let filter = walk::WalkFilter::new(root, &[], false);
+        let walk = walk::ParallelWalk { jobs, max_depth: None, follow_links: false, filter: &filter };
+        let files = walk.files(root, "grep");
+        let mut matched = false;
+        let results = walk::parallel_map(&files, jobs, |path| self.matching_lines(path, show_name, colored));
+        for (path, result) in files.iter().zip(results) {
+            match result {
+                Ok(lines) => matched |= self.print_matches(lines, printed, colored),
+                Err(e) => errln!("grep: {}: {}", path.display(), e),
+            }
+        }
+        matched
+    }
+}
+
+impl ShellCommand for GrepFiles {
+    fn run(&self) -> io::Result<()> {
+        let paths = if self.paths.is_empty() {
+            vec![".".to_string()]
+        } else {
+            self.paths.clone()
+        };
+        // Prefix lines with the file name unless exactly one file was given
+        let show_name = paths.len() > 1 || Path::new(&paths[0]).is_dir();
+        let colored = crate::current_config().ui.colored_output && context::stdout_is_terminal();
+        let mut matched = false;
+        let mut printed = false;
+
+        for root in &paths {
+            if let Some(jobs) = self.jobs {
+                matched |= self.search_parallel(Path::new(root), jobs, show_name, &mut printed, colored);
+                continue;
+            }
+            for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+                if context::is_interrupted() {
+                    return Ok(());
+                }
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        errln!("grep: {}", e);
+                        continue;
+                    }
+                };
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+
+                match self.matching_lines(entry.path(), show_name, colored) {
+                    Ok(lines) => matched |= self.print_matches(lines, &mut printed, colored),
+                    Err(e) => errln!("grep: {}: {}", entry.path().display(), e),
+                }
+            }
+        }
+
+        if !matched {
+            outln!("No matches for '{}'", self.pattern);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Search file contents for text, skipping binary files. -A, -B and -C show N lines of context after, before or around each match. --parallel or --jobs N searches several files at once. Usage: grep [-i] [--binary|--text] [-A N] [-B N] [-C N] [--parallel|--jobs N] <pattern> [path...]".to_string()
+    }
+}
+
+// New command to compress files into a zip archive
+#[derive(Default)]
+pub struct CompressFiles {
+    pub source: String,
+    pub destination: String,
+    // --strip-root: store a directory's contents at the top of the archive
+    // instead of inside a folder named after it
+    pub strip_root: bool,
+    // --exclude GLOB (repeatable): paths left out of the archive
+    pub excludes: Vec<String>,
+    // --no-ignore: also archive paths that .gitignore files ignore
+    pub no_ignore: bool,
+    // -x / --one-file-system: leave out directories on other file systems
+    pub one_file_system: bool,
+}
+
+impl CompressFiles {
+    // The archiver invocation. Stripping the root runs it from inside the
+    // source directory, so the destination is made absolute first. When
+    // paths are filtered, zip reads the ones to store from stdin (see
+    // `archive_entries`). Compress-Archive cannot filter, so on Windows the
+    // whole source is archived.
+    fn archive_command(&self) -> io::Result<Command> {
+        let destination = env::current_dir()?.join(&self.destination);
+
+        let command = if cfg!(windows) {
+            let source = if self.strip_root {
+                Path::new(&self.source).join("*").display().to_string()
+            } else {
+                self.source.clone()
+            };
+            let script = format!(
+                "Compress-Archive -Path {} -DestinationPath {} -Force",
+                sanitize::ps_quote(&source),
+                sanitize::ps_quote(&destination.display().to_string())
+            );
+            let mut command = Command::new("powershell");
+            command.args(["-Command", &script]);
+            command
+        } else {
+            let mut command = Command::new("zip");
+            if self.filters() {
+                command.arg(&destination).arg("-@");
+            } else if self.strip_root {
+                command.arg("-r").arg(&destination).arg(".");
+            } else {
+                command.arg("-r").arg(&destination).arg(&self.source);
+            }
+            if self.strip_root {
+                command.current_dir(&self.source);
+            }
+            command
+        };
+        Ok(command)
+    }
+
+    // Whether anything under the source is left out of the archive
+    fn filters(&self) -> bool {
+        !self.excludes.is_empty() || !self.no_ignore || self.one_file_system
+    }
+
+    // Everything under the source that is neither excluded nor ignored, as
+    // zip should store it. A skipped directory is left out with its contents.
+    fn archive_entries(&self) -> Vec<String> {
+        let root = Path::new(&self.source);
+        let filter = walk::WalkFilter::new(root, &self.excludes, !self.no_ignore).one_file_system(self.one_file_system);
+        walkdir::WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !filter.skips(entry))
+            .filter_map(|entry| entry.map_err(|e| errln!("compress: {}", e)).ok())
+            .filter_map(|entry| {
+                let path = if self.strip_root { entry.path().strip_prefix(root).ok()? } else { entry.path() };
+                (!path.as_os_str().is_empty()).then(|| path.display().to_string())
+            })
+            .collect()
+    }
+}
+
+impl ShellCommand for CompressFiles {
+    fn run(&self) -> io::Result<()> {
+        // The archive would otherwise try to include itself while being written
+        if Path::new(&self.source).is_dir() && utils::is_within(&self.destination, &self.source)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the archive '{}' would be inside '{}', which is being compressed; write it elsewhere",
+                    self.destination, self.source
+                ),
+            ));
+        }
+
+        if self.strip_root && !Path::new(&self.source).is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("--strip-root needs a directory to compress, and '{}' is not one", self.source),
+            ));
+        }
+
+        if cfg!(windows) && (!self.excludes.is_empty() || self.one_file_system) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "--exclude and --one-file-system need zip; PowerShell's Compress-Archive cannot skip paths",
+            ));
+        }
+
+        info!("Compressing {} to {}...", self.source, self.destination);
+        
+        // PowerShell's Compress-Archive on Windows, zip elsewhere
+        let mut command = self.archive_command()?;
+        let output = if cfg!(windows) || !self.filters() {
+            command.output()?
+        } else {
+            let mut zip = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+            if let Some(mut stdin) = zip.stdin.take() {
+                io::Write::write_all(&mut stdin, self.archive_entries().join("\n").as_bytes())?;
+            }
+            zip.wait_with_output()?
+        };
+        print_output(output);
+        
+        info!("Compression complete.");
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Compress files into a zip archive. A directory is stored as a folder in the archive; --strip-root stores its contents at the top instead, and --exclude leaves out matching paths. Paths ignored by .gitignore are left out unless --no-ignore is given, and -x leaves out directories on other file systems. Usage: compress [--strip-root] [--exclude GLOB]... [--no-ignore] [-x|--one-file-system] <source> <destination>".to_string()
+    }
+}
+
+// Add new commands for aliases
+#[derive(Default)]
+pub struct AliasCommand {
+    pub name: Option<String>,
+    pub command: Option<String>,
+}
+
+impl ShellCommand for AliasCommand {
+    fn run(&self) -> std::io::Result<()> {
+        let alias_manager = crate::AliasManager::new()?;
+        
+        match (&self.name, &self.command) {
+            (Some(name), Some(command)) => {
+                // Add or update alias
+                if !crate::AliasManager::confirm_dangerous(name, command)? {
+                    outln!("Alias '{}' not created", name);
+                    return Ok(());
+                }
+                let mut manager = alias_manager;
+                manager.add_alias(name.clone(), command.clone())?;
+                outln!("Alias '{}' created for '{}'", name, command);
+            }
+            (None, None) => {
+                // List all aliases
+                alias_manager.list_aliases();
+            }
+            _ => {
+                errln!("Error: alias requires both name and command or no arguments");
+            }
+        }
+        
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Create, list, edit or rename aliases. Usage: alias [name command | --edit <name> | --rename <old> <new>]".to_string()
+    }
+}
+
+// Edit an alias's command in place, starting from its current value
+#[derive(Default)]
+pub struct EditAlias {
+    pub name: String,
+}
+
+impl ShellCommand for EditAlias {
+    fn run(&self) -> io::Result<()> {
+        let mut alias_manager = crate::AliasManager::new()?;
+        let current = match alias_manager.get_alias(&self.name) {
+            Some(command) => command.clone(),
+            None => {
+                outln!("No such alias: {}", self.name);
+                return Ok(());
+            }
+        };
+
+        let mut editor = rustyline::DefaultEditor::new().map_err(io::Error::other)?;
+        let edited = match editor.readline_with_initial(&format!("{} = ", self.name), (&current, "")) {
+            Ok(line) => line.trim().to_string(),
+            Err(rustyline::error::ReadlineError::Interrupted | rustyline::error::ReadlineError::Eof) => {
+                outln!("Edit cancelled.");
+                return Ok(());
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        };
+
+        if edited.is_empty() || edited == current {
+            outln!("Alias '{}' unchanged", self.name);
+            return Ok(());
+        }
+        if !crate::AliasManager::confirm_dangerous(&self.name, &edited)? {
+            outln!("Alias '{}' unchanged", self.name);
+            return Ok(());
+        }
+        alias_manager.add_alias(self.name.clone(), edited.clone())?;
+        outln!("Alias '{}' updated to '{}'", self.name, edited);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Edit an alias's command at the prompt. Usage: alias --edit <name>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct RenameAlias {
+    pub old: String,
+    pub new: String,
+}
+
+impl ShellCommand for RenameAlias {
+    fn run(&self) -> io::Result<()> {
+        let mut alias_manager = crate::AliasManager::new()?;
+
+        if alias_manager.rename_alias(&self.old, &self.new)? {
+            outln!("Alias '{}' renamed to '{}'", self.old, self.new);
+        } else {
+            outln!("No such alias: {}", self.old);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Rename an alias, keeping its command. Usage: alias --rename <old> <new>".to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct UnaliasCommand {
+    pub name: String,
+}
+
+impl ShellCommand for UnaliasCommand {
+    fn run(&self) -> std::io::Result<()> {
+        let mut alias_manager = crate::AliasManager::new()?;
+        
+        match alias_manager.remove_alias(&self.name) {
+            Ok(true) => outln!("Alias '{}' removed", self.name),
+            Ok(false) => outln!("No such alias: {}", self.name),
+            Err(e) => return Err(e),
+        }
+        
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Remove an alias. Usage: unalias <name>".to_string()
+    }
+}
+
+// Set environment variables for this session and the commands it runs
+#[derive(Default)]
+pub struct ExportVar {
+    pub assignments: Vec<(String, String)>,
+}
+
+impl ShellCommand for ExportVar {
+    fn run(&self) -> io::Result<()> {
+        for (name, value) in &self.assignments {
+            env::set_var(name, value);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Set environment variables for the rest of the session. Usage: export NAME=value [NAME=value...]".to_string()
+    }
+}
+
+// Report how each name would be interpreted, like bash's `type`
+#[derive(Default)]
+pub struct TypeCommand {
+    pub names: Vec<String>,
+}
+
+impl TypeCommand {
+    // Follows the same precedence as dispatch: aliases, then built-ins, then PATH.
+    // A leading backslash bypasses aliases, as it does when running the command.
+    fn describe(name: &str, aliases: &crate::AliasManager) -> String {
+        let (lookup, escaped) = match name.strip_prefix('\\') {
+            Some(unescaped) => (unescaped, true),
+            None => (name, false),
+        };
+        let builtin = registry().get(lookup).is_some();
+
+        if !escaped {
+            if let Some(command) = aliases.get_alias(lookup) {
+                let shadowed = if builtin { " (shadowing the built-in)" } else { "" };
+                return format!("{} is aliased to `{}`{}", name, command, shadowed);
+            }
+        }
+        if builtin {
+            return format!("{} is a rustshell built-in", name);
+        }
+        match utils::find_in_path(lookup) {
+            Some(path) => format!("{} is {}", name, path.display()),
+            None => format!("{}: not found", name),
+        }
+    }
+}
+
+impl ShellCommand for TypeCommand {
+    fn run(&self) -> io::Result<()> {
+        let aliases = crate::AliasManager::new()?;
+        for name in &self.names {
+            outln!("{}", Self::describe(name, &aliases));
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Show whether a name is an alias, a built-in or a system command. Usage: type <name> [name...]".to_string()
+    }
+}
+
+// Commands run one after another, e.g. the iterations of `repeat` and `for`.
+// Stops at the first command that fails.
+#[derive(Default)]
+pub struct CommandSequence {
+    pub commands: Vec<Box<dyn ShellCommand>>,
+}
+
+impl ShellCommand for CommandSequence {
+    fn run(&self) -> io::Result<()> {
+        for command in &self.commands {
+            // Ctrl-C stops a loop between iterations as well as within one
+            if context::is_interrupted() {
+                break;
+            }
+            command.execute().forward()?;
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Run each command in turn.".to_string()
+    }
+
+    fn needs_terminal(&self) -> bool {
+        self.commands.iter().any(|command| command.needs_terminal())
+    }
+}
+
+// Command for pipeline execution
+#[derive(Default)]
+pub struct PipeCommand {
+    pub commands: Vec<Vec<String>>,
+}
+
+impl ShellCommand for PipeCommand {
+    fn run(&self) -> std::io::Result<()> {
+        if self.commands.len() < 2 {
+            errln!("Error: pipe requires at least two commands");
+            return Ok(());
+        }
+        
+        // Setup for piping
+        let mut previous_stdout = None;
+        
+        for (i, cmd_args) in self.commands.iter().enumerate() {
+            if cmd_args.is_empty() {
+                errln!("Error: empty command in pipeline");
+                return Ok(());
+            }
+            
+            let is_last = i == self.commands.len() - 1;
+            
+            // Create the command
+            let mut cmd = if cfg!(windows) {
+                sanitize::validate_cmd_args(cmd_args)?;
+                let mut command = std::process::Command::new("cmd");
+                command.args(["/C", &sanitize::cmd_join(cmd_args)]);
+                command
+            } else {
+                let mut command = std::process::Command::new(&cmd_args[0]);
+                if cmd_args.len() > 1 {
+                    command.args(&cmd_args[1..]);
+                }
+                command
+            };
+            
+            // Setup stdin from previous command's stdout if available
+            if let Some(stdout) = previous_stdout {
+                cmd.stdin(stdout);
+            } else if context::is_background() {
+                cmd.stdin(Stdio::null());
+            }
+            
+            // Setup stdout for piping to next command or capturing output
+            if !is_last {
+                cmd.stdout(Stdio::piped());
+            }
+            
+            // Execute the command
+            let mut child = cmd.spawn()?;
+            
+            // Get stdout for the next command in the pipeline
+            previous_stdout = if !is_last {
+                child.stdout.take()
+            } else {
+                None
+            };
+            
+            // If it's the last command, wait for it to finish
+            if is_last {
+                let status = child.wait()?;
+                if !status.success() {
+                    errln!("Pipeline command failed with exit code: {:?}", status.code());
+                }
+            }
+        }
+        
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Execute commands in a pipeline. Usage: pipe 'cmd1' 'cmd2' ...".to_string()
+    }
+
+    fn needs_terminal(&self) -> bool {
+        true
+    }
+}
+
+// Open the system clipboard, explaining the usual reason when there is none
+fn system_clipboard() -> io::Result<arboard::Clipboard> {
+    arboard::Clipboard::new().map_err(|e| {
+        io::Error::other(format!(
+            "clipboard unavailable ({}); on Linux a running X11 or Wayland session is required",
+            e
+        ))
+    })
+}
+
+// Put a file's contents, or a command's output, on the system clipboard
+#[derive(Default)]
+pub struct CopyToClipboard {
+    pub path: Option<String>,
+    // Command (and arguments) whose standard output is copied instead of a file
+    pub command: Vec<String>,
+}
+
+impl ShellCommand for CopyToClipboard {
+    fn run(&self) -> io::Result<()> {
+        let (text, source) = match (&self.path, self.command.split_first()) {
+            (Some(path), _) => (fs::read_to_string(path)?, path.clone()),
+            (None, Some((program, args))) => {
+                let output = Command::new(program).args(args).stderr(Stdio::inherit()).output()?;
+                (String::from_utf8_lossy(&output.stdout).to_string(), format!("output of '{}'", self.command.join(" ")))
+            }
+            (None, None) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "nothing to copy")),
+        };
+
+        system_clipboard()?
+            .set_text(text.clone())
+            .map_err(|e| io::Error::other(format!("could not write to the clipboard: {}", e)))?;
+        info!("Copied {} ({} bytes) to the clipboard.", source, text.len());
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Copy a file, or a command's output, to the clipboard. Usage: copy-to-clipboard <file> | --run <command> [args...]".to_string()
+    }
+}
+
+// Print the clipboard, or write it to a file
+#[derive(Default)]
+pub struct PasteClipboard {
+    pub path: Option<String>,
+}
+
+impl ShellCommand for PasteClipboard {
+    fn run(&self) -> io::Result<()> {
+        let text = system_clipboard()?
+            .get_text()
+            .map_err(|e| io::Error::other(format!("could not read text from the clipboard: {}", e)))?;
+
+        match &self.path {
+            Some(path) => {
+                fs::write(path, &text)?;
+                info!("Wrote {} bytes from the clipboard to {}.", text.len(), path);
+            }
+            None => outln!("{}", text),
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Print the clipboard, or write it to a file. Usage: paste [> <file>]".to_string()
+    }
+}
+
+// Open a file or URL with the desktop's default application
+#[derive(Default)]
+pub struct OpenPath {
+    pub target: String,
+}
+
+impl OpenPath {
+    fn is_url(&self) -> bool {
+        self.target.contains("://") || self.target.starts_with("mailto:")
+    }
+
+    // The platform's launcher: `start` on Windows, `open` on macOS and
+    // `xdg-open` elsewhere
+    fn launcher(&self) -> Command {
+        let mut command = if cfg!(windows) {
+            // The empty argument is the window title `start` would otherwise
+            // take a quoted path for
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        } else if cfg!(target_os = "macos") {
+            Command::new("open")
+        } else {
+            Command::new("xdg-open")
+        };
+        command.arg(&self.target);
+        command
+    }
+}
+
+impl ShellCommand for OpenPath {
+    fn run(&self) -> io::Result<()> {
+        if !self.is_url() && !Path::new(&self.target).exists() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{}: No such file or directory", self.target)));
+        }
+
+        let mut launcher = self.launcher();
+        let program = launcher.get_program().to_string_lossy().to_string();
+        let status = launcher.stdout(Stdio::null()).stderr(Stdio::null()).status().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                format!("{} is not installed, so there is no way to open files from here", program),
+            ),
+            _ => e,
+        })?;
+        if !status.success() {
+            return Err(io::Error::other(format!("no application is set up to open {}", self.target)));
+        }
+        info!("Opened {}", self.target);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Open a file or URL with its default application. Usage: open <path|url>".to_string()
+    }
+}
+
+// Summarize a text file with the configured LLM
+#[derive(Default)]
+pub struct SummarizeFile {
+    pub path: String,
+}
+
+// Room for a summary; translations use a much smaller `llm.max_tokens`
+const SUMMARY_MAX_TOKENS: u32 = 400;
+
+impl SummarizeFile {
+    async fn summarize(&self, config: &crate::config::RustShellConfig) -> io::Result<String> {
+        if !config.features.enable_llm || config.features.offline_mode {
+            return Err(io::Error::other("LLM features are disabled in the configuration"));
+        }
+
+        let bytes = fs::read(&self.path)?;
+        if utils::looks_binary(&bytes) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a text file", self.path)));
+        }
+        let content = String::from_utf8_lossy(&bytes);
+        let (prompt, truncated) = crate::llm::prompts::build_summary_prompt(&self.path, &content);
+        if truncated {
+            info!(
+                "Note: {} is long; only its first {} characters are summarized.",
+                self.path,
+                crate::llm::prompts::SUMMARY_MAX_CHARS
+            );
+        }
+
+        let llm_config = config.to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+        let client = crate::llm::LLMClient::new(llm_config)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let request = crate::llm::LLMRequest {
+            prompt,
+            max_tokens: SUMMARY_MAX_TOKENS,
+            temperature: config.llm.temperature,
+            context: None,
+        };
+        let response = client.generate(&request).await.map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(response.content.trim().to_string())
+    }
+}
+
+impl ShellCommand for SummarizeFile {
+    fn run(&self) -> io::Result<()> {
+        let config = crate::current_config();
+        let summary = block_on(self.summarize(&config))?;
+        outln!("{}", summary);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Summarize a text file using the LLM. Usage: summarize <file>".to_string()
+    }
+}
+
+// Ask the LLM a question, optionally about a command's output: `ls | ask which
+// of these are config files?`. The command's output is captured with
+// `ShellCommand::run` instead of being printed.
+#[derive(Default)]
+pub struct AskCommand {
+    pub question: String,
+    // The command before `| ask`; empty to read piped stdin, if any
+    pub command: Vec<String>,
+}
+
+// `<command> | ask <question>` as an AskCommand; None for any other line
+pub fn ask_pipeline(args: &[String]) -> Option<AskCommand> {
+    let bar = args.iter().position(|arg| arg == "|")?;
+    match &args[bar + 1..] {
+        [ask, question @ ..] if ask == "ask" && bar > 0 && !question.is_empty() => Some(AskCommand {
+            question: unquote(&question.join(" ")),
+            command: args[..bar].to_vec(),
+        }),
+        _ => None,
+    }
+}
+
+// The question without the quotes it was typed in, for `ask "why?"`
+pub fn unquote(text: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(inner) = text.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return inner.to_string();
+        }
+    }
+    text.to_string()
+}
+
+impl AskCommand {
+    // The output to ask about, with a label for it in the prompt
+    fn capture(&self) -> io::Result<Option<(String, String)>> {
+        use std::io::{IsTerminal, Read, Write};
+
+        if self.command.is_empty() {
+            if io::stdin().is_terminal() {
+                return Ok(None);
+            }
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            return Ok(Some(("(standard input)".to_string(), input)));
+        }
+
+        let command = crate::parse_command(&self.command, None).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("could not run '{}'", self.command.join(" ")))
+        })?;
+        // Informational messages are left out, as they are not the output
+        let quiet = context::is_quiet();
+        context::set_quiet(true);
+        let (result, _) = context::capture(context::CaptureMode::Plain, || command.execute());
+        context::set_quiet(quiet);
+        context::stderr().write_all(result.stderr.as_bytes())?;
+        if let Some(e) = &result.error {
+            errln!("error: {}", e);
+        }
+        Ok(Some((self.command.join(" "), result.stdout)))
+    }
+
+    async fn ask(&self, config: &crate::config::RustShellConfig, piped: Option<(&str, &str)>) -> io::Result<String> {
+        if !config.features.enable_llm || config.features.offline_mode {
+            return Err(io::Error::other("LLM features are disabled in the configuration"));
+        }
+
+        let (prompt, truncated) = crate::llm::prompts::build_ask_prompt(&self.question, piped);
+        if truncated {
+            info!(
+                "Note: the output is long; only its first {} characters are sent.",
+                crate::llm::prompts::SUMMARY_MAX_CHARS
+            );
+        }
+
+        let llm_config = config.to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+        let client = crate::llm::LLMClient::new(llm_config)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let request = crate::llm::LLMRequest {
+            prompt,
+            max_tokens: SUMMARY_MAX_TOKENS,
+            temperature: config.llm.temperature,
+            context: None,
+        };
+        let response = client.generate(&request).await.map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(response.content.trim().to_string())
+    }
+}
+
+impl ShellCommand for AskCommand {
+    fn run(&self) -> io::Result<()> {
+        let config = crate::current_config();
+        let captured = self.capture()?;
+        let piped = captured.as_ref().map(|(command, output)| (command.as_str(), output.as_str()));
+        let answer = block_on(self.ask(&config, piped))?;
+        outln!("{}", answer);
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Ask the LLM a question, about a command's output if one is piped in. Usage: [command |] ask <question>".to_string()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Translate a request without running the result, for scripts and for checking
+// what a request would do
+#[derive(Default)]
+pub struct TranslateCommand {
+    pub request: String,
+    pub output: OutputFormat,
+}
+
+impl TranslateCommand {
+    async fn translate(&self, config: &crate::config::RustShellConfig) -> io::Result<crate::llm::prompts::CommandTranslation> {
+        if !config.features.enable_llm || config.features.offline_mode {
+            return Err(io::Error::other("LLM features are disabled in the configuration"));
+        }
+
+        let command = crate::translate_request(&self.request, config)
+            .await
+            .map_err(|e| io::Error::other(format!("{:#}", e)))?;
+
+        let mut safety_warnings = Vec::new();
+        if config.is_dangerous_command(&command) {
+            safety_warnings.push("matches a dangerous pattern from safety.dangerous_patterns".to_string());
+        }
+        if config.requires_confirmation(&command) {
+            safety_warnings.push("requires confirmation before running".to_string());
+        }
+
+        // Providers don't report confidence, so it only reflects whether the
+        // program the command starts with exists here
+        let known = crate::translation_problem(&command).is_none();
+
+        Ok(crate::llm::prompts::CommandTranslation {
+            original_request: self.request.clone(),
+            translated_command: command,
+            os: crate::llm::prompts::detect_os(),
+            confidence: if known { 1.0 } else { 0.5 },
+            safety_warnings,
+        })
+    }
+}
+
+impl ShellCommand for TranslateCommand {
+    fn run(&self) -> io::Result<()> {
+        let config = crate::current_config();
+        let translation = block_on(self.translate(&config))?;
+
+        match self.output {
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&translation).map_err(io::Error::other)?;
+                outln!("{}", json);
+            }
+            OutputFormat::Text => {
+                outln!("{}", translation.translated_command);
+                for warning in &translation.safety_warnings {
+                    errln!("Warning: {}", warning);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Translate a natural-language request into a command without running it. Usage: translate [--output json|text] <request>"
+            .to_string()
+    }
+}
+
+// Translate a file of natural-language steps in a single LLM request
+#[derive(Default)]
+pub struct TranslateBatch {
+    pub path: String,
+}
+
+impl TranslateBatch {
+    // Non-empty lines, skipping `#` comments
+    fn read_steps(&self) -> io::Result<Vec<String>> {
+        let content = fs::read_to_string(&self.path)?;
+        let steps: Vec<String> = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+
+        if steps.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} contains no steps", self.path)));
+        }
+        Ok(steps)
+    }
+
+    async fn translate(&self, config: &crate::config::RustShellConfig) -> io::Result<Vec<(String, String)>> {
+        if !config.features.enable_llm || config.features.offline_mode {
+            return Err(io::Error::other("LLM features are disabled in the configuration"));
+        }
+
+        let steps = self.read_steps()?;
+        let template = crate::llm::prompts::PromptTemplate::new();
+        let request = crate::llm::LLMRequest {
+            prompt: template.build_batch_prompt(&steps, &crate::llm::prompts::detect_os(), &crate::llm::prompts::detect_shell()),
+            // `llm.max_tokens` is sized for one command
+            max_tokens: config.llm.max_tokens.saturating_mul(steps.len() as u32),
+            temperature: config.llm.temperature,
+            context: Some(template.system_prompt.clone()),
+        };
+
+        let llm_config = config.to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+        let client = crate::llm::LLMClient::new(llm_config)
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let response = client.generate(&request).await.map_err(|e| io::Error::other(e.to_string()))?;
+
+        let commands = crate::llm::prompts::parse_batch_reply(&response.content, steps.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Could not read the translation: {}", e)))?;
+        Ok(steps.into_iter().zip(commands).collect())
+    }
+}
+
+impl ShellCommand for TranslateBatch {
+    fn run(&self) -> io::Result<()> {
+        let config = crate::current_config();
+        let translations = block_on(self.translate(&config))?;
+
+        // Printed as a script: each command under its step as a comment, so the
+        // output can be reviewed and saved or run as is
+        for (step, command) in translations {
+            outln!("# {}", step);
+            if config.is_dangerous_command(&command) {
+                outln!("# ⚠️  potentially dangerous, review before running");
+            }
+            outln!("{}", command);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Translate a file of natural-language steps, one per line, in a single LLM request. Usage: translate-batch <file>"
+            .to_string()
+    }
+}
+
+// Show recent natural-language requests and what they were translated to
+#[derive(Default)]
+pub struct NlHistoryCommand {
+    pub count: Option<usize>,
+}
+
+const NL_HISTORY_DEFAULT_COUNT: usize = 20;
+
+impl ShellCommand for NlHistoryCommand {
+    fn run(&self) -> io::Result<()> {
+        if !crate::current_config().features.enable_history {
+            outln!("History is disabled (features.enable_history = false)");
+            return Ok(());
+        }
+
+        let entries = nlhistory::read_last(&nlhistory::history_file(), self.count.unwrap_or(NL_HISTORY_DEFAULT_COUNT))?;
+        if entries.is_empty() {
+            outln!("No natural-language requests recorded yet.");
+        }
+        for entry in entries {
+            let time = chrono::DateTime::from_timestamp(entry.time as i64, 0)
+                .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            outln!("{}  {}  ->  {}", time, entry.request, entry.command);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "List recent natural-language requests and the commands they became (default {}). Usage: nl-history [count]",
+            NL_HISTORY_DEFAULT_COUNT
+        )
+    }
+}
+
+// Show or delete the local usage statistics kept with `features.collect_stats`
+#[derive(Default)]
+pub struct UsageStatsCommand {
+    pub count: Option<usize>,
+    pub clear: bool,
+}
+
+const USAGE_STATS_DEFAULT_COUNT: usize = 10;
+
+impl ShellCommand for UsageStatsCommand {
+    fn run(&self) -> io::Result<()> {
+        let file = usage::stats_file();
+        if self.clear {
+            match fs::remove_file(&file) {
+                Ok(()) => outln!("Deleted the usage statistics in {}", file.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => outln!("No usage statistics recorded yet."),
+                Err(e) => return Err(e),
+            }
+            return Ok(());
+        }
+
+        if !crate::current_config().features.collect_stats {
+            outln!("Usage statistics are off; set features.collect_stats = true to record them.");
+        }
+        let stats = usage::UsageStats::load(&file)?;
+        if stats.commands.is_empty() {
+            outln!("No usage statistics recorded yet.");
+            return Ok(());
+        }
+        for line in stats.report(self.count.unwrap_or(USAGE_STATS_DEFAULT_COUNT)) {
+            outln!("{}", line);
+        }
+        outln!("Kept only on this machine, in {}; a session's counts are added when it ends.", file.display());
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "Show your most used commands (default {}) and LLM usage per day, or delete the statistics. \
+             Recorded only with features.collect_stats and never sent anywhere. Usage: stats [count|clear]",
+            USAGE_STATS_DEFAULT_COUNT
+        )
+    }
+}
+
+// Inspect or empty the LLM response cache, which lasts for the session
+#[derive(Default)]
+pub struct CacheCommand {
+    pub clear: bool,
+}
+
+impl ShellCommand for CacheCommand {
+    fn run(&self) -> io::Result<()> {
+        if self.clear {
+            let removed = block_on(crate::llm::LLMClient::clear_cache());
+            outln!("Cleared {} cached response(s)", removed);
+        } else {
+            outln!("{} cached response(s)", block_on(crate::llm::LLMClient::cache_size()));
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Show the number of cached LLM responses, or clear them. Usage: cache [clear]".to_string()
+    }
+}
+
+// List the models the configured provider offers
+#[derive(Default)]
+pub struct ModelsCommand {}
+
+impl ModelsCommand {
+    // One line per model, with the configured one marked
+    fn format_models(mut models: Vec<String>, current: &str) -> Vec<String> {
+        models.sort();
+        models.dedup();
+        let mut lines: Vec<String> = models
+            .iter()
+            .map(|model| format!("{} {}", if model == current { "*" } else { " " }, model))
+            .collect();
+        if !models.iter().any(|model| model == current) {
+            lines.push(format!("(the configured model {} is not in this list)", current));
+        }
+        lines
+    }
+}
+
+impl ShellCommand for ModelsCommand {
+    fn run(&self) -> io::Result<()> {
+        let llm_config = crate::current_config().to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+        let models = block_on(crate::llm::LLMClient::list_models(&llm_config))
+            .map_err(|e| io::Error::other(format!("could not list models: {}", e)))?;
+
+        for line in Self::format_models(models, &llm_config.model) {
+            outln!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "List the models available from the configured LLM provider, marking the one in use. Usage: models".to_string()
+    }
+}
+
+// Check the LLM configuration end to end with a tiny probe request
+#[derive(Default)]
+pub struct LlmTest {}
+
+impl LlmTest {
+    // Show enough of a key to tell which one is configured without revealing it
+    fn mask_key(key: &str) -> String {
+        let chars: Vec<char> = key.chars().collect();
+        if chars.len() <= 8 {
+            return "*".repeat(chars.len());
+        }
+        let tail: String = chars[chars.len() - 4..].iter().collect();
+        format!("{}...{}", chars[..3].iter().collect::<String>(), tail)
+    }
+
+    async fn run(config: &crate::config::RustShellConfig) -> io::Result<()> {
+        let llm_config = config.to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+
+        outln!("Provider: {}", llm_config.provider);
+        outln!("Model:    {}", llm_config.model);
+        match llm_config.endpoint.as_deref().filter(|e| !e.is_empty()) {
+            Some(endpoint) => outln!("Endpoint: {}", endpoint),
+            None => outln!("Endpoint: provider default"),
+        }
+        match (&llm_config.provider, &llm_config.api_key) {
+            (crate::llm::LLMProvider::Mock(_), _) => outln!("API key:  not required"),
+            (_, Some(key)) => outln!("API key:  found ({})", Self::mask_key(key)),
+            (_, None) => outln!(
+                "API key:  missing (set {} or llm.api_key_env)",
+                config.llm.api_key_env.as_deref().unwrap_or("the provider's API key variable")
+            ),
+        }
+        if !config.features.enable_llm || config.features.offline_mode {
+            outln!("Note: LLM features are disabled by features.enable_llm / features.offline_mode");
+        }
+
+        let client = crate::llm::LLMClient::new(llm_config)
+            .await
+            .map_err(|e| io::Error::other(format!("could not create the client: {}", e)))?;
+        if !client.is_available() {
+            return Err(io::Error::other(format!("{} is not available", client.provider_name())));
+        }
+
+        out!("Probe:    ");
+        io::Write::flush(&mut context::stdout())?;
+        let request = crate::llm::LLMRequest {
+            prompt: "Reply with the single word: pong".to_string(),
+            max_tokens: 5,
+            temperature: 0.0,
+            context: None,
+        };
+        let started = std::time::Instant::now();
+        match client.generate(&request).await {
+            Ok(response) => {
+                outln!("OK in {:.1}s (replied {:?})", started.elapsed().as_secs_f64(), response.content.trim());
+                Ok(())
+            }
+            Err(e) => {
+                outln!("FAILED");
+                Err(io::Error::other(format!("{} request failed: {}", client.provider_name(), e)))
+            }
+        }
+    }
+}
+
+impl ShellCommand for LlmTest {
+    fn run(&self) -> io::Result<()> {
+        let config = crate::current_config();
+        block_on(Self::run(&config))
+    }
+
+    fn help(&self) -> String {
+        "Check the LLM provider, model, API key and connectivity. Usage: llm-test".to_string()
+    }
+}
+
+// Time repeated small requests to the configured provider
+pub const LLM_BENCH_DEFAULT_RUNS: usize = 5;
+
+#[derive(Default)]
+pub struct LlmBench {
+    pub runs: Option<usize>,
+}
+
+// One successful benchmark request
+pub struct BenchSample {
+    pub latency: std::time::Duration,
+    pub completion_tokens: Option<u32>,
+}
+
+impl LlmBench {
+    // Min/mean/max latency, and token throughput when the provider reports usage
+    fn stats_table(samples: &[BenchSample]) -> Vec<String> {
+        fn min_mean_max(values: &[f64], precision: usize) -> Vec<String> {
+            let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            [min, mean, max].iter().map(|value| format!("{:.*}", precision, value)).collect()
+        }
+
+        let latencies: Vec<f64> = samples.iter().map(|sample| sample.latency.as_secs_f64() * 1000.0).collect();
+        let mut rows = vec![[vec!["Latency (ms)".to_string()], min_mean_max(&latencies, 0)].concat()];
+
+        let throughput: Vec<f64> = samples
+            .iter()
+            .filter_map(|sample| {
+                let tokens = sample.completion_tokens? as f64;
+                Some(tokens / sample.latency.as_secs_f64().max(f64::EPSILON))
+            })
+            .collect();
+        if !throughput.is_empty() {
+            rows.push([vec!["Tokens/s".to_string()], min_mean_max(&throughput, 1)].concat());
+        }
+
+        format_table(&[("", Align::Left), ("Min", Align::Right), ("Mean", Align::Right), ("Max", Align::Right)], &rows)
+    }
+
+    async fn run(llm_config: crate::llm::LLMConfig, runs: usize) -> io::Result<Vec<BenchSample>> {
+        // Cached replies would measure the cache, not the provider
+        let llm_config = crate::llm::LLMConfig { use_cache: false, ..llm_config };
+        let client = crate::llm::LLMClient::new(llm_config)
+            .await
+            .map_err(|e| io::Error::other(format!("could not create the client: {}", e)))?;
+
+        let request = crate::llm::LLMRequest {
+            prompt: "Reply with the single word: pong".to_string(),
+            max_tokens: 5,
+            temperature: 0.0,
+            context: None,
+        };
+
+        let mut samples = Vec::new();
+        for run in 1..=runs {
+            let started = std::time::Instant::now();
+            match client.generate(&request).await {
+                Ok(response) => samples.push(BenchSample {
+                    latency: started.elapsed(),
+                    completion_tokens: response.usage.map(|usage| usage.completion_tokens),
+                }),
+                Err(e) => errln!("Request {} of {} failed: {}", run, runs, e),
+            }
+        }
+        outln!("{} of {} requests to {} succeeded", samples.len(), runs, client.provider_name());
+        Ok(samples)
+    }
+}
+
+impl ShellCommand for LlmBench {
+    fn run(&self) -> io::Result<()> {
+        let llm_config = crate::current_config().to_llm_config().map_err(|e| io::Error::other(e.to_string()))?;
+        let samples = block_on(Self::run(llm_config, self.runs.unwrap_or(LLM_BENCH_DEFAULT_RUNS)))?;
+
+        if samples.is_empty() {
+            return Err(io::Error::other("no request succeeded"));
+        }
+        for line in Self::stats_table(&samples) {
+            outln!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "Send a small prompt to the LLM provider repeatedly (default {}) and report latency and throughput. Usage: llm-bench [runs]",
+            LLM_BENCH_DEFAULT_RUNS
+        )
+    }
+}
+
+// Run every environment check and print a checklist
+#[derive(Default)]
+pub struct Doctor {}
+
+impl ShellCommand for Doctor {
+    fn run(&self) -> io::Result<()> {
+        let checks = doctor::run_checks();
+        for check in &checks {
+            outln!("{} {}: {}", check.status.marker(), check.label, check.detail);
+        }
+
+        let count = |status| checks.iter().filter(|c| c.status == status).count();
+        outln!(
+            "\n{} passed, {} warning(s), {} failure(s)",
+            count(doctor::Status::Pass),
+            count(doctor::Status::Warn),
+            count(doctor::Status::Fail)
+        );
+        if count(doctor::Status::Warn) + count(doctor::Status::Fail) > 0 {
+            outln!("Run `llm-test` to check connectivity to the LLM provider.");
+        }
+        Ok(())
+    }
+
+    fn help(&self) -> String {
+        "Check the configuration, API key, data directory, aliases and external tools. Usage: doctor".to_string()
+    }
+}
+
+// Emit a completion script for the `rustshell` binary in the host shell
+#[derive(Default)]
+pub struct GenerateCompletions {
+    pub shell: String,
+}
+
+// Build the completion script for `shell`, or None if the shell isn't supported.
+// The first word completes to commands and global flags, later words to files.
+pub fn completion_script(shell: &str) -> Option<String> {
+    let commands: Vec<&str> = registry().names().collect();
+    let words: Vec<&str> = commands.iter().chain(GLOBAL_FLAGS.iter()).copied().collect();
+    let word_list = words.join(" ");
+    
+    let script = match shell {
+        "bash" => format!(
+            r#"_rustshell() {{
+local cur="${{COMP_WORDS[COMP_CWORD]}}"
+if [ "$COMP_CWORD" -eq 1 ]; then
+    COMPREPLY=( $(compgen -W "{}" -- "$cur") )
+else
+    COMPREPLY=( $(compgen -f -- "$cur") )
+fi
+}}
+complete -o default -F _rustshell rustshell
+"#,
+            word_list
+        ),
+        "zsh" => format!(
+            r#"#compdef rustshell
+_rustshell() {{
+if (( CURRENT == 2 )); then
+    compadd -- {}
+else
+    _files
+fi
+}}
+compdef _rustshell rustshell
+"#,
+            word_list
+        ),
+        "fish" => format!(
+            "complete -c rustshell -n '__fish_use_subcommand' -a '{}'\n",
+            commands.join(" ")
+        ) + &GLOBAL_FLAGS
+            .iter()
+            .map(|flag| format!("complete -c rustshell -n '__fish_use_subcommand' -l {} -r\n", flag.trim_start_matches("--")))
+            .collect::<String>(),
+        "powershell" | "pwsh" => format!(
+            r#"Register-ArgumentCompleter -Native -CommandName rustshell -ScriptBlock {{
+param($wordToComplete, $commandAst, $cursorPosition)
+if ($commandAst.CommandElements.Count -gt 2) {{ return }}
+@({}) | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+    [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+}}
+}}
+"#,
+            words.iter().map(|w| format!("'{}'", w)).collect::<Vec<_>>().join(", ")
+        ),
+        _ => return None,
+    };
+    
+    Some(script)
+}
+
+impl ShellCommand for GenerateCompletions {
+    fn run(&self) -> io::Result<()> {
+        match completion_script(&self.shell) {
+            Some(script) => {
+                out!("{}", script);
+                Ok(())
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported shell '{}' (expected bash, zsh, fish or powershell)", self.shell),
+            )),
+        }
+    }
+
+    fn help(&self) -> String {
+        "Print a shell completion script. Usage: completions <bash|zsh|fish|powershell>".to_string()
+    }
+}
+
+// Commands run synchronously inside the tokio runtime started by main; this
+// lets them await async work such as LLM requests
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+// Helper function to print command output
+fn print_output(output: Output) {
+    if !output.stdout.is_empty() {
+        out!("{}", String::from_utf8_lossy(&output.stdout));
+    }
+    
+    if !output.stderr.is_empty() {
+        errln!("{}", String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_scripts() {
+        for shell in ["bash", "zsh", "fish", "powershell"] {
+            let script = completion_script(shell).unwrap();
+            assert!(script.contains("make_dir"), "{} script lacks commands", shell);
+            assert!(script.contains("timeout"), "{} script lacks flags", shell);
+        }
+        assert!(completion_script("bash").unwrap().contains("complete -o default -F _rustshell rustshell"));
+        assert!(completion_script("tcsh").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_paths_are_not_shell_interpreted() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("pwned");
+        let evil = format!("nothing\"; touch {}; \"", marker.display());
+
+        let _ = ShowFile { paths: vec![evil.clone()] }.run();
+        let _ = ListDir { path: Some(evil.clone()), ..Default::default() }.run();
+        let _ = FindFiles { pattern: evil, path: Some(dir.path().display().to_string()), ..Default::default() }.run();
+
+        assert!(!marker.exists(), "injected command was executed");
+    }
+
+    #[test]
+    fn test_format_table_aligns_columns() {
+        let rows = vec![
+            vec!["main.rs".to_string(), "1.2K".to_string()],
+            vec!["é.txt".to_string(), "15M".to_string()],
+        ];
+        let table = format_table(&[("Name", Align::Left), ("Size", Align::Right)], &rows);
+        assert_eq!(table, vec!["Name     Size", "-------  ----", "main.rs  1.2K", "é.txt     15M"]);
+
+        let find = FindFiles { pattern: "*.rs".to_string(), ..Default::default() };
+        assert!(find.matches("main.rs"));
+        assert!(!find.matches("lib.rs.orig"));
+        assert!(!find.matches("README.md"));
+    }
+
+    #[test]
+    fn test_find_glob_syntax() {
+        let find = |pattern: &str| FindFiles { pattern: pattern.to_string(), ..Default::default() };
+
+        assert!(find("*-v[0-9].txt").matches("notes-v2.txt"));
+        assert!(!find("*-v[0-9].txt").matches("notes-vx.txt"));
+        assert!(find("*.{rs,toml}").matches("src/Cargo.toml"));
+        assert!(find("?.rs").matches("a.rs"));
+        // A glob matches the whole name, plain text any part of it
+        assert!(!find("?.rs").matches("abc.rs.bak"));
+        assert!(!find("v[0-9]").matches("notes-v2.txt"));
+        assert!(find("notes").matches("my-notes.txt"));
+
+        // With a `/` the whole relative path has to match
+        assert!(find("src/**/*.rs").matches("src/commands/walk.rs"));
+        assert!(find("src/**/*.rs").matches("src/main.rs"));
+        assert!(!find("src/*.rs").matches("src/commands/walk.rs"));
+        assert!(!find("src/**/*.rs").matches("tests/src/main.rs"));
+
+        assert_eq!(find("[a-").matcher().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_format_columns_fills_top_to_bottom() {
+        let names: Vec<String> = ["a", "b", "c", "d", "e"].iter().map(|n| n.to_string()).collect();
+        // Each column is 3 wide ("a" + 2 spaces), so 7 characters fit two columns
+        assert_eq!(format_columns(&names, 7), vec!["a  d", "b  e", "c"]);
+        assert_eq!(format_columns(&names, 1).len(), 5);
+        assert_eq!(format_columns(&names, 200), vec!["a  b  c  d  e"]);
+        assert!(format_columns(&[], 80).is_empty());
+    }
+
+    #[test]
+    fn test_make_dir_existing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build").display().to_string();
+
+        let plain = MakeDir { paths: vec![path.clone()], ..Default::default() };
+        plain.run().unwrap();
+        assert_eq!(plain.run().unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+        let exists_ok = MakeDir { paths: vec![path.clone()], exists_ok: true, ..Default::default() };
+        exists_ok.run().unwrap();
+        let parents = MakeDir { paths: vec![path.clone()], parents: true, ..Default::default() };
+        parents.run().unwrap();
+
+        // A file in the way is never silently accepted
+        let file = dir.path().join("notes").display().to_string();
+        fs::write(&file, "").unwrap();
+        assert!(MakeDir { paths: vec![file.clone()], exists_ok: true, ..Default::default() }.run().is_err());
+        assert!(MakeDir { paths: vec![file], parents: true, ..Default::default() }.run().is_err());
+    }
+
+    #[test]
+    fn test_copy_refuses_destination_inside_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("project");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("sub/file.txt"), "data").unwrap();
+
+        let into_itself = CopyFile {
+            src: src.display().to_string(),
+            dst: src.join("sub/backup").display().to_string(),
+            recursive: true,
+            ..Default::default()
+        };
+        assert_eq!(into_itself.run().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        let backup = dir.path().join("backup");
+        let copy = CopyFile { dst: backup.display().to_string(), ..into_itself };
+        copy.run().unwrap();
+        assert_eq!(fs::read_to_string(backup.join("sub/file.txt")).unwrap(), "data");
+
+        let archive = CompressFiles {
+            source: src.display().to_string(),
+            destination: src.join("project.zip").display().to_string(),
+            ..Default::default()
+        };
+        assert_eq!(archive.run().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_log_captures_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("build.log");
+        let mut run = ExecuteCommand {
+            command: "echo".to_string(),
+            args: vec!["first".to_string()],
+            log: Some(log.display().to_string()),
+            append: false,
+        };
+        run.run().unwrap();
+        run.args = vec!["second".to_string()];
+        run.append = true;
+        run.run().unwrap();
+
+        assert_eq!(fs::read_to_string(&log).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_execute_returns_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "first line\n").unwrap();
+
+        let path = file.display().to_string();
+        let show = ShowFile { paths: vec![path.clone()] }.execute();
+        assert_eq!(show.exit_code, 0);
+        assert_eq!(show.stdout, format!("--- Contents of {} ---\nfirst line\n--- End of file ---\n", path));
+        assert!(show.stderr.is_empty());
+
+        let missing = ShowFile { paths: vec![dir.path().join("absent").display().to_string()] }.execute();
+        assert_eq!(missing.exit_code, 1);
+        assert!(missing.stderr.is_empty());
+        assert_eq!(missing.error.unwrap().kind(), io::ErrorKind::NotFound);
+
+        // A program's exit code and output are returned, not printed
+        if cfg!(unix) {
+            let run = ExecuteCommand {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), "echo out; echo err >&2; exit 3".to_string()],
+                ..Default::default()
+            };
+            let result = run.execute();
+            assert_eq!((result.exit_code, result.stdout.as_str(), result.stderr.as_str()), (3, "out\n", "err\n"));
+            assert!(result.error.is_none());
+        }
+        assert!(!context::is_capturing());
+    }
+
+    #[tokio::test]
+    async fn test_summarize_through_mock_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.log");
+        fs::write(&path, "compiling...\nerror: linker failed\n").unwrap();
+
+        let mut config = crate::config::RustShellConfig::default();
+        config.llm.provider = "mock:The build failed at the link step.".to_string();
+        let command = SummarizeFile { path: path.display().to_string() };
+        assert_eq!(command.summarize(&config).await.unwrap(), "The build failed at the link step.");
+
+        config.features.offline_mode = true;
+        assert!(command.summarize(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ask_about_piped_output() {
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let ask = ask_pipeline(&args("list -a | ask \"which are config files?\"")).unwrap();
+        assert_eq!(ask.command, args("list -a"));
+        assert_eq!(ask.question, "which are config files?");
+        assert!(ask_pipeline(&args("| ask why")).is_none());
+        assert!(ask_pipeline(&args("list | grep x")).is_none());
+
+        let mut config = crate::config::RustShellConfig::default();
+        config.llm.provider = "mock:Cargo.toml is the config file.".to_string();
+        let answer = ask.ask(&config, Some(("list -a", "Cargo.toml\nsrc\n"))).await.unwrap();
+        assert_eq!(answer, "Cargo.toml is the config file.");
+    }
+
+    #[tokio::test]
+    async fn test_translate_reports_without_running() {
+        let mut config = crate::config::RustShellConfig::default();
+        config.llm.provider = "mock:rm -rf /".to_string();
+        let command = TranslateCommand { request: "clean everything".to_string(), output: OutputFormat::Json };
+
+        let translation = command.translate(&config).await.unwrap();
+        assert_eq!(translation.original_request, "clean everything");
+        assert_eq!(translation.translated_command, "rm -rf /");
+        assert_eq!(translation.safety_warnings.len(), 2);
+        let json = serde_json::to_value(&translation).unwrap();
+        assert_eq!(json["translated_command"], "rm -rf /");
+    }
+
+    #[tokio::test]
+    async fn test_translate_batch_through_mock_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.txt");
+        fs::write(&path, "# provisioning\ncreate a directory called app\n\nlist the files\n").unwrap();
+
+        let mut config = crate::config::RustShellConfig::default();
+        config.llm.provider = r#"mock:["mkdir app", "ls"]"#.to_string();
+        let command = TranslateBatch { path: path.display().to_string() };
+        assert_eq!(
+            command.translate(&config).await.unwrap(),
+            vec![
+                ("create a directory called app".to_string(), "mkdir app".to_string()),
+                ("list the files".to_string(), "ls".to_string()),
+            ]
+        );
+
+        // A reply that doesn't line up with the steps is rejected, not guessed at
+        config.llm.provider = r#"mock:["mkdir app"]"#.to_string();
+        assert!(command.translate(&config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_llm_test_with_mock_provider() {
+        let mut config = crate::config::RustShellConfig::default();
+        config.llm.provider = "mock:pong".to_string();
+        LlmTest::run(&config).await.unwrap();
+
+        assert_eq!(LlmTest::mask_key("sk-abcdefghijklmnop"), "sk-...mnop");
+        assert_eq!(LlmTest::mask_key("short"), "*****");
+    }
+
+    #[test]
+    fn test_grep_binary_modes() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("blob.bin");
+        fs::write(&binary, b"needle\x00\x01\x02").unwrap();
+        let text = dir.path().join("notes.txt");
+        fs::write(&text, "hay\nNeedle here\n").unwrap();
+
+        let found = |grep: &GrepFiles, path: &Path| !grep.matching_lines(path, true, false).unwrap().is_empty();
+        let mut grep = GrepFiles { pattern: "needle".to_string(), ..Default::default() };
+        assert!(!found(&grep, &binary));
+        assert!(!found(&grep, &text));
+
+        grep.ignore_case = true;
+        assert!(found(&grep, &text));
+
+        grep.binary = BinaryMode::Include;
+        assert!(found(&grep, &binary));
+        grep.binary = BinaryMode::Text;
+        assert!(found(&grep, &binary));
+    }
+
+    #[test]
+    fn test_grep_errors_go_to_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("absent").display().to_string();
+        let result = GrepFiles { pattern: "needle".to_string(), paths: vec![missing.clone()], ..Default::default() }.execute();
+        assert!(result.stderr.starts_with("grep: ") && result.stderr.contains(&missing));
+        assert!(!result.stdout.contains("grep:"));
+    }
+
+    #[test]
+    fn test_grep_context_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        fs::write(&path, "one\nERROR a\nthree\nfour\nfive\nsix\nERROR b\nERROR c\nnine\n").unwrap();
+
+        let grep = GrepFiles { pattern: "ERROR".to_string(), before: 1, after: 1, ..Default::default() };
+        assert_eq!(
+            grep.matching_lines(&path, false, false).unwrap(),
+            ["1-one", "2:ERROR a", "3-three", "--", "6-six", "7:ERROR b", "8:ERROR c", "9-nine"]
+        );
+
+        // Groups that touch are not separated
+        let grep = GrepFiles { before: 2, after: 0, ..grep };
+        assert_eq!(
+            grep.matching_lines(&path, true, false).unwrap()[..3],
+            [format!("{}-1-one", path.display()), format!("{}:2:ERROR a", path.display()), "--".to_string()]
+        );
+        let grep = GrepFiles { before: 0, after: 3, ..grep };
+        assert_eq!(grep.matching_lines(&path, false, false).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_type_follows_dispatch_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut aliases = crate::AliasManager::with_file(dir.path().join("aliases")).unwrap();
+        aliases.add_alias("ls".to_string(), "list -la".to_string()).unwrap();
+        aliases.add_alias("ll".to_string(), "list -l".to_string()).unwrap();
+
+        assert_eq!(TypeCommand::describe("ll", &aliases), "ll is aliased to `list -l`");
+        assert_eq!(
+            TypeCommand::describe("ls", &aliases),
+            "ls is aliased to `list -la` (shadowing the built-in)"
+        );
+        assert_eq!(TypeCommand::describe("\\ls", &aliases), "\\ls is a rustshell built-in");
+        assert_eq!(TypeCommand::describe("no-such-command-xyz", &aliases), "no-such-command-xyz: not found");
+
+        #[cfg(unix)]
+        assert!(TypeCommand::describe("sh", &aliases).starts_with("sh is /"));
+    }
+
+    #[test]
+    fn test_remove_dir_refuses_root() {
+        let root = if cfg!(windows) { "C:\\" } else { "/" };
+        assert!(dangerous_removal_reason(Path::new(root)).is_some());
+    }
+
+    #[test]
+    fn test_remove_dir_refuses_current_dir() {
+        assert!(dangerous_removal_reason(Path::new(".")).is_some());
+        assert!(dangerous_removal_reason(Path::new("..")).is_some());
+    }
+
+    #[test]
+    fn test_remove_dir_allows_ordinary_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(dangerous_removal_reason(dir.path()).is_none());
+    }
+
+    #[test]
+    fn test_models_mark_the_configured_one() {
+        let models = vec!["gpt-4o".to_string(), "gpt-3.5-turbo".to_string(), "gpt-4o".to_string()];
+        assert_eq!(ModelsCommand::format_models(models.clone(), "gpt-4o"), vec!["  gpt-3.5-turbo", "* gpt-4o"]);
+
+        let lines = ModelsCommand::format_models(models, "gpt-5-typo");
+        assert_eq!(lines.last().unwrap(), "(the configured model gpt-5-typo is not in this list)");
+    }
+
+    #[test]
+    fn test_llm_bench_stats() {
+        let sample = |millis, tokens| BenchSample {
+            latency: std::time::Duration::from_millis(millis),
+            completion_tokens: tokens,
+        };
+
+        let lines = LlmBench::stats_table(&[sample(100, Some(10)), sample(300, Some(30)), sample(200, None)]);
+        assert_eq!(lines[2], "Latency (ms)    100    200    300");
+        assert_eq!(lines[3], "Tokens/s      100.0  100.0  100.0");
+
+        // Without usage data there is no throughput row
+        assert_eq!(LlmBench::stats_table(&[sample(50, None)]).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_llm_bench_with_mock_provider() {
+        let config = crate::llm::LLMConfig {
+            provider: crate::llm::LLMProvider::Mock(None),
+            requests_per_minute: 0,
+            ..Default::default()
+        };
+        let samples = LlmBench::run(config, 3).await.unwrap();
+        assert_eq!(samples.len(), 3);
+        assert!(samples.iter().all(|sample| sample.completion_tokens.is_none()));
+    }
+
+    #[test]
+    fn test_peek_head_and_tail() {
+        let text: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+
+        let peek = PeekFile::peek(io::Cursor::new(text.as_bytes()), 2, 3).unwrap();
+        assert_eq!(peek.head, vec!["line 1", "line 2"]);
+        assert_eq!(peek.omitted, 15);
+        assert_eq!(peek.tail, vec!["line 18", "line 19", "line 20"]);
+
+        // Overlapping head and tail show the whole file once
+        let peek = PeekFile::peek(io::Cursor::new(b"a\nb\nc".as_slice()), 2, 5).unwrap();
+        assert_eq!((peek.head.len(), peek.omitted, peek.tail), (2, 0, vec!["c".to_string()]));
+
+        let peek = PeekFile::peek(io::Cursor::new(text.as_bytes()), 0, 0).unwrap();
+        assert_eq!((peek.head.len(), peek.omitted, peek.tail.len()), (0, 20, 0));
+    }
+
+    #[test]
+    fn test_trim_normalizes_whitespace() {
+        let text = "fn main() {  \n\tlet x = 1;\t\n}\n\n\n";
+        assert_eq!(TrimFile::normalize(text.as_bytes(), None).unwrap(), "fn main() {\n\tlet x = 1;\n}\n");
+        assert_eq!(TrimFile::normalize(text.as_bytes(), Some(4)).unwrap(), "fn main() {\n    let x = 1;\n}\n");
+        assert_eq!(TrimFile::expand_tabs("ab\tc", 4), "ab  c");
+
+        // A missing final newline is added; CRLF endings are kept
+        assert_eq!(TrimFile::normalize("a \r\nb".as_bytes(), None).unwrap(), "a\r\nb\r\n");
+        assert_eq!(TrimFile::normalize(" \n\n".as_bytes(), None).unwrap(), "");
+    }
+
+    #[test]
+    fn test_change_dir_explains_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "").unwrap();
+
+        let cd = |path: &Path| ChangeDir { path: path.display().to_string() }.run().unwrap_err();
+
+        let err = cd(&file);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), format!("cd: not a directory: {}", file.display()));
+
+        let missing = dir.path().join("missing");
+        let err = cd(&missing);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), format!("cd: no such directory: {}", missing.display()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_follows_symlinks_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("real/sub")).unwrap();
+        fs::write(dir.path().join("real/sub/notes.txt"), "").unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("real"), tree.join("linked")).unwrap();
+        // A loop back to an ancestor must not hang the search
+        std::os::unix::fs::symlink(&tree, tree.join("loop")).unwrap();
+
+        let found = |follow_symlinks| {
+            let find = FindFiles { pattern: ".txt".to_string(), follow_symlinks, ..Default::default() };
+            find.walk(&tree).map(|entry| entry.path().strip_prefix(&tree).unwrap().to_path_buf()).collect::<Vec<_>>()
+        };
+        assert!(found(false).is_empty());
+        assert_eq!(found(true), vec![PathBuf::from("linked/sub/notes.txt")]);
+    }
+
+    #[test]
+    fn test_find_max_depth() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        for file in ["top.log", "a/mid.log", "a/b/deep.log"] {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let found = |max_depth| {
+            let find = FindFiles { pattern: ".log".to_string(), max_depth, ..Default::default() };
+            find.walk(dir.path()).map(|entry| entry.file_name().to_string_lossy().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(found(Some(1)), vec!["top.log"]);
+        assert_eq!(found(Some(2)), vec!["mid.log", "top.log"]);
+        assert_eq!(found(None).len(), 3);
+    }
+
+    #[test]
+    fn test_find_by_modification_time() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = tempfile::tempdir().unwrap();
+        let now = SystemTime::now();
+        for (name, age_hours) in [("today.log", 1), ("last-week.log", 24 * 5), ("old.log", 24 * 40)] {
+            let file = File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(age_hours * 3600)).unwrap();
+        }
+
+        let found = |newer_than: Option<&str>, older_than: Option<&str>| {
+            let find = FindFiles {
+                pattern: ".log".to_string(),
+                newer_than: newer_than.and_then(utils::parse_duration),
+                older_than: older_than.and_then(utils::parse_duration),
+                ..Default::default()
+            };
+            let sequential: Vec<PathBuf> = find.walk(dir.path()).map(walkdir::DirEntry::into_path).collect();
+            assert_eq!(find.walk_parallel(dir.path(), 2), sequential);
+            sequential.iter().map(|path| path.file_name().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(found(Some("2d"), None), vec!["today.log"]);
+        assert_eq!(found(None, Some("1w")), vec!["old.log"]);
+        assert_eq!(found(Some("1w"), Some("2h")), vec!["last-week.log"]);
+        assert_eq!(found(None, None).len(), 3);
+
+        let future = FindFiles { newer_than: Some(Duration::from_secs(60)), ..Default::default() };
+        assert!(future.age_matches(now, Some(now + Duration::from_secs(3600))));
+        assert!(!future.age_matches(now, None));
+    }
+
+    #[test]
+    fn test_find_null_separated_output() {
+        let mut out = Vec::new();
+        let find = FindFiles { null: true, ..Default::default() };
+        for path in ["my file.txt", "two\nlines.txt"] {
+            find.write_path(&mut out, Path::new(path)).unwrap();
+        }
+        assert_eq!(out, b"my file.txt\0two\nlines.txt\0");
+
+        out.clear();
+        FindFiles::default().write_path(&mut out, Path::new("my file.txt")).unwrap();
+        assert_eq!(out, b"my file.txt\n");
+    }
+
+    #[test]
+    fn test_find_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for (name, size) in [("empty.bin", 0), ("small.bin", 100), ("sub/big.bin", 3 << 20), ("big.txt", 2 << 20)] {
+            fs::write(dir.path().join(name), vec![0u8; size]).unwrap();
+        }
+
+        let found = |pattern: &str, larger_than: Option<&str>, smaller_than: Option<&str>, max_depth| {
+            let find = FindFiles {
+                pattern: pattern.to_string(),
+                larger_than: larger_than.and_then(utils::parse_size),
+                smaller_than: smaller_than.and_then(utils::parse_size),
+                max_depth,
+                ..Default::default()
+            };
+            let sequential: Vec<PathBuf> = find.walk(dir.path()).map(walkdir::DirEntry::into_path).collect();
+            assert_eq!(find.walk_parallel(dir.path(), 2), sequential);
+            sequential.iter().map(|path| path.file_name().unwrap().to_string_lossy().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(found("", Some("1M"), None, None), vec!["big.txt", "big.bin"]);
+        assert_eq!(found(".bin", Some("1M"), None, Some(1)), Vec::<String>::new());
+        assert_eq!(found(".bin", None, Some("1K"), None), vec!["empty.bin", "small.bin"]);
+        assert_eq!(found("", Some("0"), Some("2M"), None), vec!["small.bin"]);
+    }
+
+    #[test]
+    fn test_filetypes_report() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("logo.PNG"), vec![0u8; 3000]).unwrap();
+        fs::write(dir.path().join("Makefile"), "all:").unwrap();
+
+        let types = FileTypes::tally(dir.path());
+        assert_eq!(types["rs"], (2, 12));
+        assert_eq!(types["png"], (1, 3000));
+
+        let by_count = FileTypes::default().report(types.clone());
+        assert_eq!(by_count, vec!["rs: 2 files, 12", "png: 1 file, 2.9K", "(none): 1 file, 4"]);
+
+        let largest = FileTypes { by_size: true, top: Some(2), ..Default::default() }.report(types);
+        assert_eq!(largest, vec!["png: 1 file, 2.9K", "rs: 2 files, 12"]);
+    }
+
+    #[test]
+    fn test_recent_files_newest_first() {
+        use std::time::{Duration, SystemTime};
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        let now = SystemTime::now();
+        let ages = [("old.txt", 7200), ("src/main.rs", 60), ("notes.md", 600), (".git/index", 0)];
+        for (name, age) in ages {
+            let file = File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        let names = |recent: RecentFiles| {
+            recent.recent(dir.path(), now).into_iter().map(|(path, _)| path.strip_prefix(dir.path()).unwrap().to_path_buf()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(RecentFiles::default()), vec![PathBuf::from("src/main.rs"), "notes.md".into(), "old.txt".into()]);
+        assert_eq!(names(RecentFiles { count: Some(1), ..Default::default() }), vec![PathBuf::from("src/main.rs")]);
+        let last_hour = RecentFiles { since: Some(Duration::from_secs(3600)), all: true, ..Default::default() };
+        assert_eq!(names(last_hour), vec![PathBuf::from(".git/index"), "src/main.rs".into(), "notes.md".into()]);
+    }
+
+    #[test]
+    fn test_hash_and_check() {
+        assert_eq!(
+            HashAlgo::Sha256.digest("abc".as_bytes()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(HashAlgo::Sha1.digest("abc".as_bytes()).unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(HashAlgo::Md5.digest("abc".as_bytes()).unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("good.txt"), "abc").unwrap();
+        fs::write(dir.path().join("bad.txt"), "abd").unwrap();
+        let manifest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  good.txt\n\
+                        900150983cd24fb0d6963f7d28e17f72 *bad.txt\n\
+                        900150983cd24fb0d6963f7d28e17f72  missing.txt\n";
+        assert_eq!(HashFiles::verify(manifest, dir.path()).unwrap(), 2);
+        assert!(HashFiles::verify("not a checksum", dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_split_and_join_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.bin");
+        let data: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        let path = path.display().to_string();
+
+        let split = SplitFile { path: path.clone(), chunk_size: 1000 };
+        assert_eq!(split.split().unwrap(), 3);
+        assert_eq!(fs::metadata(part_name(&path, 3)).unwrap().len(), 500);
+        assert_eq!(split.split().unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+        let restored = dir.path().join("restored.bin");
+        JoinFiles { prefix: path, output: restored.display().to_string() }.run().unwrap();
+        assert_eq!(fs::read(restored).unwrap(), data);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_compress_strip_root_archives_from_inside_the_directory() {
+        let args = |compress: &CompressFiles| {
+            let command = compress.archive_command().unwrap();
+            let args: Vec<String> = command.get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+            (args, command.get_current_dir().map(Path::to_path_buf))
+        };
+        let destination = env::current_dir().unwrap().join("out.zip").display().to_string();
+
+        let mut compress = CompressFiles { source: "docs".to_string(), destination: "out.zip".to_string(), no_ignore: true, ..Default::default() };
+        assert_eq!(args(&compress), (vec!["-r".to_string(), destination.clone(), "docs".to_string()], None));
+
+        compress.strip_root = true;
+        assert_eq!(args(&compress), (vec!["-r".to_string(), destination, ".".to_string()], Some(PathBuf::from("docs"))));
+    }
+
+    #[test]
+    fn test_exclude_skips_matching_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("target/debug/main.rs"), "x".repeat(100)).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("build.log"), "log").unwrap();
+        let excludes = vec!["target".to_string(), "*.log".to_string()];
+
+        let find = FindFiles { pattern: "main".to_string(), excludes: excludes.clone(), ..Default::default() };
+        let found: Vec<PathBuf> = find.walk(dir.path()).map(|entry| entry.into_path()).collect();
+        assert_eq!(found, vec![dir.path().join("src/main.rs")]);
+        assert_eq!(find.walk_parallel(dir.path(), 2), found);
+
+        let du = DiskUsage { excludes: excludes.clone(), ..Default::default() };
+        assert_eq!(du.total(dir.path()), 12);
+        assert_eq!(DiskUsage { jobs: Some(2), ..du }.total(dir.path()), 12);
+        assert_eq!(DiskUsage::default().total(dir.path()), 115);
+
+        let source = dir.path().display().to_string();
+        let compress = CompressFiles { source: source.clone(), destination: "out.zip".to_string(), strip_root: true, excludes, no_ignore: false, one_file_system: false };
+        assert_eq!(compress.archive_entries(), vec!["src", "src/main.rs"]);
+        let compress = CompressFiles { strip_root: false, ..compress };
+        assert_eq!(compress.archive_entries(), vec![source.clone(), format!("{}/src", source), format!("{}/src/main.rs", source)]);
+    }
+
+    #[test]
+    fn test_replace_substitutes_regex_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("names.txt");
+        fs::write(&path, "Ada Lovelace\nAlan Turing\n").unwrap();
+
+        let replace = ReplaceInFile {
+            path: path.display().to_string(),
+            pattern: r"(\w+) (\w+)".to_string(),
+            replacement: r"\2, $1".to_string(),
+            backup: true,
+            ..Default::default()
+        };
+        assert_eq!(replace.replacement(), "${2}, $1");
+        assert_eq!(replace.substitute("Ada Lovelace").unwrap(), ("Lovelace, Ada".to_string(), 1));
+
+        replace.run().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Lovelace, Ada\nTuring, Alan\n");
+        assert_eq!(fs::read_to_string(dir.path().join("names.txt.bak")).unwrap(), "Ada Lovelace\nAlan Turing\n");
+
+        let preview = replace.preview("a\nb\nc\n", "a\nB\nc\n", false);
+        assert_eq!(preview[1], format!("+++ {} (after replace)", path.display()));
+        assert_eq!(preview[2..], ["@@ -1,3 +1,3 @@", " a", "-b", "+B", " c"]);
+
+        let invalid = ReplaceInFile { pattern: "(".to_string(), ..replace };
+        assert_eq!(invalid.substitute("x").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_uses_the_desktop_launcher() {
+        let open = OpenPath { target: "report.pdf".to_string() };
+        let launcher = open.launcher();
+        assert_eq!(launcher.get_program(), "xdg-open");
+        assert_eq!(launcher.get_args().collect::<Vec<_>>(), ["report.pdf"]);
+        assert!(!open.is_url());
+        assert!(OpenPath { target: "https://example.com".to_string() }.is_url());
+
+        let missing = OpenPath { target: "no-such-report.pdf".to_string() };
+        assert_eq!(missing.run().unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_recursive_delete_summarizes_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/one.txt"), "12345").unwrap();
+        fs::write(dir.path().join("a/b/two.txt"), "678").unwrap();
+
+        let remove = RemoveDir { path: dir.path().display().to_string(), recursive: true, ..Default::default() };
+        assert_eq!(remove.contents(), (2, 2, 8));
+
+        let target = dir.path().join("a");
+        // Outside interactive mode nobody can answer the prompt
+        let unconfirmed = RemoveDir { path: target.display().to_string(), recursive: true, ..Default::default() };
+        let mut config = crate::config::RustShellConfig::default();
+        assert_eq!(unconfirmed.confirm(&config).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        config.ui.confirm_destructive = false;
+        assert!(unconfirmed.confirm(&config).unwrap());
+        assert!(target.exists());
+
+        RemoveDir { path: target.display().to_string(), recursive: true, yes: true, ..Default::default() }.run().unwrap();
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_watch_exec_failures_are_reported() {
+        let watch = |exec: &str, command: Box<dyn ShellCommand>| WatchDir {
+            exec: exec.split_whitespace().map(String::from).collect(),
+            exec_command: Some(command),
+            ..Default::default()
+        };
+        let stderr = |watch: &WatchDir| {
+            let command = watch.exec_command.as_deref().unwrap();
+            let ((), captured) = context::capture(context::CaptureMode::Collect, || watch.run_exec(command));
+            String::from_utf8(captured.stderr).unwrap()
+        };
+
+        // A built-in that fails is reported, not only a program
+        let show = watch("show absent", Box::new(ShowFile { paths: vec!["/nonexistent/absent".to_string()] }));
+        assert!(stderr(&show).starts_with("watch-dir: 'show absent' failed: "));
+
+        if cfg!(unix) {
+            let program = ExecuteCommand { command: "sh".to_string(), args: vec!["-c".to_string(), "exit 2".to_string()], ..Default::default() };
+            assert_eq!(stderr(&watch("sh -c exit 2", Box::new(program))), "watch-dir: 'sh -c exit 2' failed (exit code 2)\n");
+        }
+    }
+
+    #[test]
+    fn test_watch_events_are_described_relative_to_the_root() {
+        use notify::event::{AccessKind, CreateKind, EventKind, ModifyKind, RenameMode};
+
+        let root = Path::new("/project");
+        let event = |kind| notify::Event::new(kind).add_path(root.join("src/main.rs"));
+        let kind = |kind| {
+            let lines = WatchDir::describe(root, &event(kind));
+            lines.first().map(|line| line.split_whitespace().skip(1).collect::<Vec<_>>().join(" "))
+        };
+
+        assert_eq!(kind(EventKind::Create(CreateKind::File)).as_deref(), Some("created src/main.rs"));
+        assert_eq!(kind(EventKind::Modify(ModifyKind::Any)).as_deref(), Some("modified src/main.rs"));
+        assert_eq!(kind(EventKind::Modify(ModifyKind::Name(RenameMode::Any))).as_deref(), Some("renamed src/main.rs"));
+        assert_eq!(kind(EventKind::Modify(ModifyKind::Name(RenameMode::From))).as_deref(), Some("removed src/main.rs"));
+        let both = event(EventKind::Modify(ModifyKind::Name(RenameMode::Both))).add_path(root.join("src/lib.rs"));
+        assert!(WatchDir::describe(root, &both)[0].ends_with("renamed   src/main.rs -> src/lib.rs"));
+        assert_eq!(kind(EventKind::Remove(notify::event::RemoveKind::Any)).as_deref(), Some("removed src/main.rs"));
+        assert_eq!(kind(EventKind::Access(AccessKind::Any)), None);
+    }
+
+    #[test]
+    fn test_nl_numbering_styles() {
+        let number = |text: &str, all| {
+            let mut out = Vec::new();
+            NumberLines::number(text.as_bytes(), &mut out, all).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(number("a\n\nb\n", false), "     1\ta\n       \n     2\tb\n");
+        assert_eq!(number("a\r\n\r\nb", true), "     1\ta\n     2\t\n     3\tb\n");
+    }
+}
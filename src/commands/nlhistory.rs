@@ -0,0 +1,96 @@
+// Natural-language requests and the commands they were translated to, kept
+// apart from the command history so effective phrasings can be looked up later.
+//
+// Stored in ~/.rustshell/nl_history as one `time<TAB>request<TAB>command` line
+// per translation, with `time` in seconds since the Unix epoch. New entries
+// are appended, so concurrent sessions don't overwrite each other.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NlEntry {
+    pub time: u64,
+    pub request: String,
+    pub command: String,
+}
+
+pub fn history_file() -> PathBuf {
+    let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".rustshell").join("nl_history")
+}
+
+// Tabs and newlines would break the line format
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn append(file: &Path, entry: &NlEntry) -> io::Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut history = fs::OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(
+        history,
+        "{}\t{}\t{}",
+        entry.time,
+        single_line(&entry.request),
+        single_line(&entry.command)
+    )
+}
+
+// The most recent `count` entries, oldest first
+pub fn read_last(file: &Path, count: usize) -> io::Result<Vec<NlEntry>> {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let entries: Vec<NlEntry> = content.lines().filter_map(parse_line).collect();
+    let skip = entries.len().saturating_sub(count);
+    Ok(entries.into_iter().skip(skip).collect())
+}
+
+fn parse_line(line: &str) -> Option<NlEntry> {
+    let mut fields = line.splitn(3, '\t');
+    let time = fields.next()?.parse().ok()?;
+    let request = fields.next()?.to_string();
+    let command = fields.next()?.to_string();
+    Some(NlEntry { time, request, command })
+}
+
+// Remember a translation. History is a convenience, so failing to write it
+// is reported but never stops the command.
+pub fn record(request: &str, command: &str) {
+    let entry = NlEntry {
+        time: super::dirhistory::now(),
+        request: request.to_string(),
+        command: command.to_string(),
+    };
+    if let Err(e) = append(&history_file(), &entry) {
+        eprintln!("Warning: could not save natural-language history: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("nl_history");
+        assert!(read_last(&file, 5).unwrap().is_empty());
+
+        for (i, request) in ["list all files", "make a\tdir called x", "show the log"].iter().enumerate() {
+            let entry = NlEntry { time: i as u64, request: request.to_string(), command: format!("cmd {}", i) };
+            append(&file, &entry).unwrap();
+        }
+
+        let last = read_last(&file, 2).unwrap();
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[0].request, "make a dir called x");
+        assert_eq!(last[1], NlEntry { time: 2, request: "show the log".to_string(), command: "cmd 2".to_string() });
+    }
+}
@@ -0,0 +1,129 @@
+// Interactive handling of `copy`/`move` onto an existing destination file
+
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::context;
+
+// Decide where `src` should be written. Returns None when the user chose to skip.
+// Only prompts in interactive mode; otherwise the destination is overwritten as before.
+pub fn resolve_destination(src: &Path, dst: &Path) -> io::Result<Option<PathBuf>> {
+    let dst_meta = match fs::metadata(dst) {
+        Ok(meta) if meta.is_file() => meta,
+        _ => return Ok(Some(dst.to_path_buf())),
+    };
+    if !context::is_interactive() {
+        return Ok(Some(dst.to_path_buf()));
+    }
+    let src_meta = fs::metadata(src)?;
+
+    println!("'{}' already exists.", dst.display());
+    println!("  source:      {}", describe_file(&src_meta));
+    println!("  destination: {} ({})", describe_file(&dst_meta), compare(&src_meta, &dst_meta));
+    print!("Overwrite, skip or rename? [o/s/r] (s): ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    match answer.trim().to_lowercase().as_str() {
+        "o" | "overwrite" => Ok(Some(dst.to_path_buf())),
+        "r" | "rename" => {
+            let renamed = unused_name(dst);
+            println!("Writing to '{}' instead.", renamed.display());
+            Ok(Some(renamed))
+        }
+        _ => {
+            println!("Skipped '{}'.", src.display());
+            Ok(None)
+        }
+    }
+}
+
+fn describe_file(meta: &Metadata) -> String {
+    match meta.modified() {
+        Ok(modified) => format!("{} bytes, modified {}", meta.len(), format_age(modified)),
+        Err(_) => format!("{} bytes", meta.len()),
+    }
+}
+
+// How the existing destination compares with the source, e.g. "destination is newer and larger"
+fn compare(src: &Metadata, dst: &Metadata) -> String {
+    let age = match (src.modified(), dst.modified()) {
+        (Ok(s), Ok(d)) if d > s => "newer",
+        (Ok(s), Ok(d)) if d < s => "older",
+        _ => "the same age",
+    };
+    let size = if dst.len() > src.len() {
+        "larger"
+    } else if dst.len() < src.len() {
+        "smaller"
+    } else {
+        "the same size"
+    };
+    format!("destination is {} and {}", age, size)
+}
+
+fn format_age(time: SystemTime) -> String {
+    let secs = match SystemTime::now().duration_since(time) {
+        Ok(elapsed) => elapsed.as_secs(),
+        Err(_) => return "in the future".to_string(),
+    };
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+// First free name of the form `stem (N).ext` next to `path`
+fn unused_name(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unused_name_skips_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("notes.txt");
+        fs::write(&target, "a").unwrap();
+        fs::write(dir.path().join("notes (1).txt"), "b").unwrap();
+
+        assert_eq!(unused_name(&target), dir.path().join("notes (2).txt"));
+    }
+
+    #[test]
+    fn test_compare_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small");
+        let large = dir.path().join("large");
+        fs::write(&small, "a").unwrap();
+        fs::write(&large, "abcdef").unwrap();
+
+        let description = compare(&fs::metadata(&small).unwrap(), &fs::metadata(&large).unwrap());
+        assert!(description.ends_with("and larger"), "{}", description);
+    }
+
+    #[test]
+    fn test_non_interactive_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        fs::write(&src, "new").unwrap();
+        fs::write(&dst, "old").unwrap();
+
+        assert_eq!(resolve_destination(&src, &dst).unwrap(), Some(dst));
+    }
+}
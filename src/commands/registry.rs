@@ -0,0 +1,1525 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use super::*;
+use crate::AliasManager;
+
+// Builds a command from its alias-expanded arguments (command name included).
+// Builders may print an error and return None for malformed input, or handle
+// the command themselves (e.g. `help`) and return None.
+pub type CommandBuilder = fn(&[String], Option<&AliasManager>) -> Option<Box<dyn ShellCommand>>;
+
+pub struct CommandSpec {
+    // Primary name first, followed by any alternative names
+    pub names: &'static [&'static str],
+    // Minimum number of arguments after the command name
+    pub min_args: usize,
+    // Printed when fewer than `min_args` arguments are given
+    pub missing_args: &'static str,
+    pub help: fn() -> String,
+    pub build: CommandBuilder,
+}
+
+impl CommandSpec {
+    pub fn name(&self) -> &'static str {
+        self.names[0]
+    }
+}
+
+// Single source of truth for the built-in commands: parsing, tab completion,
+// `showall`, `help` and the exported reference are all derived from it.
+pub struct CommandRegistry {
+    specs: Vec<CommandSpec>,
+    index: HashMap<&'static str, usize>,
+}
+
+impl CommandRegistry {
+    fn new(specs: Vec<CommandSpec>) -> Self {
+        let mut index = HashMap::new();
+        for (i, spec) in specs.iter().enumerate() {
+            for name in spec.names {
+                let previous = index.insert(*name, i);
+                debug_assert!(previous.is_none(), "command name registered twice: {}", name);
+            }
+        }
+
+        CommandRegistry { specs, index }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.index.get(name).map(|&i| &self.specs[i])
+    }
+
+    pub fn specs(&self) -> &[CommandSpec] {
+        &self.specs
+    }
+
+    // Every name and alternative name, in registration order
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.specs.iter().flat_map(|spec| spec.names.iter().copied())
+    }
+
+    // Build the command for `args`. Unknown names run as system commands.
+    pub fn build(&self, args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+        let spec = match self.get(&args[0]) {
+            Some(spec) => spec,
+            None => {
+                return Some(Box::new(ExecuteCommand {
+                    command: args[0].clone(),
+                    args: args[1..].to_vec(),
+                    ..Default::default()
+                }));
+            }
+        };
+
+        if args.len() - 1 < spec.min_args {
+            errln!("Error: {}", spec.missing_args);
+            return None;
+        }
+
+        (spec.build)(args, alias_manager)
+    }
+}
+
+pub fn registry() -> &'static CommandRegistry {
+    static REGISTRY: OnceLock<CommandRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(builtin_registry)
+}
+
+fn builtin_registry() -> CommandRegistry {
+    CommandRegistry::new(vec![
+        CommandSpec {
+            names: &["make_dir", "mkdir"],
+            min_args: 1,
+            missing_args: "make_dir requires a directory name",
+            help: || MakeDir::default().help(),
+            build: build_make_dir,
+        },
+        CommandSpec {
+            names: &["create_file", "touch"],
+            min_args: 1,
+            missing_args: "create_file requires at least one file name",
+            help: || MakeFile::default().help(),
+            build: |args, _| Some(Box::new(MakeFile { paths: args[1..].to_vec() })),
+        },
+        CommandSpec {
+            names: &["copy", "cp"],
+            min_args: 2,
+            missing_args: "copy requires source and destination paths",
+            help: || CopyFile::default().help(),
+            build: build_copy,
+        },
+        CommandSpec {
+            names: &["move", "mv"],
+            min_args: 2,
+            missing_args: "move requires source and destination paths",
+            help: || MoveFile::default().help(),
+            build: build_move,
+        },
+        CommandSpec {
+            names: &["delete_file", "rm"],
+            min_args: 1,
+            missing_args: "delete_file requires at least one file name",
+            help: || RemoveFile::default().help(),
+            build: |args, _| Some(Box::new(RemoveFile { paths: args[1..].to_vec() })),
+        },
+        CommandSpec {
+            names: &["delete_dir", "rmdir"],
+            min_args: 1,
+            missing_args: "delete_dir requires a directory name",
+            help: || RemoveDir::default().help(),
+            build: build_remove_dir,
+        },
+        CommandSpec {
+            names: &["change_dir", "cd"],
+            min_args: 1,
+            missing_args: "change_dir requires a directory path",
+            help: || ChangeDir::default().help(),
+            build: |args, _| Some(Box::new(ChangeDir { path: args[1].clone() })),
+        },
+        CommandSpec {
+            names: &["j"],
+            min_args: 1,
+            missing_args: "j requires part of a directory name",
+            help: || JumpDir::default().help(),
+            build: |args, _| Some(Box::new(JumpDir { query: args[1..].join(" ") })),
+        },
+        CommandSpec {
+            names: &["list", "ls"],
+            min_args: 0,
+            missing_args: "",
+            help: || ListDir::default().help(),
+            build: build_list,
+        },
+        CommandSpec {
+            names: &["where_am_i", "pwd"],
+            min_args: 0,
+            missing_args: "",
+            help: || CurrentPath::default().help(),
+            build: |_, _| Some(Box::new(CurrentPath {})),
+        },
+        CommandSpec {
+            names: &["run", "exec"],
+            min_args: 1,
+            missing_args: "run requires a command to execute",
+            help: || ExecuteCommand::default().help(),
+            build: build_run,
+        },
+        CommandSpec {
+            names: &["tee"],
+            min_args: 2,
+            missing_args: "tee requires a log file and a command",
+            help: || "Run a command, showing its output and saving it to a file. Usage: tee [-a] <file> <command> [args...]".to_string(),
+            build: build_tee,
+        },
+        CommandSpec {
+            names: &["show", "cat"],
+            min_args: 1,
+            missing_args: "show requires a file path",
+            help: || ShowFile::default().help(),
+            build: |args, _| Some(Box::new(ShowFile { paths: args[1..].to_vec() })),
+        },
+        CommandSpec {
+            names: &["peek"],
+            min_args: 1,
+            missing_args: "peek requires a file path",
+            help: || PeekFile::default().help(),
+            build: build_peek,
+        },
+        CommandSpec {
+            names: &["nl"],
+            min_args: 0,
+            missing_args: "",
+            help: || NumberLines::default().help(),
+            build: build_nl,
+        },
+        CommandSpec {
+            names: &["trim"],
+            min_args: 1,
+            missing_args: "trim requires a file path",
+            help: || TrimFile::default().help(),
+            build: build_trim,
+        },
+        CommandSpec {
+            names: &["watch-dir"],
+            min_args: 0,
+            missing_args: "",
+            help: || WatchDir::default().help(),
+            build: build_watch_dir,
+        },
+        CommandSpec {
+            names: &["replace"],
+            min_args: 3,
+            missing_args: "replace requires a file, a pattern and a replacement",
+            help: || ReplaceInFile::default().help(),
+            build: build_replace,
+        },
+        CommandSpec {
+            names: &["find"],
+            min_args: 1,
+            missing_args: "find requires a pattern to search for",
+            help: || FindFiles::default().help(),
+            build: build_find,
+        },
+        CommandSpec {
+            names: &["du"],
+            min_args: 0,
+            missing_args: "",
+            help: || DiskUsage::default().help(),
+            build: build_du,
+        },
+        CommandSpec {
+            names: &["filetypes"],
+            min_args: 0,
+            missing_args: "",
+            help: || FileTypes::default().help(),
+            build: build_filetypes,
+        },
+        CommandSpec {
+            names: &["recent"],
+            min_args: 0,
+            missing_args: "",
+            help: || RecentFiles::default().help(),
+            build: build_recent,
+        },
+        CommandSpec {
+            names: &["hash", "checksum"],
+            min_args: 1,
+            missing_args: "hash requires at least one file, or --check <manifest>",
+            help: || HashFiles::default().help(),
+            build: build_hash,
+        },
+        CommandSpec {
+            names: &["split"],
+            min_args: 2,
+            missing_args: "split requires a file and a chunk size, e.g. split big.iso 100M",
+            help: || SplitFile::default().help(),
+            build: |args, _| match utils::parse_size(&args[2]).filter(|size| *size > 0) {
+                Some(chunk_size) => Some(Box::new(SplitFile { path: args[1].clone(), chunk_size })),
+                None => {
+                    errln!("Error: invalid chunk size '{}'; use a number of bytes or e.g. 64K, 100M, 1G", args[2]);
+                    None
+                }
+            },
+        },
+        CommandSpec {
+            names: &["join"],
+            min_args: 2,
+            missing_args: "join requires the split file's name and an output file",
+            help: || JoinFiles::default().help(),
+            build: |args, _| Some(Box::new(JoinFiles { prefix: args[1].clone(), output: args[2].clone() })),
+        },
+        CommandSpec {
+            names: &["grep"],
+            min_args: 1,
+            missing_args: "grep requires a pattern to search for",
+            help: || GrepFiles::default().help(),
+            build: build_grep,
+        },
+        CommandSpec {
+            names: &["summarize"],
+            min_args: 1,
+            missing_args: "summarize requires a file path",
+            help: || SummarizeFile::default().help(),
+            build: |args, _| Some(Box::new(SummarizeFile { path: args[1].clone() })),
+        },
+        CommandSpec {
+            names: &["ask"],
+            min_args: 1,
+            missing_args: "ask requires a question",
+            help: || AskCommand::default().help(),
+            build: |args, _| Some(Box::new(AskCommand { question: unquote(&args[1..].join(" ")), command: Vec::new() })),
+        },
+        CommandSpec {
+            names: &["translate"],
+            min_args: 1,
+            missing_args: "translate requires a request to translate",
+            help: || TranslateCommand::default().help(),
+            build: build_translate,
+        },
+        CommandSpec {
+            names: &["translate-batch"],
+            min_args: 1,
+            missing_args: "translate-batch requires a file of steps",
+            help: || TranslateBatch::default().help(),
+            build: |args, _| Some(Box::new(TranslateBatch { path: args[1].clone() })),
+        },
+        CommandSpec {
+            names: &["nl-history"],
+            min_args: 0,
+            missing_args: "",
+            help: || NlHistoryCommand::default().help(),
+            build: |args, _| match args.get(1).map(|count| count.parse::<usize>()) {
+                None => Some(Box::new(NlHistoryCommand { count: None })),
+                Some(Ok(count)) => Some(Box::new(NlHistoryCommand { count: Some(count) })),
+                Some(Err(_)) => {
+                    errln!("Error: nl-history takes a number of entries to show");
+                    None
+                }
+            },
+        },
+        CommandSpec {
+            names: &["stats"],
+            min_args: 0,
+            missing_args: "",
+            help: || UsageStatsCommand::default().help(),
+            build: |args, _| match args.get(1).map(String::as_str) {
+                None => Some(Box::new(UsageStatsCommand::default())),
+                Some("clear") => Some(Box::new(UsageStatsCommand { count: None, clear: true })),
+                Some(count) => match count.parse::<usize>() {
+                    Ok(count) => Some(Box::new(UsageStatsCommand { count: Some(count), clear: false })),
+                    Err(_) => {
+                        errln!("Error: stats takes a number of commands to show, or 'clear'");
+                        None
+                    }
+                },
+            },
+        },
+        CommandSpec {
+            names: &["cache"],
+            min_args: 0,
+            missing_args: "",
+            help: || CacheCommand::default().help(),
+            build: |args, _| match args.get(1).map(String::as_str) {
+                None => Some(Box::new(CacheCommand { clear: false })),
+                Some("clear") => Some(Box::new(CacheCommand { clear: true })),
+                Some(other) => {
+                    errln!("Error: unknown cache action '{}'. Usage: cache [clear]", other);
+                    None
+                }
+            },
+        },
+        CommandSpec {
+            names: &["models"],
+            min_args: 0,
+            missing_args: "",
+            help: || ModelsCommand::default().help(),
+            build: |_, _| Some(Box::new(ModelsCommand {})),
+        },
+        CommandSpec {
+            names: &["llm-test"],
+            min_args: 0,
+            missing_args: "",
+            help: || LlmTest::default().help(),
+            build: |_, _| Some(Box::new(LlmTest {})),
+        },
+        CommandSpec {
+            names: &["llm-bench"],
+            min_args: 0,
+            missing_args: "",
+            help: || LlmBench::default().help(),
+            build: |args, _| match args.get(1).map(|runs| runs.parse::<usize>()) {
+                None => Some(Box::new(LlmBench { runs: None })),
+                Some(Ok(runs)) if runs > 0 => Some(Box::new(LlmBench { runs: Some(runs) })),
+                Some(_) => {
+                    errln!("Error: llm-bench takes a positive number of runs");
+                    None
+                }
+            },
+        },
+        CommandSpec {
+            names: &["doctor"],
+            min_args: 0,
+            missing_args: "",
+            help: || Doctor::default().help(),
+            build: |_, _| Some(Box::new(Doctor {})),
+        },
+        CommandSpec {
+            names: &["compress", "zip"],
+            min_args: 2,
+            missing_args: "compress requires source and destination paths",
+            help: || CompressFiles::default().help(),
+            build: build_compress,
+        },
+        CommandSpec {
+            names: &["copy-to-clipboard", "yank"],
+            min_args: 1,
+            missing_args: "copy-to-clipboard requires a file, or --run followed by a command",
+            help: || CopyToClipboard::default().help(),
+            build: build_copy_to_clipboard,
+        },
+        CommandSpec {
+            names: &["open"],
+            min_args: 1,
+            missing_args: "open requires a file or URL",
+            help: || OpenPath::default().help(),
+            build: |args, _| Some(Box::new(OpenPath { target: args[1].clone() })),
+        },
+        CommandSpec {
+            names: &["paste"],
+            min_args: 0,
+            missing_args: "",
+            help: || PasteClipboard::default().help(),
+            build: build_paste,
+        },
+        CommandSpec {
+            names: &["alias"],
+            min_args: 0,
+            missing_args: "",
+            help: || AliasCommand::default().help(),
+            build: build_alias,
+        },
+        CommandSpec {
+            names: &["unalias"],
+            min_args: 1,
+            missing_args: "unalias requires an alias name",
+            help: || UnaliasCommand::default().help(),
+            build: |args, _| Some(Box::new(UnaliasCommand { name: args[1].clone() })),
+        },
+        CommandSpec {
+            names: &["export"],
+            min_args: 1,
+            missing_args: "export requires NAME=value",
+            help: || ExportVar::default().help(),
+            build: build_export,
+        },
+        CommandSpec {
+            names: &["type"],
+            min_args: 1,
+            missing_args: "type requires at least one name",
+            help: || TypeCommand::default().help(),
+            build: |args, _| Some(Box::new(TypeCommand { names: args[1..].to_vec() })),
+        },
+        CommandSpec {
+            names: &["repeat"],
+            min_args: 2,
+            missing_args: "repeat requires a count and a command",
+            help: || "Run a command N times; $i is replaced by the iteration number. Usage: repeat <N> <command> [args...]".to_string(),
+            build: build_repeat,
+        },
+        CommandSpec {
+            names: &["for"],
+            min_args: 4,
+            missing_args: "for requires a variable, a list and a command. Use: for x in a b c: <command>",
+            help: || "Run a command once per item, replacing $x with the item. Usage: for <x> in <items...>: <command> [args...]".to_string(),
+            build: build_for,
+        },
+        CommandSpec {
+            names: &["pipe"],
+            min_args: 2,
+            missing_args: "pipe requires at least two commands",
+            help: || PipeCommand::default().help(),
+            build: build_pipe,
+        },
+        CommandSpec {
+            names: &["completions"],
+            min_args: 1,
+            missing_args: "completions requires a shell name (bash, zsh, fish or powershell)",
+            help: || GenerateCompletions::default().help(),
+            build: |args, _| Some(Box::new(GenerateCompletions { shell: args[1].to_lowercase() })),
+        },
+        CommandSpec {
+            names: &["reload"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Re-read the configuration and aliases (interactive mode). Usage: reload".to_string(),
+            build: |_, _| interactive_only("reload"),
+        },
+        CommandSpec {
+            names: &["save-session"],
+            min_args: 1,
+            missing_args: "save-session requires a file name",
+            help: || "Save this session's commands as a script to replay with --script (interactive mode). Usage: save-session <file>".to_string(),
+            build: |_, _| interactive_only("save-session"),
+        },
+        CommandSpec {
+            names: &["jobs"],
+            min_args: 0,
+            missing_args: "",
+            help: || "List background jobs started with a trailing & (interactive mode). Usage: jobs".to_string(),
+            build: |_, _| interactive_only("jobs"),
+        },
+        CommandSpec {
+            names: &["fg"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Wait for a background job to finish, the most recent one by default (interactive mode). Usage: fg [n]".to_string(),
+            build: |_, _| interactive_only("fg"),
+        },
+        CommandSpec {
+            names: &["interactive"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Start interactive mode. Usage: interactive".to_string(),
+            build: |_, _| {
+                println!("Already in interactive mode.");
+                None
+            },
+        },
+        CommandSpec {
+            names: &["showall"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Display all available commands. Usage: showall".to_string(),
+            build: |_, alias_manager| {
+                print_all_commands(alias_manager);
+                None
+            },
+        },
+        CommandSpec {
+            names: &["help"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Show help, or export the command reference. Usage: help [--export markdown|roff]".to_string(),
+            build: build_help,
+        },
+        CommandSpec {
+            names: &["exit", "quit"],
+            min_args: 0,
+            missing_args: "",
+            help: || "Leave interactive mode. Usage: exit".to_string(),
+            build: |args, _| interactive_only(&args[0]),
+        },
+    ])
+}
+
+fn interactive_only(name: &str) -> Option<Box<dyn ShellCommand>> {
+    errln!("Error: {} is only available in interactive mode", name);
+    None
+}
+
+fn build_make_dir(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut parents = false;
+    let mut exists_ok = false;
+    let mut paths = Vec::new();
+
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-p" => parents = true,
+            "--exists-ok" => exists_ok = true,
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    if paths.is_empty() {
+        errln!("Error: make_dir requires a directory name");
+        return None;
+    }
+    Some(Box::new(MakeDir { paths, parents, exists_ok }))
+}
+
+fn build_list(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut list = ListDir::default();
+
+    for arg in &args[1..] {
+        if arg == "--table" {
+            list.table = true;
+            continue;
+        }
+        match arg.strip_prefix('-') {
+            // Short flags may be combined, e.g. `-la`
+            Some(flags) if !flags.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'l' => list.long = true,
+                        '1' => list.one_per_line = true,
+                        'a' => list.all = true,
+                        'h' => list.human = true,
+                        _ => {
+                            errln!("Error: unknown list option '-{}'", flag);
+                            return None;
+                        }
+                    }
+                }
+            }
+            _ => list.path = Some(arg.clone()),
+        }
+    }
+
+    Some(Box::new(list))
+}
+
+fn build_translate(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut translate = TranslateCommand::default();
+    let mut words = Vec::new();
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        if arg != "--output" {
+            words.push(arg.as_str());
+            continue;
+        }
+        translate.output = match args.next().map(String::as_str) {
+            Some("json") => OutputFormat::Json,
+            Some("text") => OutputFormat::Text,
+            _ => {
+                errln!("Error: --output must be 'json' or 'text'");
+                return None;
+            }
+        };
+    }
+
+    // The request may be quoted as one argument or given as separate words
+    translate.request = words.join(" ").trim_matches(|c| c == '"' || c == '\'').to_string();
+    if translate.request.is_empty() {
+        errln!("Error: translate requires a request to translate");
+        return None;
+    }
+    Some(Box::new(translate))
+}
+
+fn build_export(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut assignments = Vec::new();
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            Some((name, value)) if !name.is_empty() && !name.contains('\0') => {
+                assignments.push((name.to_string(), value.to_string()));
+            }
+            _ => {
+                errln!("Error: invalid assignment '{}'. Use: export NAME=value", arg);
+                return None;
+            }
+        }
+    }
+    Some(Box::new(ExportVar { assignments }))
+}
+
+fn build_filetypes(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut filetypes = FileTypes::default();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sort" => match args.next().map(String::as_str) {
+                Some("count") => filetypes.by_size = false,
+                Some("size") => filetypes.by_size = true,
+                _ => {
+                    errln!("Error: --sort takes count or size");
+                    return None;
+                }
+            },
+            "--top" => match args.next().and_then(|top| top.parse::<usize>().ok()).filter(|top| *top > 0) {
+                Some(top) => filetypes.top = Some(top),
+                None => {
+                    errln!("Error: --top requires a positive number of extensions");
+                    return None;
+                }
+            },
+            _ if filetypes.path.is_none() => filetypes.path = Some(arg.clone()),
+            _ => {
+                errln!("Error: too many arguments. Usage: filetypes [--sort count|size] [--top N] [directory]");
+                return None;
+            }
+        }
+    }
+    Some(Box::new(filetypes))
+}
+
+fn build_recent(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut recent = RecentFiles::default();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" | "-n" => match args.next().and_then(|count| count.parse::<usize>().ok()).filter(|count| *count > 0) {
+                Some(count) => recent.count = Some(count),
+                None => {
+                    errln!("Error: --count requires a positive number of files");
+                    return None;
+                }
+            },
+            "--since" => match args.next().and_then(|since| utils::parse_duration(since)) {
+                Some(since) => recent.since = Some(since),
+                None => {
+                    errln!("Error: --since requires a duration such as 30m, 1h or 2d");
+                    return None;
+                }
+            },
+            "-a" | "--all" => recent.all = true,
+            _ if recent.path.is_none() => recent.path = Some(arg.clone()),
+            _ => {
+                errln!("Error: too many arguments. Usage: recent [--count N] [--since 1h] [-a] [directory]");
+                return None;
+            }
+        }
+    }
+    Some(Box::new(recent))
+}
+
+fn build_hash(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut hash = HashFiles::default();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--algo" => match args.next().and_then(|algo| HashAlgo::parse(algo)) {
+                Some(algo) => hash.algo = algo,
+                None => {
+                    errln!("Error: --algo takes sha256, sha1 or md5");
+                    return None;
+                }
+            },
+            "--check" | "-c" => match args.next() {
+                Some(manifest) => hash.check = Some(manifest.clone()),
+                None => {
+                    errln!("Error: --check requires a checksum file");
+                    return None;
+                }
+            },
+            "--parallel" => hash.jobs = job_count(arg, None),
+            "--jobs" | "-j" => hash.jobs = Some(job_count(arg, args.next())?),
+            _ => hash.paths.push(arg.clone()),
+        }
+    }
+
+    match (&hash.check, hash.paths.is_empty()) {
+        (Some(_), false) => errln!("Error: --check reads the files to verify from the checksum file"),
+        (None, true) => errln!("Error: hash requires at least one file"),
+        _ => return Some(Box::new(hash)),
+    }
+    None
+}
+
+// Threads for --parallel (one per CPU) or --jobs N, None for a bad count
+fn job_count(flag: &str, count: Option<&String>) -> Option<usize> {
+    if flag == "--parallel" {
+        return Some(walk::default_jobs());
+    }
+    let jobs = count.and_then(|count| count.parse::<usize>().ok()).filter(|jobs| *jobs > 0);
+    if jobs.is_none() {
+        errln!("Error: {} requires a positive number of threads", flag);
+    }
+    jobs
+}
+
+// The pattern following --exclude
+fn exclude_pattern(pattern: Option<&String>) -> Option<String> {
+    let pattern = pattern.filter(|pattern| !pattern.is_empty()).cloned();
+    if pattern.is_none() {
+        errln!("Error: --exclude requires a pattern, e.g. --exclude target");
+    }
+    pattern
+}
+
+fn build_du(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut du = DiskUsage::default();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--exclude" => du.excludes.push(exclude_pattern(args.next())?),
+            "--no-ignore" => du.no_ignore = true,
+            "-x" | "--one-file-system" => du.one_file_system = true,
+            "--parallel" => du.jobs = job_count(arg, None),
+            "--jobs" | "-j" => du.jobs = Some(job_count(arg, args.next())?),
+            _ => du.paths.push(arg.clone()),
+        }
+    }
+    Some(Box::new(du))
+}
+
+fn build_compress(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut compress = CompressFiles::default();
+    let mut operands = Vec::new();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strip-root" => compress.strip_root = true,
+            "--exclude" => compress.excludes.push(exclude_pattern(args.next())?),
+            "--no-ignore" => compress.no_ignore = true,
+            "-x" | "--one-file-system" => compress.one_file_system = true,
+            _ => operands.push(arg.clone()),
+        }
+    }
+
+    let [source, destination] = operands.as_slice() else {
+        errln!("Error: compress requires source and destination paths. Usage: compress [--strip-root] [--exclude GLOB]... [--no-ignore] [-x|--one-file-system] <source> <destination>");
+        return None;
+    };
+    compress.source = source.clone();
+    compress.destination = destination.clone();
+    Some(Box::new(compress))
+}
+
+fn build_find(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut find = FindFiles::default();
+    let mut operands = Vec::new();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--table" => find.table = true,
+            "-0" | "--null" => find.null = true,
+            "-L" | "--follow-symlinks" => find.follow_symlinks = true,
+            "--max-depth" => match args.next().and_then(|depth| depth.parse::<usize>().ok()) {
+                Some(depth) => find.max_depth = Some(depth),
+                None => {
+                    errln!("Error: --max-depth requires a number of levels");
+                    return None;
+                }
+            },
+            "--newer-than" | "--older-than" => match args.next().and_then(|age| utils::parse_duration(age)) {
+                Some(age) if arg == "--newer-than" => find.newer_than = Some(age),
+                Some(age) => find.older_than = Some(age),
+                None => {
+                    errln!("Error: {} requires a duration such as 30m, 1h or 2d", arg);
+                    return None;
+                }
+            },
+            "--larger-than" | "--smaller-than" => match args.next().and_then(|size| utils::parse_size(size)) {
+                Some(size) if arg == "--larger-than" => find.larger_than = Some(size),
+                Some(size) => find.smaller_than = Some(size),
+                None => {
+                    errln!("Error: {} requires a size such as 512, 64K or 10M", arg);
+                    return None;
+                }
+            },
+            "--exclude" => find.excludes.push(exclude_pattern(args.next())?),
+            "--no-ignore" => find.no_ignore = true,
+            "-x" | "--one-file-system" => find.one_file_system = true,
+            "--parallel" => find.jobs = job_count(arg, None),
+            "--jobs" | "-j" => find.jobs = Some(job_count(arg, args.next())?),
+            _ => operands.push(arg.clone()),
+        }
+    }
+
+    if find.table && find.null {
+        errln!("Error: find --table and -0 can't be used together");
+        return None;
+    }
+
+    // A pattern typed in quotes, to keep its braces, is matched without them
+    match operands.as_slice() {
+        [pattern] => find.pattern = unquote(pattern),
+        [pattern, path] => {
+            find.pattern = unquote(pattern);
+            find.path = Some(path.clone());
+        }
+        [] => {
+            errln!("Error: find requires a pattern to search for");
+            return None;
+        }
+        _ => {
+            errln!("Error: too many arguments. Usage: find [--table|-0] [-L|--follow-symlinks] [--max-depth N] [--newer-than 2d] [--older-than 1w] [--larger-than 10M] [--smaller-than 1K] [--exclude GLOB]... [--no-ignore] [-x|--one-file-system] [--parallel|--jobs N] <pattern> [directory]");
+            return None;
+        }
+    }
+    Some(Box::new(find))
+}
+
+fn build_peek(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut peek = PeekFile::default();
+    let mut paths = Vec::new();
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        if !matches!(flag, "--head" | "--tail" | "-n") {
+            paths.push(arg.clone());
+            continue;
+        }
+        let Some(count) = args.next().and_then(|count| count.parse::<usize>().ok()) else {
+            errln!("Error: {} requires a number of lines", flag);
+            return None;
+        };
+        match flag {
+            "--head" => peek.head = Some(count),
+            "--tail" => peek.tail = Some(count),
+            _ => {
+                peek.head = Some(count);
+                peek.tail = Some(count);
+            }
+        }
+    }
+
+    match paths.as_slice() {
+        [path] => peek.path = path.clone(),
+        [] => {
+            errln!("Error: peek requires a file path");
+            return None;
+        }
+        _ => {
+            errln!("Error: peek takes one file. Usage: peek [--head N] [--tail N] [-n N] <file>");
+            return None;
+        }
+    }
+    Some(Box::new(peek))
+}
+
+fn build_nl(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut nl = NumberLines::default();
+    let mut paths = Vec::new();
+
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-ba" => nl.all = true,
+            "-bt" => nl.all = false,
+            flag if flag.starts_with("-b") => {
+                errln!("Error: unsupported numbering style '{}'; use -ba or -bt", flag);
+                return None;
+            }
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    match paths.as_slice() {
+        [] => {}
+        [path] => nl.path = Some(path.clone()),
+        _ => {
+            errln!("Error: nl takes one file. Usage: nl [-ba|-bt] [file]");
+            return None;
+        }
+    }
+    Some(Box::new(nl))
+}
+
+fn build_trim(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut trim = TrimFile::default();
+    let mut paths = Vec::new();
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdout" => trim.stdout = true,
+            "--tabs-to-spaces" => match args.next().and_then(|width| width.parse::<usize>().ok()) {
+                Some(width) if width > 0 => trim.tabs_to_spaces = Some(width),
+                _ => {
+                    errln!("Error: --tabs-to-spaces requires a positive tab width");
+                    return None;
+                }
+            },
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    match paths.as_slice() {
+        [path] => trim.path = path.clone(),
+        [] => {
+            errln!("Error: trim requires a file path");
+            return None;
+        }
+        _ => {
+            errln!("Error: trim takes one file. Usage: trim [--stdout] [--tabs-to-spaces N] <file>");
+            return None;
+        }
+    }
+    Some(Box::new(trim))
+}
+
+fn build_watch_dir(args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    // Everything after --exec is the command to run
+    let (operands, exec) = match args[1..].iter().position(|arg| arg == "--exec") {
+        Some(at) => (&args[1..at + 1], &args[at + 2..]),
+        None => (&args[1..], &[][..]),
+    };
+    if args.iter().any(|arg| arg == "--exec") && exec.is_empty() {
+        errln!("Error: --exec requires a command to run");
+        return None;
+    }
+
+    match operands {
+        [] | [_] => {
+            let exec_command = match exec {
+                [] => None,
+                _ => Some(crate::parse_command(exec, alias_manager)?),
+            };
+            Some(Box::new(WatchDir {
+                path: operands.first().cloned().unwrap_or_else(|| ".".to_string()),
+                exec: exec.to_vec(),
+                exec_command,
+            }))
+        }
+        _ => {
+            errln!("Error: watch-dir watches one directory. Usage: watch-dir [path] [--exec <command>...]");
+            None
+        }
+    }
+}
+
+fn build_replace(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut replace = ReplaceInFile::default();
+    let mut operands = Vec::new();
+
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--backup" => replace.backup = true,
+            "--stdout" => replace.stdout = true,
+            "--count" => replace.count = true,
+            "--dry-run" | "-n" => replace.dry_run = true,
+            _ => operands.push(arg.clone()),
+        }
+    }
+
+    let [path, pattern, replacement] = operands.as_slice() else {
+        errln!("Error: replace takes a file, a pattern and a replacement. Usage: replace [--dry-run] [--backup] [--stdout] [--count] <file> <pattern> <replacement>");
+        return None;
+    };
+    if replace.backup && replace.stdout {
+        errln!("Error: --backup has nothing to do with --stdout, which leaves the file unchanged");
+        return None;
+    }
+    replace.path = path.clone();
+    replace.pattern = pattern.clone();
+    replace.replacement = replacement.clone();
+    Some(Box::new(replace))
+}
+
+fn build_grep(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut grep = GrepFiles::default();
+    let mut pattern = None;
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-i" if pattern.is_none() => grep.ignore_case = true,
+            "--binary" if pattern.is_none() => grep.binary = BinaryMode::Include,
+            "--text" | "-a" if pattern.is_none() => grep.binary = BinaryMode::Text,
+            "--parallel" if pattern.is_none() => grep.jobs = job_count(arg, None),
+            "--jobs" | "-j" if pattern.is_none() => grep.jobs = Some(job_count(arg, args.next())?),
+            "-A" | "-B" | "-C" | "--context" if pattern.is_none() => {
+                let Some(lines) = args.next().and_then(|lines| lines.parse::<usize>().ok()) else {
+                    errln!("Error: {} requires a number of lines", arg);
+                    return None;
+                };
+                match arg.as_str() {
+                    "-A" => grep.after = lines,
+                    "-B" => grep.before = lines,
+                    _ => (grep.before, grep.after) = (lines, lines),
+                }
+            }
+            _ if pattern.is_none() => pattern = Some(arg.clone()),
+            _ => grep.paths.push(arg.clone()),
+        }
+    }
+
+    match pattern {
+        Some(pattern) => {
+            grep.pattern = pattern;
+            Some(Box::new(grep))
+        }
+        None => {
+            errln!("Error: grep requires a pattern to search for");
+            None
+        }
+    }
+}
+
+fn build_remove_dir(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let mut recursive = false;
+    let mut force_dangerous = false;
+    let mut yes = false;
+    let mut path = None;
+
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-r" => recursive = true,
+            "--force-dangerous" => force_dangerous = true,
+            "--yes" | "-y" => yes = true,
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            errln!("Error: delete_dir requires a directory name");
+            return None;
+        }
+    };
+
+    Some(Box::new(RemoveDir {
+        path,
+        recursive,
+        force_dangerous,
+        yes,
+    }))
+}
+
+fn build_alias(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    match args.get(1).map(String::as_str) {
+        Some("--edit") => {
+            return match &args[2..] {
+                [name] => Some(Box::new(EditAlias { name: name.clone() })),
+                _ => {
+                    errln!("Error: invalid alias syntax. Use: alias --edit <name>");
+                    None
+                }
+            };
+        }
+        Some("--rename") => {
+            return match &args[2..] {
+                [old, new] => Some(Box::new(RenameAlias { old: old.clone(), new: new.clone() })),
+                _ => {
+                    errln!("Error: invalid alias syntax. Use: alias --rename <old> <new>");
+                    None
+                }
+            };
+        }
+        _ => {}
+    }
+
+    if args.len() == 1 {
+        // List aliases
+        Some(Box::new(AliasCommand {
+            name: None,
+            command: None,
+        }))
+    } else if args.len() >= 3 {
+        // Create alias: alias name command args...
+        Some(Box::new(AliasCommand {
+            name: Some(args[1].clone()),
+            // `alias gl 'git log | head'` keeps the pipe, not the quotes
+            command: Some(unquote(&args[2..].join(" "))),
+        }))
+    } else {
+        errln!("Error: invalid alias syntax. Use: alias <name> <command>");
+        None
+    }
+}
+
+// Leading single-letter flags, which may be combined (`-rv`), and the
+// arguments after them; `--` ends the flags. None if a flag isn't one of `allowed`.
+fn split_flags<'a>(args: &'a [String], allowed: &str) -> Option<(Vec<char>, &'a [String])> {
+    let mut flags = Vec::new();
+    let mut rest = args;
+    while let Some((arg, tail)) = rest.split_first() {
+        if arg == "--" {
+            return Some((flags, tail));
+        }
+        let letters = match arg.strip_prefix('-') {
+            Some(letters) if !letters.is_empty() => letters,
+            _ => break,
+        };
+        for letter in letters.chars() {
+            if !allowed.contains(letter) {
+                return None;
+            }
+            flags.push(letter);
+        }
+        rest = tail;
+    }
+    Some((flags, rest))
+}
+
+fn build_copy(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let Some((flags, paths)) = split_flags(&args[1..], "rRv") else {
+        errln!("Error: unknown option. Usage: copy [-r] [-v] <source> <destination>");
+        return None;
+    };
+
+    match paths {
+        [src, dst] => Some(Box::new(CopyFile {
+            src: src.clone(),
+            dst: dst.clone(),
+            recursive: flags.contains(&'r') || flags.contains(&'R'),
+            verbose: flags.contains(&'v'),
+        })),
+        _ => {
+            errln!("Error: copy requires source and destination paths");
+            None
+        }
+    }
+}
+
+fn build_move(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let Some((flags, paths)) = split_flags(&args[1..], "v") else {
+        errln!("Error: unknown option. Usage: move [-v] <source> <destination>");
+        return None;
+    };
+
+    match paths {
+        [src, dst] => Some(Box::new(MoveFile {
+            src: src.clone(),
+            dst: dst.clone(),
+            verbose: flags.contains(&'v'),
+        })),
+        _ => {
+            errln!("Error: move requires source and destination paths");
+            None
+        }
+    }
+}
+
+fn build_run(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let (log, rest) = match &args[1..] {
+        [flag, log, rest @ ..] if flag == "--log" => (Some(log.clone()), rest),
+        [flag, ..] if flag == "--log" => {
+            errln!("Error: --log requires a file name");
+            return None;
+        }
+        rest => (None, rest),
+    };
+
+    match rest.split_first() {
+        Some((command, command_args)) => Some(Box::new(ExecuteCommand {
+            command: command.clone(),
+            args: command_args.to_vec(),
+            log,
+            append: false,
+        })),
+        None => {
+            errln!("Error: run requires a command to execute");
+            None
+        }
+    }
+}
+
+fn build_tee(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let append = args[1] == "-a";
+    let rest = if append { &args[2..] } else { &args[1..] };
+
+    match rest {
+        [log, command, command_args @ ..] => Some(Box::new(ExecuteCommand {
+            command: command.clone(),
+            args: command_args.to_vec(),
+            log: Some(log.clone()),
+            append,
+        })),
+        _ => {
+            errln!("Error: tee requires a log file and a command");
+            None
+        }
+    }
+}
+
+fn build_copy_to_clipboard(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    if args[1] == "--run" {
+        if args.len() < 3 {
+            errln!("Error: --run requires a command");
+            return None;
+        }
+        return Some(Box::new(CopyToClipboard { path: None, command: args[2..].to_vec() }));
+    }
+
+    Some(Box::new(CopyToClipboard { path: Some(args[1].clone()), command: Vec::new() }))
+}
+
+fn build_paste(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    // Accept both `paste > file` and `paste file`
+    let path = match &args[1..] {
+        [] => None,
+        [redirect, path] if redirect == ">" => Some(path.clone()),
+        [path] if path != ">" => Some(path.clone()),
+        _ => {
+            errln!("Error: invalid paste syntax. Use: paste [> <file>]");
+            return None;
+        }
+    };
+
+    Some(Box::new(PasteClipboard { path }))
+}
+
+// Replace `$name` with `value` in every argument
+fn substitute(args: &[String], name: &str, value: &str) -> Vec<String> {
+    let placeholder = format!("${}", name);
+    args.iter().map(|arg| arg.replace(&placeholder, value)).collect()
+}
+
+// Build every iteration through the normal dispatch, so aliases and built-ins
+// behave exactly as when typed directly
+fn build_sequence(iterations: Vec<Vec<String>>, alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let commands = iterations
+        .iter()
+        .map(|args| crate::parse_command(args, alias_manager))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Box::new(CommandSequence { commands }))
+}
+
+// Every iteration is parsed before the first one runs, so the count is capped
+const MAX_REPEAT: usize = 10_000;
+
+fn build_repeat(args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let count: usize = match args[1].parse() {
+        Ok(count) => count,
+        Err(_) => {
+            errln!("Error: repeat count must be a non-negative number, got '{}'", args[1]);
+            return None;
+        }
+    };
+    if count > MAX_REPEAT {
+        errln!("Error: repeat runs a command at most {} times, got {}", MAX_REPEAT, count);
+        return None;
+    }
+
+    let command = &args[2..];
+    let iterations = (1..=count).map(|i| substitute(command, "i", &i.to_string())).collect();
+    build_sequence(iterations, alias_manager)
+}
+
+fn build_for(args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    let usage = || {
+        errln!("Error: invalid for syntax. Use: for x in a b c: <command>");
+        None
+    };
+
+    let variable = &args[1];
+    if args[2] != "in" {
+        return usage();
+    }
+
+    // Items run up to the one ending in ':' (or a lone ':')
+    let mut items = Vec::new();
+    let mut command_start = None;
+    for (i, arg) in args.iter().enumerate().skip(3) {
+        if let Some(item) = arg.strip_suffix(':') {
+            if !item.is_empty() {
+                items.push(item.to_string());
+            }
+            command_start = Some(i + 1);
+            break;
+        }
+        items.push(arg.clone());
+    }
+
+    let command = match command_start {
+        Some(start) if start < args.len() => &args[start..],
+        _ => return usage(),
+    };
+
+    let iterations = items.iter().map(|item| substitute(command, variable, item)).collect();
+    build_sequence(iterations, alias_manager)
+}
+
+fn build_pipe(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    // Each argument becomes a separate command in the pipeline
+    let commands: Vec<Vec<String>> = args[1..]
+        .iter()
+        .map(|cmd_str| cmd_str.split_whitespace().map(String::from).collect())
+        .collect();
+
+    Some(Box::new(PipeCommand { commands }))
+}
+
+fn build_help(args: &[String], _: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    if args.len() > 1 && args[1] == "--export" {
+        let format = args.get(2).map(String::as_str).unwrap_or("markdown");
+        match export_help(format) {
+            Some(reference) => print!("{}", reference),
+            None => errln!("Error: unknown export format '{}'. Use markdown or roff", format),
+        }
+        return None;
+    }
+
+    print_help();
+    None
+}
+
+// Split a help string of the form "Description. Usage: syntax"
+fn split_help(help: &str) -> (&str, Option<&str>) {
+    match help.split_once(" Usage: ") {
+        Some((description, usage)) => (description, Some(usage)),
+        None => (help, None),
+    }
+}
+
+// Render the command reference as Markdown or roff (man page) for packaging
+pub fn export_help(format: &str) -> Option<String> {
+    let mut out = String::new();
+
+    match format {
+        "markdown" | "md" => {
+            out.push_str("# RustShell Command Reference\n");
+            for spec in registry().specs() {
+                let help = (spec.help)();
+                let (description, usage) = split_help(&help);
+                out.push_str(&format!("\n## {}\n\n{}\n", spec.names.join(" / "), description));
+                if let Some(usage) = usage {
+                    out.push_str(&format!("\nUsage: `{}`\n", usage));
+                }
+            }
+        }
+        "roff" | "man" => {
+            out.push_str(".TH RUSTSHELL 1\n.SH NAME\nrustshell \\- cross-platform shell with natural language commands\n");
+            out.push_str(".SH SYNOPSIS\n.B rustshell\n[\\fIOPTIONS\\fR] [\\fICOMMAND\\fR [\\fIARGS\\fR...]]\n.SH COMMANDS\n");
+            for spec in registry().specs() {
+                let help = (spec.help)();
+                let (description, usage) = split_help(&help);
+                out.push_str(&format!(".TP\n.B {}\n{}\n", spec.names.join(", "), description.replace('-', "\\-")));
+                if let Some(usage) = usage {
+                    out.push_str(&format!(".br\nUsage: {}\n", usage.replace('-', "\\-")));
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(out)
+}
+
+pub fn print_help() {
+    println!("Cross-Platform Shell - Available Commands:");
+    for spec in registry().specs() {
+        let help = (spec.help)();
+        let (description, usage) = split_help(&help);
+        let usage = usage.unwrap_or(spec.name());
+        let description = description.trim_end_matches('.');
+        if usage.len() > 28 {
+            // Too long for the column: put the description on its own line
+            println!("  {}\n  {:<28} {}", usage, "", description);
+        } else {
+            println!("  {:<28} {}", usage, description);
+        }
+    }
+
+    println!();
+    println!("Note: Traditional shell commands (mkdir, ls, etc.) also work.");
+    println!();
+
+    // Print OS-specific information
+    let os_info = if cfg!(windows) {
+        "Windows"
+    } else if cfg!(target_os = "macos") {
+        "macOS"
+    } else if cfg!(target_os = "linux") {
+        "Linux"
+    } else {
+        "Unknown OS"
+    };
+
+    println!("Current OS: {}", os_info);
+}
+
+// The `showall` listing: every command with its alternative names, then aliases
+fn print_all_commands(alias_manager: Option<&AliasManager>) {
+    println!("Available commands:");
+
+    // Display commands in columns
+    let mut count = 0;
+    for spec in registry().specs() {
+        print!("{:<20}", spec.names.join("/"));
+        count += 1;
+        if count % 4 == 0 {
+            println!();
+        }
+    }
+    if count % 4 != 0 {
+        println!();
+    }
+
+    // Also display aliases if available
+    if let Some(manager) = alias_manager {
+        if !manager.aliases.is_empty() {
+            println!("\nDefined aliases:");
+            for (name, command) in &manager.aliases {
+                println!("  {} = '{}'", name, command);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_alternative_names_share_a_spec() {
+        let registry = registry();
+        assert_eq!(registry.get("mkdir").unwrap().name(), "make_dir");
+        assert_eq!(registry.get("rm").unwrap().name(), "delete_file");
+        assert_eq!(registry.get("cp").unwrap().name(), "copy");
+        assert_eq!(registry.get("mv").unwrap().name(), "move");
+        assert!(registry.get("no_such_command").is_none());
+    }
+
+    #[test]
+    fn test_arity_and_fallback() {
+        let registry = registry();
+        assert!(registry.build(&args("copy only_source"), None).is_none());
+        assert!(registry.build(&args("copy a b"), None).is_some());
+        assert!(registry.build(&args("copy -rv src backup"), None).is_some());
+        assert!(registry.build(&args("copy -x a b"), None).is_none());
+        assert!(registry.build(&args("move -v -- -odd-name b"), None).is_some());
+        // Unknown commands run as system commands
+        assert!(registry.build(&args("git status"), None).is_some());
+
+        assert!(registry.build(&args("paste > notes.txt"), None).is_some());
+        assert!(registry.build(&args("paste >"), None).is_none());
+        assert!(registry.build(&args("yank --run"), None).is_none());
+    }
+
+    #[test]
+    fn test_repeat_and_for() {
+        let registry = registry();
+        assert!(registry.build(&args("repeat 3 create_file part$i.txt"), None).is_some());
+        assert!(registry.build(&args("repeat three pwd"), None).is_none());
+        assert!(registry.build(&args("repeat 10000 pwd"), None).is_some());
+        assert!(registry.build(&args("repeat 10001 pwd"), None).is_none());
+        assert!(registry.build(&args("repeat 18446744073709551615 pwd"), None).is_none());
+        assert!(registry.build(&args("for f in a b c: show $f"), None).is_some());
+        assert!(registry.build(&args("for f in a b : show $f"), None).is_some());
+        assert!(registry.build(&args("for f in a b show $f"), None).is_none());
+        // An iteration that fails to parse fails the whole loop
+        assert!(registry.build(&args("repeat 2 copy only_source"), None).is_none());
+
+        assert_eq!(substitute(&args("touch part$i.txt"), "i", "2"), args("touch part2.txt"));
+    }
+
+    #[test]
+    fn test_help_export() {
+        let markdown = export_help("markdown").unwrap();
+        assert!(markdown.contains("## make_dir / mkdir"));
+        assert!(markdown.contains("Usage: `make_dir [-p] [--exists-ok] <directory>...`"));
+
+        let roff = export_help("roff").unwrap();
+        assert!(roff.starts_with(".TH RUSTSHELL 1"));
+        assert!(roff.contains(".B delete_dir, rmdir"));
+
+        assert!(export_help("html").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+// How a failed command is reported, in interactive and command mode alike:
+//
+//   error: show notse.txt
+//     cause: No such file or directory (os error 2)
+//     hint: did you mean 'notes.txt'?
+//
+// The hint is only given when there is a likely fix, such as a path with a typo.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use super::registry;
+use crate::utils;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+// Print the report for `command` (alias-expanded) to stderr, in color when
+// `colored` and stderr is a terminal
+pub fn report(command: &[String], error: &io::Error, colored: bool) {
+    for line in format_error(command, error, colored && io::stderr().is_terminal()) {
+        eprintln!("{}", line);
+    }
+}
+
+pub fn format_error(command: &[String], error: &io::Error, colored: bool) -> Vec<String> {
+    let paint = |color: &str, text: &str| if colored { format!("{}{}{}", color, text, RESET) } else { text.to_string() };
+
+    let mut lines = vec![
+        format!("{} {}", paint(RED, "error:"), command.join(" ")),
+        format!("  cause: {}", error),
+    ];
+    if let Some(hint) = suggestion(command, error) {
+        lines.push(format!("  {} {}", paint(YELLOW, "hint:"), hint));
+    }
+    lines
+}
+
+fn suggestion(command: &[String], error: &io::Error) -> Option<String> {
+    if error.kind() != io::ErrorKind::NotFound {
+        return None;
+    }
+    let (program, args) = command.split_first()?;
+
+    // A mistyped path is the likeliest cause; then a program that doesn't exist
+    let missing = args.iter().find(|arg| !arg.starts_with('-') && !Path::new(arg).exists());
+    if let Some(path) = missing {
+        if let Some(similar) = similar_path(path) {
+            return Some(format!("did you mean '{}'?", similar));
+        }
+    }
+
+    if registry().get(program).is_some() || utils::find_in_path(program).is_some() {
+        return None;
+    }
+    match closest(program, registry().names().map(String::from)) {
+        Some(name) => Some(format!("'{}' is not a command; did you mean '{}'?", program, name)),
+        None => Some(format!("'{}' is not a built-in or a program on PATH", program)),
+    }
+}
+
+// `path` with each missing component replaced by a near miss that exists, so
+// `srcc/mian.rs` becomes `src/main.rs`. None if some component has no match.
+fn similar_path(path: &str) -> Option<String> {
+    let mut fixed = PathBuf::new();
+    for component in Path::new(path).components() {
+        let next = fixed.join(component);
+        if next.exists() {
+            fixed = next;
+            continue;
+        }
+
+        let name = component.as_os_str().to_str()?;
+        let dir = if fixed.as_os_str().is_empty() { Path::new(".") } else { fixed.as_path() };
+        let names = fs::read_dir(dir).ok()?.filter_map(|entry| entry.ok()?.file_name().into_string().ok());
+        fixed.push(closest(name, names)?);
+    }
+    Some(fixed.display().to_string())
+}
+
+// The candidate nearest to `word`, if any is close enough to be a typo
+fn closest(word: &str, candidates: impl Iterator<Item = String>) -> Option<String> {
+    let allowed = (word.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (utils::edit_distance(word, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_report_suggests_fixes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        let command = |line: String| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        let lines = format_error(&command(format!("show {}", dir.path().join("srcc/mian.rs").display())), &not_found, false);
+        assert_eq!(lines[2], format!("  hint: did you mean '{}'?", dir.path().join("src/main.rs").display()));
+
+        let typo = dir.path().join("notse.txt").display().to_string();
+        let lines = format_error(&command(format!("show {}", typo)), &not_found, false);
+        assert_eq!(lines[0], format!("error: show {}", typo));
+        assert_eq!(lines[1], format!("  cause: {}", not_found));
+        assert_eq!(lines[2], format!("  hint: did you mean '{}'?", dir.path().join("notes.txt").display()));
+
+        let lines = format_error(&command("shwo x".to_string()), &not_found, true);
+        assert_eq!(lines[0], "\x1b[1;31merror:\x1b[0m shwo x");
+        assert_eq!(lines[2], "  \x1b[33mhint:\x1b[0m 'shwo' is not a command; did you mean 'show'?");
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(format_error(&command(format!("show {}", typo)), &denied, false).len(), 2);
+    }
+}
@@ -0,0 +1,86 @@
+// Guards against command injection when arguments reach a shell.
+//
+// Threat model: arguments come from the user, from alias expansion, or from an
+// LLM translation that may be hallucinated or adversarial (e.g. a file name like
+// `x"; rm -rf ~; "`). Passed to `sh -c`, `cmd /C` or `powershell -Command` by
+// string interpolation, such input would run arbitrary commands.
+//
+// Mitigations:
+// - On Unix, external programs are spawned with explicit argument vectors and no
+//   shell, so metacharacters are passed through literally.
+// - PowerShell scripts embed values only as single-quoted literals (`ps_quote`).
+// - `cmd /C` is used only where unavoidable (cmd.exe built-ins such as `dir`), and
+//   arguments containing cmd metacharacters are rejected (`validate_cmd_args`).
+//
+// This does not make a deliberately typed destructive command safe; that is the
+// job of the confirmation and dangerous-pattern checks.
+
+use std::io;
+
+// Characters that cmd.exe interprets even inside arguments
+const CMD_METACHARACTERS: &[char] = &['&', '|', '<', '>', '^', '%', '!', '\n', '\r'];
+
+// Quote `value` as a PowerShell single-quoted string literal. Nothing inside
+// single quotes is expanded; embedded quotes are escaped by doubling them.
+pub fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Refuse arguments that cmd.exe would treat as operators or variable expansion
+pub fn validate_cmd_args(args: &[String]) -> io::Result<()> {
+    for arg in args {
+        if let Some(c) = arg.chars().find(|c| CMD_METACHARACTERS.contains(c)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("refusing to pass '{}' to cmd.exe: contains shell metacharacter {:?}", arg, c),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Join arguments into a cmd.exe command line, quoting those with spaces.
+// Arguments must have passed `validate_cmd_args` first.
+pub fn cmd_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(' ') || arg.contains('\t') {
+                format!("\"{}\"", arg.replace('"', "\"\""))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ps_quote_neutralizes_injection() {
+        assert_eq!(ps_quote("report.txt"), "'report.txt'");
+        assert_eq!(
+            ps_quote("x'; Remove-Item -Recurse C:\\ #"),
+            "'x''; Remove-Item -Recurse C:\\ #'"
+        );
+        assert_eq!(ps_quote("$(whoami)"), "'$(whoami)'");
+    }
+
+    #[test]
+    fn test_validate_cmd_args_rejects_operators() {
+        let safe = vec!["dir".to_string(), "C:\\My Files".to_string()];
+        assert!(validate_cmd_args(&safe).is_ok());
+
+        for evil in ["x & del /s /q C:\\", "a | format c:", "%USERPROFILE%", "a > b", "x\nshutdown /s"] {
+            assert!(validate_cmd_args(&[evil.to_string()]).is_err(), "accepted {:?}", evil);
+        }
+    }
+
+    #[test]
+    fn test_cmd_join_quotes_spaces() {
+        let args = vec!["dir".to_string(), "C:\\My Files".to_string()];
+        assert_eq!(cmd_join(&args), "dir \"C:\\My Files\"");
+    }
+}
@@ -0,0 +1,143 @@
+// Usage statistics behind the `stats` command: how often each command runs,
+// and LLM translations and tokens per day. They are only recorded with
+// `features.collect_stats` and stay in ~/.rustshell/stats.json; nothing is
+// ever sent anywhere.
+//
+// Sessions add their counts when they end. The file is re-read under a lock
+// first, so concurrent sessions don't overwrite each other's counts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageStats {
+    // Times each command was run, by name
+    #[serde(default)]
+    pub commands: BTreeMap<String, u64>,
+    // Activity per day, keyed by YYYY-MM-DD
+    #[serde(default)]
+    pub days: BTreeMap<String, DayUsage>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayUsage {
+    pub commands: u64,
+    pub translations: u64,
+    pub tokens: u64,
+}
+
+// Days of activity `stats` lists, most recent last
+const REPORT_DAYS: usize = 7;
+
+pub fn stats_file() -> PathBuf {
+    let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".rustshell").join("stats.json")
+}
+
+impl UsageStats {
+    pub fn load(file: &Path) -> io::Result<Self> {
+        match fs::read_to_string(file) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file.display(), e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(UsageStats::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Count a session's commands, translations and tokens towards `day`
+    pub fn add(&mut self, day: &str, command_counts: &HashMap<String, usize>, translations: usize, tokens: u64) {
+        let today = self.days.entry(day.to_string()).or_default();
+        for (name, count) in command_counts {
+            *self.commands.entry(name.clone()).or_default() += *count as u64;
+            today.commands += *count as u64;
+        }
+        today.translations += translations as u64;
+        today.tokens += tokens;
+    }
+
+    // The `top` most used commands, then the most recent days of activity
+    pub fn report(&self, top: usize) -> Vec<String> {
+        let mut most_used: Vec<(&String, &u64)> = self.commands.iter().collect();
+        // Ties go to the alphabetically first command so the report is stable
+        most_used.sort_by(|(a_name, a_count), (b_name, b_count)| b_count.cmp(a_count).then(a_name.cmp(b_name)));
+        let width = most_used.iter().take(top).map(|(name, _)| name.len()).max().unwrap_or(0);
+
+        let mut lines = vec!["Most used commands:".to_string()];
+        lines.extend(most_used.iter().take(top).map(|(name, count)| format!("  {:<width$}  {}", name, count)));
+
+        lines.push("Recent activity:".to_string());
+        lines.push(format!("  {:<10}  {:>8}  {:>12}  {:>8}", "day", "commands", "translations", "tokens"));
+        let skip = self.days.len().saturating_sub(REPORT_DAYS);
+        lines.extend(self.days.iter().skip(skip).map(|(day, usage)| {
+            format!("  {:<10}  {:>8}  {:>12}  {:>8}", day, usage.commands, usage.translations, usage.tokens)
+        }));
+        lines
+    }
+}
+
+// Apply `change` to the statistics in `file` while holding its lock. The
+// file itself is replaced on every save, so a separate `.lock` file is locked.
+pub fn update(file: &Path, change: impl FnOnce(&mut UsageStats)) -> io::Result<()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut lock_name = file.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(".lock");
+    let lock: File = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(file.with_file_name(lock_name))?;
+    lock.lock_exclusive()?;
+
+    let mut stats = UsageStats::load(file)?;
+    change(&mut stats);
+    let content = serde_json::to_string_pretty(&stats).map_err(io::Error::other)?;
+    utils::atomic_write(file, content.as_bytes())
+}
+
+// Add a finished session to the statistics. They are a convenience, so
+// failing to save them is reported but never stops the shell.
+pub fn record(command_counts: &HashMap<String, usize>, translations: usize, tokens: u64) {
+    let day = chrono::Local::now().format("%Y-%m-%d").to_string();
+    if let Err(e) = update(&stats_file(), |stats| stats.add(&day, command_counts, translations, tokens)) {
+        eprintln!("Warning: could not save usage statistics: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_accumulate() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("stats.json");
+        assert_eq!(UsageStats::load(&file).unwrap(), UsageStats::default());
+
+        let session: HashMap<String, usize> = [("list", 3), ("cd", 1)].iter().map(|(k, v)| (k.to_string(), *v)).collect();
+        update(&file, |stats| stats.add("2026-10-13", &session, 2, 300)).unwrap();
+        update(&file, |stats| stats.add("2026-10-14", &session, 1, 50)).unwrap();
+        update(&file, |stats| stats.add("2026-10-14", &HashMap::from([("show".to_string(), 3)]), 0, 0)).unwrap();
+
+        let stats = UsageStats::load(&file).unwrap();
+        assert_eq!(stats.commands["list"], 6);
+        assert_eq!(stats.days["2026-10-14"], DayUsage { commands: 7, translations: 1, tokens: 50 });
+
+        let report = stats.report(2);
+        assert_eq!(report[1..3], ["  list  6", "  show  3"]);
+        assert_eq!(report[3], "Recent activity:");
+        assert_eq!(report[5], "  2026-10-13         4             2       300");
+        assert_eq!(report.len(), 7);
+
+        fs::write(&file, "not json").unwrap();
+        assert_eq!(UsageStats::load(&file).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
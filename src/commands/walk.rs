@@ -0,0 +1,393 @@
+// Which entries the directory-walking commands (find, du, compress) leave
+// out: paths matching their --exclude patterns and, unless --no-ignore is
+// given, paths a .gitignore file ignores; with --one-file-system, also
+// directories on another file system than the root. Skipped directories are
+// never descended into, as with ripgrep and fd.
+//
+// Also the multi-threaded walk behind --parallel / --jobs N, whose results
+// come back in the same order as a sorted single-threaded walk.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+
+use crate::utils;
+
+pub struct WalkFilter<'a> {
+    // The walk's root as given, which every entry path starts with
+    root: PathBuf,
+    // The same directory as an absolute path, for matching .gitignore rules
+    absolute_root: PathBuf,
+    excludes: &'a [String],
+    gitignore: Option<Gitignores>,
+    // The root's file system, if the walk is to stay on it
+    file_system: Option<FileSystem>,
+}
+
+// The .gitignore files that apply inside a repository, loaded as the walk
+// reaches each directory
+struct Gitignores {
+    // The repository's top-level directory, or the walk root outside one
+    top: PathBuf,
+    by_dir: Mutex<HashMap<PathBuf, Option<Gitignore>>>,
+}
+
+impl<'a> WalkFilter<'a> {
+    pub fn new(root: &Path, excludes: &'a [String], respect_gitignore: bool) -> Self {
+        let absolute_root: PathBuf = env::current_dir()
+            .map(|cwd| cwd.join(root))
+            .unwrap_or_else(|_| root.to_path_buf())
+            .components()
+            .collect();
+        let gitignore = respect_gitignore.then(|| Gitignores {
+            top: absolute_root
+                .ancestors()
+                .find(|dir| dir.join(".git").exists())
+                .unwrap_or(&absolute_root)
+                .to_path_buf(),
+            by_dir: Mutex::new(HashMap::new()),
+        });
+        WalkFilter { root: root.to_path_buf(), absolute_root, excludes, gitignore, file_system: None }
+    }
+
+    // --one-file-system / -x: also skip directories on another file system
+    // than the root, such as mounted network drives or /proc, as `du -x` does
+    pub fn one_file_system(mut self, enabled: bool) -> Self {
+        self.file_system = if enabled { FileSystem::of(&self.root) } else { None };
+        self
+    }
+
+    // Whether the walk should skip `entry` (and, for a directory, its contents)
+    pub fn skips(&self, entry: &walkdir::DirEntry) -> bool {
+        self.skips_path(entry.path(), entry.file_type().is_dir())
+    }
+
+    fn skips_path(&self, path: &Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        if utils::is_excluded(relative, is_dir, self.excludes) {
+            return true;
+        }
+
+        // The root itself is always walked, even if a parent ignores it
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+        if is_dir && self.file_system.as_ref().is_some_and(|file_system| !file_system.contains(path)) {
+            return true;
+        }
+        let Some(gitignore) = &self.gitignore else {
+            return false;
+        };
+        // git's own data is never part of the project
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        gitignore.ignores(&self.absolute_root.join(relative), is_dir)
+    }
+}
+
+impl Gitignores {
+    // The .gitignore nearest to `path` that has a rule for it decides, so a
+    // `!keep.log` in a subdirectory overrides `*.log` further up, as in git
+    fn ignores(&self, path: &Path, is_dir: bool) -> bool {
+        let mut by_dir = self.by_dir.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for dir in path.ancestors().skip(1) {
+            let matcher = by_dir.entry(dir.to_path_buf()).or_insert_with(|| load(dir));
+            match matcher.as_ref().map(|matcher| matcher.matched(path, is_dir)) {
+                Some(Match::Ignore(_)) => return true,
+                Some(Match::Whitelist(_)) => return false,
+                _ => {}
+            }
+            if dir == self.top {
+                break;
+            }
+        }
+        false
+    }
+}
+
+// The file system a walk started on, identified by its device number
+#[cfg(unix)]
+struct FileSystem(u64);
+
+#[cfg(unix)]
+impl FileSystem {
+    fn of(root: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(root).ok().map(|metadata| FileSystem(metadata.dev()))
+    }
+
+    // A directory whose metadata can't be read is left for the walk to report
+    fn contains(&self, dir: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(dir).map_or(true, |metadata| metadata.dev() == self.0)
+    }
+}
+
+// Windows offers no device numbers here, so the boundaries are approximated
+// by reparse points, which volumes mounted in a folder and junctions are
+#[cfg(windows)]
+struct FileSystem;
+
+#[cfg(windows)]
+impl FileSystem {
+    fn of(_root: &Path) -> Option<Self> {
+        Some(FileSystem)
+    }
+
+    fn contains(&self, dir: &Path) -> bool {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+        fs::symlink_metadata(dir).map_or(true, |metadata| metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT == 0)
+    }
+}
+
+fn load(dir: &Path) -> Option<Gitignore> {
+    let file = dir.join(".gitignore");
+    if !file.is_file() {
+        return None;
+    }
+    let (matcher, error) = Gitignore::new(&file);
+    if let Some(e) = error {
+        eprintln!("warning: {}: {}", file.display(), e);
+    }
+    Some(matcher)
+}
+
+// Threads used by --parallel: one per CPU
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1)
+}
+
+// How a parallel walk descends, mirroring the walkdir options the commands use
+pub struct ParallelWalk<'a> {
+    pub jobs: usize,
+    pub max_depth: Option<usize>,
+    pub follow_links: bool,
+    pub filter: &'a WalkFilter<'a>,
+}
+
+// Directories waiting to be read, and how many threads are reading one. The
+// walk is over once nothing is pending and no thread can add more.
+struct Pending {
+    dirs: Vec<(PathBuf, usize)>,
+    busy: usize,
+}
+
+impl ParallelWalk<'_> {
+    // The files under `root`, sorted by path, which is the order a walk that
+    // visits entries sorted by name finds them in. Unreadable directories
+    // are reported with `command` as the prefix and skipped.
+    pub fn files(&self, root: &Path, command: &str) -> Vec<PathBuf> {
+        // Like walkdir, a symlinked root is always followed
+        if !root.is_dir() {
+            return if root.is_file() { vec![root.to_path_buf()] } else { Vec::new() };
+        }
+        if self.max_depth == Some(0) {
+            return Vec::new();
+        }
+
+        let pending = Mutex::new(Pending { dirs: vec![(root.to_path_buf(), 0)], busy: 0 });
+        let changed = Condvar::new();
+        // With links followed, a directory reached twice (e.g. through a loop) is read once
+        let visited = Mutex::new(HashSet::new());
+
+        let mut files: Vec<PathBuf> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.jobs.max(1))
+                .map(|_| scope.spawn(|| self.worker(&pending, &changed, &visited, command)))
+                .collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+        });
+        files.sort();
+        files
+    }
+
+    fn worker(&self, pending: &Mutex<Pending>, changed: &Condvar, visited: &Mutex<HashSet<PathBuf>>, command: &str) -> Vec<PathBuf> {
+        let lock = || pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut files = Vec::new();
+        loop {
+            let (dir, depth) = {
+                let mut state = lock();
+                loop {
+                    // Ctrl-C abandons the directories still to be read
+                    if super::context::is_interrupted() {
+                        state.dirs.clear();
+                    }
+                    if let Some(next) = state.dirs.pop() {
+                        state.busy += 1;
+                        break next;
+                    }
+                    if state.busy == 0 {
+                        changed.notify_all();
+                        return files;
+                    }
+                    state = changed.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+                }
+            };
+
+            let subdirs = self.read_dir(&dir, depth, visited, command, &mut files);
+            let mut state = lock();
+            state.dirs.extend(subdirs);
+            state.busy -= 1;
+            changed.notify_all();
+        }
+    }
+
+    // Collect the files directly inside `dir` and return the subdirectories to read next
+    fn read_dir(&self, dir: &Path, depth: usize, visited: &Mutex<HashSet<PathBuf>>, command: &str, files: &mut Vec<PathBuf>) -> Vec<(PathBuf, usize)> {
+        if self.follow_links {
+            let first_visit = fs::canonicalize(dir)
+                .map(|real| visited.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(real))
+                .unwrap_or(true);
+            if !first_visit {
+                return Vec::new();
+            }
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("{}: {}: {}", command, dir.display(), e);
+                return Vec::new();
+            }
+        };
+
+        let mut subdirs = Vec::new();
+        for entry in entries.filter_map(|entry| entry.map_err(|e| eprintln!("{}: {}", command, e)).ok()) {
+            let path = entry.path();
+            let file_type = if self.follow_links { fs::metadata(&path).map(|m| m.file_type()) } else { entry.file_type() };
+            let Ok(file_type) = file_type else {
+                continue;
+            };
+            if self.filter.skips_path(&path, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_file() {
+                files.push(path);
+            } else if file_type.is_dir() && depth + 1 < self.max_depth.unwrap_or(usize::MAX) {
+                subdirs.push((path, depth + 1));
+            }
+        }
+        subdirs
+    }
+}
+
+// `work` applied to every item on `jobs` threads, with the results in the
+// items' order so output matches a single-threaded run
+pub fn parallel_map<T: Sync, R: Send>(items: &[T], jobs: usize, work: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1).min(items.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            return done;
+                        };
+                        done.push((index, work(item)));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_default()).collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_gitignore_rules_are_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("repo");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("logs")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("logs/.gitignore"), "!keep.log\n").unwrap();
+        for file in ["target/debug/app", "logs/keep.log", "logs/debug.log", "main.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let walk = |respect_gitignore: bool| {
+            let filter = WalkFilter::new(&root, &[], respect_gitignore);
+            let mut files: Vec<String> = walkdir::WalkDir::new(&root)
+                .into_iter()
+                .filter_entry(|entry| !filter.skips(entry))
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().strip_prefix(&root).unwrap().display().to_string())
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(walk(true), vec![".gitignore", "logs/.gitignore", "logs/keep.log", "main.rs"]);
+        assert_eq!(walk(false).len(), 6);
+    }
+
+    #[test]
+    fn test_parallel_walk_matches_sorted_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["a/b/c.txt", "a/d.txt", "a-e/f.txt", "g.txt", "skip/h.txt"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let excludes = vec!["skip".to_string()];
+        let filter = WalkFilter::new(dir.path(), &excludes, false);
+        let sequential: Vec<PathBuf> = walkdir::WalkDir::new(dir.path())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !filter.skips(entry))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+
+        let walk = ParallelWalk { jobs: 4, max_depth: None, follow_links: false, filter: &filter };
+        assert_eq!(walk.files(dir.path(), "test"), sequential);
+        assert_eq!(sequential.len(), 4);
+
+        let shallow = ParallelWalk { max_depth: Some(1), ..walk };
+        assert_eq!(shallow.files(dir.path(), "test"), vec![dir.path().join("g.txt")]);
+
+        assert_eq!(parallel_map(&[3, 1, 2], 2, |n| n * 10), vec![30, 10, 20]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_one_file_system() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/a.txt"), "").unwrap();
+        fs::write(dir.path().join("b.txt"), "").unwrap();
+
+        let same = WalkFilter::new(dir.path(), &[], false).one_file_system(true);
+        let walk = ParallelWalk { jobs: 2, max_depth: None, follow_links: false, filter: &same };
+        assert_eq!(walk.files(dir.path(), "test").len(), 2);
+
+        // As if the root were on another device: the root is still walked,
+        // but no directory below it
+        let mut other = WalkFilter::new(dir.path(), &[], false);
+        other.file_system = Some(FileSystem(u64::MAX));
+        assert!(other.skips_path(&dir.path().join("sub"), true));
+        assert!(!other.skips_path(dir.path(), true));
+        let walk = ParallelWalk { filter: &other, ..walk };
+        assert_eq!(walk.files(dir.path(), "test"), vec![dir.path().join("b.txt")]);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::llm::{LLMConfig, LLMProvider};
+use crate::llm::{default_model_for, FallbackProvider, LLMConfig, LLMProvider};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -15,13 +15,44 @@ pub struct RustShellConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMSettings {
     pub provider: String,
+    // Empty picks the provider's default model
+    #[serde(default)]
     pub model: String,
     pub api_key_env: Option<String>,
+    // File holding the API key, e.g. ~/.rustshell/openai.key; takes precedence
+    // over `api_key_env`
+    #[serde(default)]
+    pub api_key_file: Option<PathBuf>,
     pub endpoint: Option<String>,
     pub timeout_seconds: u64,
     pub max_tokens: u32,
     pub temperature: f32,
     pub enable_cache: bool,
+    // Upper bound on LLM requests per minute; 0 disables the limit
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+    // Number of LLM responses kept in the cache
+    #[serde(default = "default_cache_size")]
+    pub cache_size: usize,
+    // Providers to try, in order, when `provider` fails, e.g. "anthropic:claude-3-haiku-20240307"
+    #[serde(default)]
+    pub fallback_providers: Vec<String>,
+    // Sent as the OpenAI-Organization / OpenAI-Project headers for org-scoped keys
+    #[serde(default)]
+    pub openai_org: Option<String>,
+    #[serde(default)]
+    pub openai_project: Option<String>,
+    // More capable model tried once when a translation comes back unusable
+    #[serde(default)]
+    pub escalation_model: Option<String>,
+}
+
+fn default_requests_per_minute() -> u32 {
+    30
+}
+
+fn default_cache_size() -> usize {
+    100
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +61,16 @@ pub struct SafetySettings {
     pub dangerous_patterns: Vec<String>,
     pub enable_dry_run: bool,
     pub block_destructive: bool,
+    // Answer assumed when a confirmation prompt gets an empty reply: "yes" or "no"
+    #[serde(default = "default_confirm_default")]
+    pub confirm_default: String,
+    // Give up waiting for an answer after this long and take `confirm_default`
+    #[serde(default)]
+    pub confirm_timeout_seconds: Option<u64>,
+}
+
+fn default_confirm_default() -> String {
+    "no".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +79,9 @@ pub struct FeatureSettings {
     pub fallback_to_traditional: bool,
     pub offline_mode: bool,
     pub enable_history: bool,
+    // Keep command and LLM usage counts in ~/.rustshell/stats.json for `stats`
+    #[serde(default)]
+    pub collect_stats: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +90,15 @@ pub struct UISettings {
     pub colored_output: bool,
     pub verbose_mode: bool,
     pub confirm_destructive: bool,
+    // Suppress informational messages such as "Creating file: x"
+    #[serde(default)]
+    pub quiet: bool,
+    // Print command, translation and token counts when leaving interactive mode
+    #[serde(default)]
+    pub session_summary: bool,
+    // Show the directory on its own line and take input after `❯ ` below it
+    #[serde(default)]
+    pub multiline_prompt: bool,
 }
 
 impl Default for RustShellConfig {
@@ -53,13 +106,21 @@ impl Default for RustShellConfig {
         Self {
             llm: LLMSettings {
                 provider: "openai".to_string(),
-                model: "gpt-3.5-turbo".to_string(),
+                // Empty follows the provider, so switching it needs no model change
+                model: String::new(),
                 api_key_env: Some("OPENAI_API_KEY".to_string()),
+                api_key_file: None,
                 endpoint: None,
                 timeout_seconds: 30,
                 max_tokens: 150,
                 temperature: 0.1,
                 enable_cache: true,
+                requests_per_minute: default_requests_per_minute(),
+                cache_size: default_cache_size(),
+                fallback_providers: Vec::new(),
+                openai_org: None,
+                openai_project: None,
+                escalation_model: None,
             },
             safety: SafetySettings {
                 require_confirmation: vec![
@@ -77,18 +138,24 @@ impl Default for RustShellConfig {
                 ],
                 enable_dry_run: true,
                 block_destructive: false,
+                confirm_default: default_confirm_default(),
+                confirm_timeout_seconds: None,
             },
             features: FeatureSettings {
                 enable_llm: true,
                 fallback_to_traditional: true,
                 offline_mode: false,
                 enable_history: true,
+                collect_stats: false,
             },
             ui: UISettings {
                 show_hints: true,
                 colored_output: true,
                 verbose_mode: false,
                 confirm_destructive: true,
+                quiet: false,
+                session_summary: false,
+                multiline_prompt: false,
             },
         }
     }
@@ -101,6 +168,9 @@ impl RustShellConfig {
         if config_path.exists() {
             let content = std::fs::read_to_string(&config_path)?;
             let config: RustShellConfig = toml::from_str(&content)?;
+            config
+                .validate()
+                .map_err(|e| anyhow::anyhow!("{} has invalid settings:\n{}", config_path.display(), e))?;
             Ok(config)
         } else {
             // Create default config file
@@ -119,13 +189,13 @@ impl RustShellConfig {
         }
         
         let content = toml::to_string_pretty(self)?;
-        std::fs::write(&config_path, content)?;
+        crate::utils::atomic_write(&config_path, content.as_bytes())?;
         
         Ok(())
     }
 
-    pub fn to_llm_config(&self) -> Result<LLMConfig> {
-        let provider = match self.llm.provider.as_str() {
+    fn parse_provider(provider: &str) -> Result<LLMProvider> {
+        Ok(match provider {
             "openai" => LLMProvider::OpenAI,
             "anthropic" => LLMProvider::Anthropic,
             provider_str if provider_str.starts_with("local:") => {
@@ -136,14 +206,80 @@ impl RustShellConfig {
                 let endpoint = provider_str.strip_prefix("custom:").unwrap().to_string();
                 LLMProvider::Custom(endpoint)
             }
-            _ => return Err(anyhow::anyhow!("Unknown LLM provider: {}", self.llm.provider)),
+            "mock" => LLMProvider::Mock(None),
+            provider_str if provider_str.starts_with("mock:") => {
+                let response = provider_str.strip_prefix("mock:").unwrap().to_string();
+                LLMProvider::Mock(Some(response))
+            }
+            _ => return Err(anyhow::anyhow!("Unknown LLM provider: {}", provider)),
+        })
+    }
+
+    // A fallback entry uses the `provider` syntax; "openai" and "anthropic" may
+    // name a model after a colon, since the primary's model rarely fits them
+    fn parse_fallback(entry: &str) -> Result<FallbackProvider> {
+        let (provider, model) = match entry.split_once(':') {
+            Some((name @ ("openai" | "anthropic"), model)) => (Self::parse_provider(name)?, Some(model.to_string())),
+            _ => (Self::parse_provider(entry)?, None),
         };
+        let model = model.unwrap_or_else(|| default_model_for(&provider).to_string());
+        Ok(FallbackProvider { provider, model })
+    }
+
+    fn cache_capacity(&self) -> std::num::NonZeroUsize {
+        std::num::NonZeroUsize::new(self.llm.cache_size).unwrap_or_else(|| {
+            eprintln!("Warning: llm.cache_size must be at least 1; using 1");
+            std::num::NonZeroUsize::MIN
+        })
+    }
+
+    // Read an API key file, warning when other users can read it
+    fn read_api_key_file(path: &std::path::Path) -> Result<String> {
+        use anyhow::Context as _;
+
+        let path = PathBuf::from(crate::utils::expand_tilde(&path.to_string_lossy()));
+        let key = std::fs::read_to_string(&path)
+            .with_context(|| format!("Could not read llm.api_key_file {}", path.display()))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                if metadata.permissions().mode() & 0o004 != 0 {
+                    eprintln!(
+                        "Warning: {} is readable by every user; restrict it with `chmod 600 {}`",
+                        path.display(),
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow::anyhow!("llm.api_key_file {} is empty", path.display()));
+        }
+        Ok(key.to_string())
+    }
+
+    pub fn to_llm_config(&self) -> Result<LLMConfig> {
+        let provider = Self::parse_provider(&self.llm.provider)?;
+        let fallback_providers = self
+            .llm
+            .fallback_providers
+            .iter()
+            .map(|entry| Self::parse_fallback(entry))
+            .collect::<Result<Vec<_>>>()?;
 
         // Try multiple sources for API key:
-        // 1. Environment variable (if api_key_env is set)
-        // 2. Direct api_key field in config
-        // 3. Default environment variables based on provider
-        let api_key = if let Some(env_var) = &self.llm.api_key_env {
+        // 1. Key file (if api_key_file is set)
+        // 2. Environment variable (if api_key_env is set)
+        // 3. Direct api_key field in config
+        // 4. Default environment variables based on provider
+        let key_file = self.llm.api_key_file.as_ref().filter(|path| !path.as_os_str().is_empty());
+        let api_key = if let Some(path) = key_file {
+            Some(Self::read_api_key_file(path)?)
+        } else if let Some(env_var) = &self.llm.api_key_env {
             // If it looks like an actual key (starts with sk-), use it directly
             if env_var.starts_with("sk-") || env_var.starts_with("anthropic-") {
                 Some(env_var.clone())
@@ -160,18 +296,109 @@ impl RustShellConfig {
             }
         };
 
+        let model = match self.llm.model.trim() {
+            "" => default_model_for(&provider).to_string(),
+            model => model.to_string(),
+        };
+
         Ok(LLMConfig {
             provider,
-            model: self.llm.model.clone(),
+            model,
             api_key,
             endpoint: self.llm.endpoint.clone(),
             timeout: Duration::from_secs(self.llm.timeout_seconds),
-            max_tokens: self.llm.max_tokens,
-            temperature: self.llm.temperature,
+            requests_per_minute: self.llm.requests_per_minute,
+            verbose: self.ui.verbose_mode,
+            use_cache: self.llm.enable_cache,
+            cache_size: self.cache_capacity(),
+            fallback_providers,
+            // Empty values, as in the sample config, mean "not set"
+            openai_org: self.llm.openai_org.clone().filter(|org| !org.is_empty()),
+            openai_project: self.llm.openai_project.clone().filter(|project| !project.is_empty()),
         })
     }
 
-    fn config_file_path() -> Result<PathBuf> {
+    // Check values that parse but make no sense, naming each bad field and how
+    // to fix it, so mistakes surface at load rather than on the first request
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let llm = &self.llm;
+        if let Err(e) = Self::parse_provider(&llm.provider) {
+            problems.push(format!(
+                "llm.provider: {}; use \"openai\", \"anthropic\", \"mock\", \"local:<url>\" or \"custom:<url>\"",
+                e
+            ));
+        }
+        for entry in &llm.fallback_providers {
+            if let Err(e) = Self::parse_fallback(entry) {
+                problems.push(format!("llm.fallback_providers: {}; entries use the same syntax as llm.provider", e));
+            }
+        }
+        if !(0.0..=2.0).contains(&llm.temperature) {
+            problems.push(format!("llm.temperature is {}; use a value from 0.0 to 2.0", llm.temperature));
+        }
+        if llm.max_tokens == 0 {
+            problems.push("llm.max_tokens is 0; use a positive limit such as 150".to_string());
+        }
+        if llm.timeout_seconds == 0 {
+            problems.push("llm.timeout_seconds is 0; use a positive number of seconds such as 30".to_string());
+        }
+
+        let answer = &self.safety.confirm_default;
+        if !answer.eq_ignore_ascii_case("yes") && !answer.eq_ignore_ascii_case("no") {
+            problems.push(format!("safety.confirm_default is \"{}\"; use \"yes\" or \"no\"", answer));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("  {}", problems.join("\n  ")))
+        }
+    }
+
+    // The configuration for retrying a translation with `llm.escalation_model`,
+    // if one is set and differs from the usual model
+    pub fn escalated(&self) -> Option<RustShellConfig> {
+        let model = self.llm.escalation_model.as_deref().filter(|m| !m.is_empty() && *m != self.llm.model)?;
+        let mut escalated = self.clone();
+        escalated.llm.model = model.to_string();
+        Some(escalated)
+    }
+
+    // How long a confirmation prompt waits for an answer; 0 means forever
+    pub fn confirm_timeout(&self) -> Option<Duration> {
+        self.safety.confirm_timeout_seconds.filter(|&secs| secs > 0).map(Duration::from_secs)
+    }
+
+    // Whether an empty reply to a confirmation prompt means yes
+    pub fn confirm_default_yes(&self) -> bool {
+        self.safety.confirm_default.eq_ignore_ascii_case("yes")
+    }
+
+    // Names of the top-level sections whose values differ from `other`
+    pub fn changed_sections(&self, other: &RustShellConfig) -> Vec<&'static str> {
+        fn differs<T: Serialize>(a: &T, b: &T) -> bool {
+            toml::to_string(a).ok() != toml::to_string(b).ok()
+        }
+
+        let mut changed = Vec::new();
+        if differs(&self.llm, &other.llm) {
+            changed.push("llm");
+        }
+        if differs(&self.safety, &other.safety) {
+            changed.push("safety");
+        }
+        if differs(&self.features, &other.features) {
+            changed.push("features");
+        }
+        if differs(&self.ui, &other.ui) {
+            changed.push("ui");
+        }
+        changed
+    }
+
+    pub fn config_file_path() -> Result<PathBuf> {
         let home_dir = dirs_next::home_dir()
             .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))?;
         
@@ -222,7 +449,13 @@ mod tests {
         let config = RustShellConfig::default();
         assert!(config.features.enable_llm);
         assert_eq!(config.llm.provider, "openai");
-        assert_eq!(config.llm.model, "gpt-3.5-turbo");
+        assert_eq!(config.llm.model, "");
+        assert_eq!(config.to_llm_config().unwrap().model, "gpt-3.5-turbo");
+
+        // Switching the provider alone switches the model too
+        let mut anthropic = RustShellConfig::default();
+        anthropic.llm.provider = "anthropic".to_string();
+        assert_eq!(anthropic.to_llm_config().unwrap().model, "claude-3-haiku-20240307");
     }
 
     #[test]
@@ -235,6 +468,149 @@ mod tests {
         assert!(!config.is_dangerous_command("mkdir test"));
     }
 
+    #[test]
+    fn test_changed_sections() {
+        let config = RustShellConfig::default();
+        let mut edited = config.clone();
+        assert!(config.changed_sections(&edited).is_empty());
+
+        edited.llm.model = "gpt-4".to_string();
+        edited.ui.verbose_mode = true;
+        assert_eq!(config.changed_sections(&edited), vec!["llm", "ui"]);
+    }
+
+    #[test]
+    fn test_mock_provider_config() {
+        let mut config = RustShellConfig::default();
+        config.llm.provider = "mock:pwd".to_string();
+
+        let llm_config = config.to_llm_config().unwrap();
+        assert!(matches!(llm_config.provider, LLMProvider::Mock(Some(ref r)) if r == "pwd"));
+    }
+
+    #[test]
+    fn test_cache_size_config() {
+        let mut config = RustShellConfig::default();
+        assert_eq!(config.to_llm_config().unwrap().cache_size.get(), 100);
+
+        config.llm.cache_size = 0;
+        assert_eq!(config.to_llm_config().unwrap().cache_size.get(), 1);
+
+        // Config files written before the field existed still load
+        let mut table = toml::Value::try_from(RustShellConfig::default()).unwrap();
+        table["llm"].as_table_mut().unwrap().remove("cache_size");
+        let old: RustShellConfig = toml::from_str(&toml::to_string(&table).unwrap()).unwrap();
+        assert_eq!(old.llm.cache_size, 100);
+    }
+
+    #[test]
+    fn test_fallback_providers_config() {
+        let mut config = RustShellConfig::default();
+        config.llm.fallback_providers = vec![
+            "anthropic:claude-3-5-haiku-latest".to_string(),
+            "anthropic".to_string(),
+            "mock:ls".to_string(),
+        ];
+
+        let fallbacks = config.to_llm_config().unwrap().fallback_providers;
+        assert!(matches!(fallbacks[0].provider, LLMProvider::Anthropic));
+        assert_eq!(fallbacks[0].model, "claude-3-5-haiku-latest");
+        assert_eq!(fallbacks[1].model, "claude-3-haiku-20240307");
+        assert!(matches!(fallbacks[2].provider, LLMProvider::Mock(Some(ref r)) if r == "ls"));
+
+        config.llm.fallback_providers = vec!["gemini".to_string()];
+        assert!(config.to_llm_config().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RustShellConfig::default().validate().is_ok());
+
+        let mut config = RustShellConfig::default();
+        config.llm.provider = String::new();
+        config.llm.temperature = 3.5;
+        config.llm.max_tokens = 0;
+        config.llm.timeout_seconds = 0;
+        config.safety.confirm_default = "maybe".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        let problems: Vec<&str> = message.lines().collect();
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("  llm.provider: Unknown LLM provider"));
+        assert_eq!(problems[1], "  llm.temperature is 3.5; use a value from 0.0 to 2.0");
+        assert!(problems[2].starts_with("  llm.max_tokens is 0"));
+        assert!(problems[3].starts_with("  llm.timeout_seconds is 0"));
+        assert!(problems[4].starts_with("  safety.confirm_default"));
+
+        let mut config = RustShellConfig::default();
+        config.llm.temperature = 2.0;
+        config.llm.fallback_providers = vec!["gemini".to_string()];
+        assert!(config.validate().unwrap_err().to_string().starts_with("  llm.fallback_providers"));
+    }
+
+    #[test]
+    fn test_empty_model_uses_provider_default() {
+        let mut config = RustShellConfig::default();
+        config.llm.provider = "anthropic".to_string();
+        config.llm.model = String::new();
+        assert_eq!(config.to_llm_config().unwrap().model, "claude-3-haiku-20240307");
+
+        config.llm.provider = "openai".to_string();
+        assert_eq!(config.to_llm_config().unwrap().model, "gpt-3.5-turbo");
+
+        config.llm.model = "gpt-4o".to_string();
+        assert_eq!(config.to_llm_config().unwrap().model, "gpt-4o");
+    }
+
+    #[test]
+    fn test_api_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("openai.key");
+        std::fs::write(&key_file, "  sk-from-file\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&key_file, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let mut config = RustShellConfig::default();
+        config.llm.api_key_file = Some(key_file.clone());
+        assert_eq!(config.to_llm_config().unwrap().api_key.as_deref(), Some("sk-from-file"));
+
+        std::fs::write(&key_file, "\n").unwrap();
+        assert!(config.to_llm_config().is_err());
+        config.llm.api_key_file = Some(dir.path().join("missing.key"));
+        assert!(config.to_llm_config().is_err());
+    }
+
+    #[test]
+    fn test_escalation_model() {
+        let mut config = RustShellConfig::default();
+        assert!(config.escalated().is_none());
+        config.llm.escalation_model = Some(String::new());
+        assert!(config.escalated().is_none());
+        config.llm.escalation_model = Some(config.llm.model.clone());
+        assert!(config.escalated().is_none());
+
+        config.llm.escalation_model = Some("gpt-4o".to_string());
+        let escalated = config.escalated().unwrap();
+        assert_eq!(escalated.llm.model, "gpt-4o");
+        assert_eq!(escalated.llm.provider, config.llm.provider);
+    }
+
+    #[test]
+    fn test_confirm_timeout() {
+        let mut config = RustShellConfig::default();
+        assert_eq!(config.confirm_timeout(), None);
+        config.safety.confirm_timeout_seconds = Some(0);
+        assert_eq!(config.confirm_timeout(), None);
+
+        let config: RustShellConfig = toml::from_str(
+            &toml::to_string(&config).unwrap().replace("confirm_timeout_seconds = 0", "confirm_timeout_seconds = 30"),
+        )
+        .unwrap();
+        assert_eq!(config.confirm_timeout(), Some(Duration::from_secs(30)));
+    }
+
     #[test]
     fn test_confirmation_required() {
         let config = RustShellConfig::default();
@@ -1,17 +1,18 @@
-use super::{LLMConfig, LLMProvider, LLMProviderTrait, LLMRequest, LLMResponse};
-use crate::llm::providers::{anthropic::AnthropicProvider, openai::OpenAIProvider};
+use super::{rate_limit, FallbackProvider, LLMConfig, LLMProvider, LLMProviderTrait, LLMRequest, LLMResponse};
+use crate::llm::providers::{anthropic::AnthropicProvider, local, mock::MockProvider, openai::OpenAIProvider};
 use anyhow::{anyhow, Result};
 use lru::LruCache;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
-use std::num::NonZeroUsize;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 use tokio::sync::Mutex;
 
 #[derive(Debug)]
 pub enum LLMProviderEnum {
     OpenAI(OpenAIProvider),
     Anthropic(AnthropicProvider),
+    Mock(MockProvider),
 }
 
 impl LLMProviderEnum {
@@ -19,6 +20,7 @@ impl LLMProviderEnum {
         match self {
             LLMProviderEnum::OpenAI(provider) => provider.generate(request).await,
             LLMProviderEnum::Anthropic(provider) => provider.generate(request).await,
+            LLMProviderEnum::Mock(provider) => provider.generate(request).await,
         }
     }
 
@@ -26,6 +28,7 @@ impl LLMProviderEnum {
         match self {
             LLMProviderEnum::OpenAI(provider) => provider.name(),
             LLMProviderEnum::Anthropic(provider) => provider.name(),
+            LLMProviderEnum::Mock(provider) => provider.name(),
         }
     }
 
@@ -33,36 +36,87 @@ impl LLMProviderEnum {
         match self {
             LLMProviderEnum::OpenAI(provider) => provider.is_available(),
             LLMProviderEnum::Anthropic(provider) => provider.is_available(),
+            LLMProviderEnum::Mock(provider) => provider.is_available(),
+        }
+    }
+
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        match self {
+            LLMProviderEnum::OpenAI(provider) => provider.list_models().await,
+            LLMProviderEnum::Anthropic(provider) => provider.list_models().await,
+            LLMProviderEnum::Mock(provider) => provider.list_models().await,
         }
     }
 }
 
 pub struct LLMClient {
     provider: LLMProviderEnum,
-    cache: Arc<Mutex<LruCache<u64, LLMResponse>>>,
     config: LLMConfig,
 }
 
+// Responses are cached for the whole process, like the rate limit, because
+// every translation builds its own client
+fn shared_cache() -> &'static Mutex<LruCache<u64, LLMResponse>> {
+    static CACHE: OnceLock<Mutex<LruCache<u64, LLMResponse>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(LruCache::new(LLMConfig::default().cache_size)))
+}
+
+// Tokens reported by providers over the whole process; cached replies cost nothing
+static TOKENS_USED: AtomicU64 = AtomicU64::new(0);
+
 impl LLMClient {
+    pub fn tokens_used() -> u64 {
+        TOKENS_USED.load(Ordering::Relaxed)
+    }
+
     pub async fn new(config: LLMConfig) -> Result<Self> {
-        let provider = match &config.provider {
+        // An empty `llm.endpoint` means the provider's default
+        let endpoint = config.endpoint.clone().filter(|e| !e.is_empty());
+        let provider = Self::build_provider(&config.provider, &config.model, config.api_key.clone(), endpoint, &config)?;
+
+        // Follow `llm.cache_size`, which may have changed since the last client
+        // (e.g. after `reload`); shrinking drops the least recently used entries
+        {
+            let mut cache = shared_cache().lock().await;
+            if cache.cap() != config.cache_size {
+                cache.resize(config.cache_size);
+            }
+        }
+
+        Ok(Self {
+            provider,
+            config,
+        })
+    }
+
+    fn build_provider(
+        provider: &LLMProvider,
+        model: &str,
+        api_key: Option<String>,
+        endpoint: Option<String>,
+        config: &LLMConfig,
+    ) -> Result<LLMProviderEnum> {
+        Ok(match provider {
             LLMProvider::OpenAI => {
-                let api_key = config
-                    .api_key
-                    .clone()
+                let api_key = api_key
                     .or_else(|| std::env::var("OPENAI_API_KEY").ok())
                     .ok_or_else(|| anyhow!("OpenAI API key not found"))?;
                 
-                LLMProviderEnum::OpenAI(OpenAIProvider::new(api_key, config.model.clone())?)
+                let provider = match endpoint {
+                    Some(endpoint) => OpenAIProvider::with_endpoint(api_key, model.to_string(), endpoint, config.timeout)?,
+                    None => OpenAIProvider::new(api_key, model.to_string(), config.timeout)?,
+                };
+                LLMProviderEnum::OpenAI(provider.with_organization(config.openai_org.clone(), config.openai_project.clone()))
             }
             LLMProvider::Anthropic => {
-                let api_key = config
-                    .api_key
-                    .clone()
+                let api_key = api_key
                     .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
                     .ok_or_else(|| anyhow!("Anthropic API key not found"))?;
                 
-                LLMProviderEnum::Anthropic(AnthropicProvider::new(api_key, config.model.clone())?)
+                LLMProviderEnum::Anthropic(match endpoint {
+                    Some(endpoint) => AnthropicProvider::with_endpoint(api_key, model.to_string(), endpoint, config.timeout)?,
+                    None => AnthropicProvider::new(api_key, model.to_string(), config.timeout)?,
+                })
             }
             LLMProvider::Local(endpoint) => {
                 return Err(anyhow!("Local provider not yet implemented: {}", endpoint));
@@ -70,37 +124,87 @@ impl LLMClient {
             LLMProvider::Custom(endpoint) => {
                 return Err(anyhow!("Custom provider not yet implemented: {}", endpoint));
             }
-        };
+            LLMProvider::Mock(fixed_response) => {
+                LLMProviderEnum::Mock(MockProvider::new(fixed_response.clone()))
+            }
+        })
+    }
 
-        // Create cache with 100 entries
-        let cache = Arc::new(Mutex::new(
-            LruCache::new(NonZeroUsize::new(100).unwrap())
-        ));
+    // Models available from the configured provider. Local servers have no
+    // provider implementation yet, so they are asked directly.
+    pub async fn list_models(config: &LLMConfig) -> Result<Vec<String>> {
+        if let LLMProvider::Local(endpoint) = &config.provider {
+            return local::list_models(endpoint, config.timeout).await;
+        }
 
-        Ok(Self {
-            provider,
-            cache,
-            config,
-        })
+        let endpoint = config.endpoint.clone().filter(|e| !e.is_empty());
+        let provider = Self::build_provider(&config.provider, &config.model, config.api_key.clone(), endpoint, config)?;
+        provider.list_models().await
+    }
+
+    // Try each configured fallback in turn after the primary provider failed.
+    // Fallbacks read their keys from the provider's usual environment variable
+    // and use its default endpoint.
+    async fn generate_with_fallbacks(&self, request: &LLMRequest, primary_error: anyhow::Error) -> Result<LLMResponse> {
+        let mut failures = vec![format!("{}: {}", self.provider.name(), primary_error)];
+
+        for FallbackProvider { provider, model } in &self.config.fallback_providers {
+            let fallback = match Self::build_provider(provider, model, None, None, &self.config) {
+                Ok(fallback) => fallback,
+                Err(e) => {
+                    failures.push(format!("{}: {}", provider, e));
+                    continue;
+                }
+            };
+
+            match fallback.generate(request).await {
+                Ok(response) => {
+                    if self.config.verbose {
+                        println!("{} failed ({}); answered by {} instead", self.provider.name(), primary_error, fallback.name());
+                    }
+                    return Ok(response);
+                }
+                Err(e) => failures.push(format!("{}: {}", fallback.name(), e)),
+            }
+        }
+
+        if failures.len() == 1 {
+            return Err(primary_error);
+        }
+        Err(anyhow!("All LLM providers failed: {}", failures.join("; ")))
     }
 
     pub async fn generate(&self, request: &LLMRequest) -> Result<LLMResponse> {
-        // Check cache first
+        // Check cache first, unless it is disabled or bypassed with --no-cache
         let cache_key = self.calculate_cache_key(request);
         
-        {
-            let mut cache = self.cache.lock().await;
+        if self.config.use_cache {
+            let mut cache = shared_cache().lock().await;
             if let Some(cached_response) = cache.get(&cache_key) {
                 return Ok(cached_response.clone());
             }
         }
 
+        let wait = rate_limit::reserve(self.config.requests_per_minute);
+        if !wait.is_zero() {
+            if self.config.verbose {
+                println!("Rate limited, waiting {:.1}s before contacting {}...", wait.as_secs_f64(), self.provider.name());
+            }
+            tokio::time::sleep(wait).await;
+        }
+
         // Generate new response
-        let response = self.provider.generate(request).await?;
+        let response = match self.provider.generate(request).await {
+            Ok(response) => response,
+            Err(e) => self.generate_with_fallbacks(request, e).await?,
+        };
+        if let Some(usage) = &response.usage {
+            TOKENS_USED.fetch_add(usage.total_tokens as u64, Ordering::Relaxed);
+        }
 
         // Cache the response
-        {
-            let mut cache = self.cache.lock().await;
+        if self.config.use_cache {
+            let mut cache = shared_cache().lock().await;
             cache.put(cache_key, response.clone());
         }
 
@@ -115,10 +219,6 @@ impl LLMClient {
         self.provider.name()
     }
 
-    pub fn config(&self) -> &LLMConfig {
-        &self.config
-    }
-
     fn calculate_cache_key(&self, request: &LLMRequest) -> u64 {
         let mut hasher = DefaultHasher::new();
         request.prompt.hash(&mut hasher);
@@ -129,16 +229,82 @@ impl LLMClient {
             context.hash(&mut hasher);
         }
         self.config.model.hash(&mut hasher);
+        // The cache is shared, so providers must not answer for each other
+        format!("{:?}", self.config.provider).hash(&mut hasher);
         hasher.finish()
     }
 
-    pub async fn clear_cache(&self) {
-        let mut cache = self.cache.lock().await;
+    // Empty the response cache, returning how many entries it held
+    pub async fn clear_cache() -> usize {
+        let mut cache = shared_cache().lock().await;
+        let size = cache.len();
         cache.clear();
+        size
     }
 
-    pub async fn cache_size(&self) -> usize {
-        let cache = self.cache.lock().await;
-        cache.len()
+    pub async fn cache_size() -> usize {
+        shared_cache().lock().await.len()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cache_can_be_bypassed() {
+        let mut config = LLMConfig {
+            provider: LLMProvider::Mock(Some("cached?".to_string())),
+            use_cache: false,
+            ..Default::default()
+        };
+        let request = LLMRequest {
+            prompt: "cache test".to_string(),
+            max_tokens: 10,
+            temperature: 0.0,
+            context: None,
+        };
+        let is_cached = |client: &LLMClient| {
+            let key = client.calculate_cache_key(&request);
+            async move { shared_cache().lock().await.contains(&key) }
+        };
+
+        let client = LLMClient::new(config.clone()).await.unwrap();
+        client.generate(&request).await.unwrap();
+        assert!(!is_cached(&client).await);
+
+        config.use_cache = true;
+        let client = LLMClient::new(config).await.unwrap();
+        client.generate(&request).await.unwrap();
+        assert!(is_cached(&client).await);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_answers_when_primary_fails() {
+        // Nothing listens on port 1, so the primary fails straight away
+        let mut config = LLMConfig {
+            api_key: Some("sk-test".to_string()),
+            endpoint: Some("http://127.0.0.1:1/v1/chat/completions".to_string()),
+            timeout: Duration::from_secs(5),
+            requests_per_minute: 0,
+            ..Default::default()
+        };
+        let request = LLMRequest {
+            prompt: "tidy up".to_string(),
+            max_tokens: 10,
+            temperature: 0.0,
+            context: None,
+        };
+
+        let client = LLMClient::new(config.clone()).await.unwrap();
+        assert!(client.generate(&request).await.is_err());
+
+        config.fallback_providers = vec![
+            FallbackProvider { provider: LLMProvider::Local("x".to_string()), model: String::new() },
+            FallbackProvider { provider: LLMProvider::Mock(Some("pwd".to_string())), model: String::new() },
+        ];
+        let client = LLMClient::new(config).await.unwrap();
+        assert_eq!(client.generate(&request).await.unwrap().content, "pwd");
+    }
+}
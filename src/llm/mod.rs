@@ -1,10 +1,12 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
 use std::time::Duration;
 
 pub mod client;
 pub mod prompts;
 pub mod providers;
+pub mod rate_limit;
 
 pub use client::LLMClient;
 
@@ -14,6 +16,7 @@ pub enum LLMProvider {
     Anthropic,
     Local(String),
     Custom(String),
+    Mock(Option<String>),
 }
 
 impl std::fmt::Display for LLMProvider {
@@ -23,10 +26,27 @@ impl std::fmt::Display for LLMProvider {
             LLMProvider::Anthropic => write!(f, "Anthropic"),
             LLMProvider::Local(endpoint) => write!(f, "Local({})", endpoint),
             LLMProvider::Custom(endpoint) => write!(f, "Custom({})", endpoint),
+            LLMProvider::Mock(_) => write!(f, "Mock"),
         }
     }
 }
 
+// Model used when none is configured for `provider`, whether it is the main
+// provider or a fallback; Anthropic rejects OpenAI model names with a 404
+pub fn default_model_for(provider: &LLMProvider) -> &'static str {
+    match provider {
+        LLMProvider::Anthropic => "claude-3-haiku-20240307",
+        _ => "gpt-3.5-turbo",
+    }
+}
+
+// A provider to try when the primary one fails, with the model to ask it for
+#[derive(Debug, Clone)]
+pub struct FallbackProvider {
+    pub provider: LLMProvider,
+    pub model: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct LLMConfig {
     pub provider: LLMProvider,
@@ -34,8 +54,18 @@ pub struct LLMConfig {
     pub api_key: Option<String>,
     pub endpoint: Option<String>,
     pub timeout: Duration,
-    pub max_tokens: u32,
-    pub temperature: f32,
+    // 0 disables rate limiting
+    pub requests_per_minute: u32,
+    pub verbose: bool,
+    // Look up and store responses in the cache
+    pub use_cache: bool,
+    // Number of responses the cache keeps
+    pub cache_size: NonZeroUsize,
+    // Tried in order when the primary provider fails
+    pub fallback_providers: Vec<FallbackProvider>,
+    // OpenAI organization and project for org-scoped keys
+    pub openai_org: Option<String>,
+    pub openai_project: Option<String>,
 }
 
 impl Default for LLMConfig {
@@ -46,8 +76,13 @@ impl Default for LLMConfig {
             api_key: None,
             endpoint: None,
             timeout: Duration::from_secs(30),
-            max_tokens: 150,
-            temperature: 0.1,
+            requests_per_minute: 30,
+            verbose: false,
+            use_cache: true,
+            cache_size: NonZeroUsize::new(100).unwrap(),
+            fallback_providers: Vec::new(),
+            openai_org: None,
+            openai_project: None,
         }
     }
 }
@@ -79,4 +114,9 @@ pub trait LLMProviderTrait: Send + Sync {
     async fn generate(&self, request: &LLMRequest) -> Result<LLMResponse>;
     fn name(&self) -> &str;
     fn is_available(&self) -> bool;
+
+    // Model IDs the provider offers, for the `models` command
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Err(anyhow::anyhow!("{} does not support listing models", self.name()))
+    }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 pub struct PromptTemplate {
     pub system_prompt: String,
     pub os_context: HashMap<String, String>,
+    pub shell_context: HashMap<String, String>,
     pub safety_rules: Vec<String>,
     pub output_format: String,
 }
@@ -16,6 +17,24 @@ impl PromptTemplate {
         os_context.insert("linux".to_string(), "Linux bash/sh shell".to_string());
         os_context.insert("macos".to_string(), "macOS bash/zsh shell".to_string());
 
+        let mut shell_context = HashMap::new();
+        shell_context.insert("bash".to_string(), "bash (POSIX syntax, e.g. `export NAME=value`)".to_string());
+        shell_context.insert("zsh".to_string(), "zsh (POSIX syntax, e.g. `export NAME=value`)".to_string());
+        shell_context.insert("sh".to_string(), "POSIX sh (no bash extensions)".to_string());
+        shell_context.insert(
+            "fish".to_string(),
+            "fish (not POSIX: `set -gx NAME value`, `(cmd)` instead of `$(cmd)`)".to_string(),
+        );
+        shell_context.insert(
+            "pwsh".to_string(),
+            "PowerShell (`$env:NAME = \"value\"`, cmdlets such as Get-ChildItem)".to_string(),
+        );
+        shell_context.insert(
+            "powershell".to_string(),
+            "Windows PowerShell (`$env:NAME = \"value\"`, cmdlets such as Get-ChildItem)".to_string(),
+        );
+        shell_context.insert("cmd".to_string(), "Windows Command Prompt (`set NAME=value`, `dir`, `del`)".to_string());
+
         let safety_rules = vec![
             "Never suggest commands that could harm the system".to_string(),
             "Always use safe file operations".to_string(),
@@ -27,25 +46,60 @@ impl PromptTemplate {
         Self {
             system_prompt: Self::default_system_prompt(),
             os_context,
+            shell_context,
             safety_rules,
             output_format: Self::default_output_format(),
         }
     }
 
-    pub fn build_prompt(&self, user_input: &str, os: &str) -> String {
+    // Shells without an entry are named as detected, e.g. "nu"
+    fn shell_info(&self, shell: &str) -> String {
+        match self.shell_context.get(shell) {
+            Some(info) => info.clone(),
+            None if shell.is_empty() || shell == "unknown" => "Unknown shell".to_string(),
+            None => shell.to_string(),
+        }
+    }
+
+    pub fn build_prompt(&self, user_input: &str, os: &str, shell: &str) -> String {
         let default_os = "Unknown OS".to_string();
         let os_info = self.os_context.get(os).unwrap_or(&default_os);
         
         format!(
-            "{}\n\nTarget OS: {}\nUser Request: \"{}\"\n\nSafety Rules:\n{}\n\nOutput Format:\n{}\n\nProvide only the command:",
+            "{}\n\nTarget OS: {}\nTarget Shell: {}\nUser Request: \"{}\"\n\nSafety Rules:\n{}\n\nOutput Format:\n{}\n\nProvide only the command:",
             self.system_prompt,
             os_info,
+            self.shell_info(shell),
             user_input,
             self.safety_rules.join("\n- "),
             self.output_format
         )
     }
 
+    // Ask for several requests in one round-trip, answered as a JSON array of
+    // commands in the same order
+    pub fn build_batch_prompt(&self, requests: &[String], os: &str, shell: &str) -> String {
+        let default_os = "Unknown OS".to_string();
+        let os_info = self.os_context.get(os).unwrap_or(&default_os);
+        let numbered: Vec<String> = requests
+            .iter()
+            .enumerate()
+            .map(|(i, request)| format!("{}. \"{}\"", i + 1, request))
+            .collect();
+
+        format!(
+            "{}\n\nTarget OS: {}\nTarget Shell: {}\nUser Requests:\n{}\n\nSafety Rules:\n{}\n\nOutput Format:\n\
+             Return ONLY a JSON array of {} strings, one command per request, in the same order. \
+             No explanations and no other text.",
+            self.system_prompt,
+            os_info,
+            self.shell_info(shell),
+            numbered.join("\n"),
+            self.safety_rules.join("\n- "),
+            requests.len()
+        )
+    }
+
     fn default_system_prompt() -> String {
         r#"You are a cross-platform command translator. Your job is to convert natural language requests into appropriate shell commands for the target operating system.
 
@@ -79,6 +133,76 @@ impl Default for PromptTemplate {
     }
 }
 
+// Characters of file content sent for summarization: roughly 3,000 tokens,
+// which leaves room for the reply within a 4K-token context window
+pub const SUMMARY_MAX_CHARS: usize = 12_000;
+
+// The first SUMMARY_MAX_CHARS characters of `content`, and whether that cut anything
+fn excerpt(content: &str) -> (&str, bool) {
+    match content.char_indices().nth(SUMMARY_MAX_CHARS) {
+        Some((cut, _)) => (&content[..cut], true),
+        None => (content, false),
+    }
+}
+
+// Build a summarization prompt for `content`, cutting it to SUMMARY_MAX_CHARS.
+// Returns the prompt and whether the content had to be truncated.
+pub fn build_summary_prompt(name: &str, content: &str) -> (String, bool) {
+    let (excerpt, truncated) = excerpt(content);
+
+    let note = if truncated {
+        "Only the beginning of the file is included; say so if it matters.\n"
+    } else {
+        ""
+    };
+
+    let prompt = format!(
+        "Summarize the following file concisely in a few sentences or bullet points. \
+         Point out errors or warnings if it is a log.\n{}\nFile: {}\n---\n{}\n---",
+        note, name, excerpt
+    );
+    (prompt, truncated)
+}
+
+// Build the prompt for `ask`: the question, with the output of `command`
+// (cut to SUMMARY_MAX_CHARS) when one was piped in. Returns the prompt and
+// whether the output had to be truncated.
+pub fn build_ask_prompt(question: &str, piped: Option<(&str, &str)>) -> (String, bool) {
+    let Some((command, output)) = piped else {
+        return (question.to_string(), false);
+    };
+
+    let (excerpt, truncated) = excerpt(output);
+    let note = if truncated {
+        "Only the beginning of the output is included; say so if it matters.\n"
+    } else {
+        ""
+    };
+    let output = if excerpt.trim().is_empty() { "(no output)" } else { excerpt.trim_end() };
+    let prompt = format!(
+        "Answer the question about the output of the shell command `{}`. Be concise.\n{}---\n{}\n---\nQuestion: {}",
+        command, note, output, question
+    );
+    (prompt, truncated)
+}
+
+// Pull the commands out of a reply to `build_batch_prompt`. Models like to wrap
+// JSON in code fences or add a sentence around it, so only the outermost
+// array is parsed.
+pub fn parse_batch_reply(reply: &str, expected: usize) -> Result<Vec<String>, String> {
+    let array = match (reply.find('['), reply.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err("the reply contains no JSON array".to_string()),
+    };
+    let commands: Vec<String> =
+        serde_json::from_str(array).map_err(|e| format!("the reply is not a JSON array of strings: {}", e))?;
+
+    if commands.len() != expected {
+        return Err(format!("expected {} commands but the reply has {}", expected, commands.len()));
+    }
+    Ok(commands.into_iter().map(|command| command.trim().to_string()).collect())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandTranslation {
     pub original_request: String,
@@ -100,6 +224,22 @@ pub fn detect_os() -> String {
     }
 }
 
+// The user's shell, from `$SHELL` (also set by Git Bash and similar on Windows)
+// or else `%COMSPEC%`
+pub fn detect_shell() -> String {
+    std::env::var("SHELL")
+        .or_else(|_| std::env::var("COMSPEC"))
+        .map(|path| shell_name(&path))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+// "/usr/bin/fish" -> "fish", "C:\Windows\system32\cmd.exe" -> "cmd"
+fn shell_name(path: &str) -> String {
+    let file = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let name = file.strip_suffix(".exe").or_else(|| file.strip_suffix(".EXE")).unwrap_or(file);
+    name.to_lowercase()
+}
+
 pub fn is_natural_language(input: &str) -> bool {
     // If the input is surrounded by quotes, treat it as natural language
     if (input.starts_with('"') && input.ends_with('"')) || 
@@ -151,13 +291,25 @@ mod tests {
     #[test]
     fn test_prompt_building() {
         let template = PromptTemplate::new();
-        let prompt = template.build_prompt("create a directory called test", "linux");
+        let prompt = template.build_prompt("create a directory called test", "linux", "fish");
         
         assert!(prompt.contains("create a directory called test"));
         assert!(prompt.contains("Linux bash/sh shell"));
+        assert!(prompt.contains("Target Shell: fish (not POSIX"));
+        assert!(template.build_prompt("x", "linux", "nu").contains("Target Shell: nu\n"));
         assert!(prompt.contains("Never suggest commands that could harm the system"));
     }
 
+    #[test]
+    fn test_parse_batch_reply() {
+        let reply = "```json\n[\"mkdir app\", \" cd app \"]\n```";
+        assert_eq!(parse_batch_reply(reply, 2).unwrap(), vec!["mkdir app", "cd app"]);
+
+        assert!(parse_batch_reply(reply, 3).unwrap_err().contains("expected 3"));
+        assert!(parse_batch_reply("mkdir app", 1).is_err());
+        assert!(parse_batch_reply("[1, 2]", 2).is_err());
+    }
+
     #[test]
     fn test_natural_language_detection() {
         assert!(is_natural_language("create a new directory"));
@@ -170,6 +322,40 @@ mod tests {
         assert!(!is_natural_language("cd /home"));
     }
 
+    #[test]
+    fn test_summary_prompt_truncates_long_files() {
+        let (prompt, truncated) = build_summary_prompt("notes.txt", "short notes");
+        assert!(!truncated);
+        assert!(prompt.contains("File: notes.txt"));
+        assert!(prompt.contains("short notes"));
+
+        // Multi-byte characters must not be split
+        let long = "é".repeat(SUMMARY_MAX_CHARS + 10);
+        let (prompt, truncated) = build_summary_prompt("big.log", &long);
+        assert!(truncated);
+        assert!(prompt.contains("Only the beginning"));
+        assert_eq!(prompt.matches('é').count(), SUMMARY_MAX_CHARS);
+    }
+
+    #[test]
+    fn test_ask_prompt() {
+        assert_eq!(build_ask_prompt("what is a PID?", None), ("what is a PID?".to_string(), false));
+
+        let (prompt, truncated) = build_ask_prompt("which are config files?", Some(("ls", "Cargo.toml\nsrc\n")));
+        assert!(!truncated);
+        assert!(prompt.contains("`ls`"));
+        assert!(prompt.contains("---\nCargo.toml\nsrc\n---\nQuestion: which are config files?"));
+        assert!(build_ask_prompt("anything?", Some(("true", ""))).0.contains("(no output)"));
+    }
+
+    #[test]
+    fn test_shell_name() {
+        assert_eq!(shell_name("/usr/bin/fish"), "fish");
+        assert_eq!(shell_name("/bin/zsh"), "zsh");
+        assert_eq!(shell_name("C:\\Windows\\system32\\cmd.exe"), "cmd");
+        assert_eq!(shell_name("C:\\Program Files\\PowerShell\\7\\pwsh.EXE"), "pwsh");
+    }
+
     #[test]
     fn test_os_detection() {
         let os = detect_os();
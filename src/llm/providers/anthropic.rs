@@ -4,6 +4,15 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+// The models listed in Anthropic's documentation, newest first
+const DOCUMENTED_MODELS: &[&str] = &[
+    "claude-3-5-sonnet-20241022",
+    "claude-3-5-haiku-20241022",
+    "claude-3-opus-20240229",
+    "claude-3-sonnet-20240229",
+    "claude-3-haiku-20240307",
+];
+
 #[derive(Debug)]
 pub struct AnthropicProvider {
     client: Client,
@@ -23,6 +32,10 @@ struct AnthropicRequest {
     model: String,
     max_tokens: u32,
     temperature: f32,
+    // The Messages API takes the system prompt here; `messages` only allows
+    // user and assistant turns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
     messages: Vec<AnthropicMessage>,
 }
 
@@ -47,9 +60,9 @@ struct AnthropicUsage {
 }
 
 impl AnthropicProvider {
-    pub fn new(api_key: String, model: String) -> Result<Self> {
+    pub fn new(api_key: String, model: String, timeout: Duration) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(timeout)
             .build()?;
 
         Ok(Self {
@@ -60,9 +73,9 @@ impl AnthropicProvider {
         })
     }
 
-    pub fn with_endpoint(api_key: String, model: String, endpoint: String) -> Result<Self> {
+    pub fn with_endpoint(api_key: String, model: String, endpoint: String, timeout: Duration) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(timeout)
             .build()?;
 
         Ok(Self {
@@ -72,32 +85,25 @@ impl AnthropicProvider {
             endpoint,
         })
     }
+
+    fn build_request(&self, request: &LLMRequest) -> AnthropicRequest {
+        AnthropicRequest {
+            model: self.model.clone(),
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            system: request.context.clone(),
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: request.prompt.clone(),
+            }],
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl LLMProviderTrait for AnthropicProvider {
     async fn generate(&self, request: &LLMRequest) -> Result<LLMResponse> {
-        let mut messages = Vec::new();
-        
-        // Add system message if context is provided
-        if let Some(context) = &request.context {
-            messages.push(AnthropicMessage {
-                role: "system".to_string(),
-                content: context.clone(),
-            });
-        }
-        
-        messages.push(AnthropicMessage {
-            role: "user".to_string(),
-            content: request.prompt.clone(),
-        });
-
-        let anthropic_request = AnthropicRequest {
-            model: self.model.clone(),
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            messages,
-        };
+        let anthropic_request = self.build_request(request);
 
         let response = self
             .client
@@ -145,4 +151,34 @@ impl LLMProviderTrait for AnthropicProvider {
     fn is_available(&self) -> bool {
         !self.api_key.is_empty()
     }
-}
\ No newline at end of file
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(DOCUMENTED_MODELS.iter().map(|model| model.to_string()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_is_sent_as_top_level_system() {
+        let provider = AnthropicProvider::new("key".to_string(), "claude-3-haiku-20240307".to_string(), Duration::from_secs(5)).unwrap();
+        let mut request = LLMRequest {
+            prompt: "list files".to_string(),
+            max_tokens: 150,
+            temperature: 0.1,
+            context: Some("You translate requests into shell commands.".to_string()),
+        };
+
+        let body = serde_json::to_value(provider.build_request(&request)).unwrap();
+        assert_eq!(body["system"], "You translate requests into shell commands.");
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["role"], "user");
+
+        request.context = None;
+        let body = serde_json::to_value(provider.build_request(&request)).unwrap();
+        assert!(body.get("system").is_none());
+    }
+}
@@ -0,0 +1,33 @@
+// Local model servers. Translation through them is not implemented yet, but
+// Ollama-compatible servers can already report the models they have pulled.
+
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Debug, Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+}
+
+// Query Ollama's `/api/tags` on the configured endpoint
+pub async fn list_models(endpoint: &str, timeout: Duration) -> Result<Vec<String>> {
+    let client = Client::builder().timeout(timeout).build()?;
+    let url = format!("{}/api/tags", endpoint.trim_end_matches('/'));
+
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(anyhow!("{} returned {}: {}", url, status, text));
+    }
+
+    let tags: OllamaTags = response.json().await?;
+    Ok(tags.models.into_iter().map(|model| model.name).collect())
+}
@@ -0,0 +1,104 @@
+use crate::llm::{LLMProviderTrait, LLMRequest, LLMResponse};
+use anyhow::Result;
+use std::collections::HashMap;
+
+// Offline provider returning canned responses, for tests and credential-free demos.
+// Requests are looked up in a table by their (lowercased) user request; anything
+// unknown gets the fixed response if one is set, or a harmless `echo` otherwise.
+#[derive(Debug)]
+pub struct MockProvider {
+    responses: HashMap<String, String>,
+    fixed_response: Option<String>,
+}
+
+impl MockProvider {
+    pub fn new(fixed_response: Option<String>) -> Self {
+        let mut responses = HashMap::new();
+        responses.insert("list files".to_string(), "ls".to_string());
+        responses.insert("list all files".to_string(), "ls".to_string());
+        responses.insert("show current directory".to_string(), "pwd".to_string());
+        responses.insert("where am i".to_string(), "pwd".to_string());
+
+        Self {
+            responses,
+            fixed_response,
+        }
+    }
+}
+
+// Pull the user's request back out of a prompt built by `PromptTemplate::build_prompt`
+fn extract_user_request(prompt: &str) -> &str {
+    const MARKER: &str = "User Request: \"";
+    match prompt.find(MARKER) {
+        Some(start) => {
+            let rest = &prompt[start + MARKER.len()..];
+            match rest.find("\"\n") {
+                Some(end) => &rest[..end],
+                None => rest,
+            }
+        }
+        None => prompt,
+    }
+}
+
+#[async_trait::async_trait]
+impl LLMProviderTrait for MockProvider {
+    async fn generate(&self, request: &LLMRequest) -> Result<LLMResponse> {
+        let user_request = extract_user_request(&request.prompt).trim();
+
+        let content = match self.responses.get(&user_request.to_lowercase()) {
+            Some(response) => response.clone(),
+            None => match &self.fixed_response {
+                Some(response) => response.clone(),
+                None => format!("echo {}", user_request),
+            },
+        };
+
+        Ok(LLMResponse {
+            content,
+            finish_reason: "stop".to_string(),
+            usage: None,
+        })
+    }
+
+    fn name(&self) -> &str {
+        "Mock"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::prompts::PromptTemplate;
+
+    fn request_for(input: &str) -> LLMRequest {
+        LLMRequest {
+            prompt: PromptTemplate::new().build_prompt(input, "linux", "bash"),
+            max_tokens: 150,
+            temperature: 0.1,
+            context: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_lookup_and_echo() {
+        let provider = MockProvider::new(None);
+
+        let response = provider.generate(&request_for("List Files")).await.unwrap();
+        assert_eq!(response.content, "ls");
+
+        let response = provider.generate(&request_for("make coffee")).await.unwrap();
+        assert_eq!(response.content, "echo make coffee");
+    }
+
+    #[tokio::test]
+    async fn test_mock_fixed_response() {
+        let provider = MockProvider::new(Some("pwd".to_string()));
+        let response = provider.generate(&request_for("anything at all")).await.unwrap();
+        assert_eq!(response.content, "pwd");
+    }
+}
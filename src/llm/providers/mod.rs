@@ -1,2 +1,4 @@
 pub mod openai;
-pub mod anthropic;
\ No newline at end of file
+pub mod anthropic;
+pub mod mock;
+pub mod local;
\ No newline at end of file
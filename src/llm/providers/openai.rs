@@ -1,6 +1,6 @@
 use crate::llm::{LLMProviderTrait, LLMRequest, LLMResponse, Usage};
 use anyhow::{anyhow, Result};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -10,6 +10,8 @@ pub struct OpenAIProvider {
     api_key: String,
     model: String,
     endpoint: String,
+    organization: Option<String>,
+    project: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,6 +40,16 @@ struct OpenAIChoice {
     finish_reason: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OpenAIModelList {
+    data: Vec<OpenAIModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIModel {
+    id: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct OpenAIUsage {
     prompt_tokens: u32,
@@ -46,9 +58,9 @@ struct OpenAIUsage {
 }
 
 impl OpenAIProvider {
-    pub fn new(api_key: String, model: String) -> Result<Self> {
+    pub fn new(api_key: String, model: String, timeout: Duration) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(timeout)
             .build()?;
 
         Ok(Self {
@@ -56,12 +68,14 @@ impl OpenAIProvider {
             api_key,
             model,
             endpoint: "https://api.openai.com/v1/chat/completions".to_string(),
+            organization: None,
+            project: None,
         })
     }
 
-    pub fn with_endpoint(api_key: String, model: String, endpoint: String) -> Result<Self> {
+    pub fn with_endpoint(api_key: String, model: String, endpoint: String, timeout: Duration) -> Result<Self> {
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(timeout)
             .build()?;
 
         Ok(Self {
@@ -69,8 +83,37 @@ impl OpenAIProvider {
             api_key,
             model,
             endpoint,
+            organization: None,
+            project: None,
         })
     }
+
+    // Org-scoped keys need the organization and project on every request
+    pub fn with_organization(mut self, organization: Option<String>, project: Option<String>) -> Self {
+        self.organization = organization;
+        self.project = project;
+        self
+    }
+
+    fn authorize(&self, builder: RequestBuilder) -> RequestBuilder {
+        let mut builder = builder.header("Authorization", format!("Bearer {}", self.api_key));
+        if let Some(organization) = &self.organization {
+            builder = builder.header("OpenAI-Organization", organization);
+        }
+        if let Some(project) = &self.project {
+            builder = builder.header("OpenAI-Project", project);
+        }
+        builder
+    }
+
+    // `/v1/models` next to the chat completions endpoint, so custom
+    // OpenAI-compatible endpoints list their own models
+    fn models_endpoint(&self) -> String {
+        match self.endpoint.strip_suffix("/chat/completions") {
+            Some(base) => format!("{}/models", base),
+            None => format!("{}/models", self.endpoint.trim_end_matches('/')),
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -95,9 +138,7 @@ impl LLMProviderTrait for OpenAIProvider {
         };
 
         let response = self
-            .client
-            .post(&self.endpoint)
-            .header("Authorization", format!("Bearer {}", self.api_key))
+            .authorize(self.client.post(&self.endpoint))
             .header("Content-Type", "application/json")
             .json(&openai_request)
             .send()
@@ -137,4 +178,39 @@ impl LLMProviderTrait for OpenAIProvider {
     fn is_available(&self) -> bool {
         !self.api_key.is_empty()
     }
-}
\ No newline at end of file
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self
+            .authorize(self.client.get(self.models_endpoint()))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("OpenAI API error {}: {}", status, text));
+        }
+
+        let list: OpenAIModelList = response.json().await?;
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_organization_headers_are_optional() {
+        let provider = OpenAIProvider::new("sk-test".to_string(), "gpt-3.5-turbo".to_string(), Duration::from_secs(5)).unwrap();
+        let request = provider.authorize(provider.client.post(&provider.endpoint)).build().unwrap();
+        assert!(request.headers().get("OpenAI-Organization").is_none());
+        assert!(request.headers().get("OpenAI-Project").is_none());
+
+        let provider = provider.with_organization(Some("org-123".to_string()), Some("proj_456".to_string()));
+        let request = provider.authorize(provider.client.post(&provider.endpoint)).build().unwrap();
+        assert_eq!(request.headers()["OpenAI-Organization"], "org-123");
+        assert_eq!(request.headers()["OpenAI-Project"], "proj_456");
+        assert_eq!(request.headers()["Authorization"], "Bearer sk-test");
+    }
+}
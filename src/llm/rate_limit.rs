@@ -0,0 +1,86 @@
+// Token-bucket limiter for outgoing LLM requests. The bucket is shared by every
+// LLMClient in the process, so an interactive session stays under the limit
+// even though each translation builds a fresh client.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct RateLimiter {
+    capacity: f64,
+    tokens: f64,
+    refill_per_second: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    // Allows bursts of up to `requests_per_minute`, refilling evenly over a minute
+    pub fn per_minute(requests_per_minute: u32, now: Instant) -> Self {
+        let capacity = requests_per_minute.max(1) as f64;
+        RateLimiter {
+            capacity,
+            tokens: capacity,
+            refill_per_second: capacity / 60.0,
+            last_refill: now,
+        }
+    }
+
+    // Take a token for one request and return how long the caller must wait
+    // before sending it. Tokens may go negative, which queues later callers
+    // behind earlier ones.
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity);
+        self.last_refill = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.refill_per_second)
+        }
+    }
+}
+
+static SHARED: Mutex<Option<(u32, RateLimiter)>> = Mutex::new(None);
+
+// Reserve a request against the process-wide limiter. A limit of 0 disables it.
+// Changing the limit (e.g. after `reload`) starts a fresh bucket.
+pub fn reserve(requests_per_minute: u32) -> Duration {
+    if requests_per_minute == 0 {
+        return Duration::ZERO;
+    }
+
+    let now = Instant::now();
+    let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+    match shared.as_mut() {
+        Some((limit, limiter)) if *limit == requests_per_minute => limiter.reserve(now),
+        _ => {
+            let mut limiter = RateLimiter::per_minute(requests_per_minute, now);
+            let wait = limiter.reserve(now);
+            *shared = Some((requests_per_minute, limiter));
+            wait
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_then_delays() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::per_minute(2, start);
+
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        // Third request waits for one token at 2/minute
+        assert_eq!(limiter.reserve(start).as_secs_f64().round(), 30.0);
+        // and the fourth queues behind it
+        assert_eq!(limiter.reserve(start).as_secs_f64().round(), 60.0);
+
+        // After refilling, requests go through immediately again
+        let later = start + Duration::from_secs(180);
+        assert_eq!(limiter.reserve(later), Duration::ZERO);
+    }
+}
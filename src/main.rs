@@ -1,35 +1,31 @@
 use std::env;
 use std::fs;
 use std::fs::File;
-use std::io;
+use std::io::{self, IsTerminal};
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output, Stdio};
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::error::ReadlineError;
-use rustyline::highlight::MatchingBracketHighlighter;
 use rustyline::hint::{Hint, Hinter};
-use rustyline::validate::MatchingBracketValidator;
 use rustyline::{CompletionType, Config, Context, EditMode, Editor, KeyEvent, KeyCode, Modifiers};
 use rustyline_derive::{Helper, Highlighter, Validator};
 use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use fs2::FileExt;
 
 // New modules for LLM integration
 mod llm;
 mod config;
+mod utils;
+mod commands;
 
-use llm::{LLMClient, LLMRequest};
-use llm::prompts::{PromptTemplate, detect_os, is_natural_language};
+use llm::{LLMClient, LLMConfig, LLMProvider, LLMRequest};
+use llm::prompts::{PromptTemplate, detect_os, detect_shell, is_natural_language};
 use config::{get_config, RustShellConfig};
 
-
-#[allow(dead_code)]
-#[allow(unused_imports)]
-// Define the Command trait which will implement platform-specific commands
-trait ShellCommand {
-    fn execute(&self) -> io::Result<()>;
-    fn help(&self) -> String;
-}
+use commands::{registry, CommandResult, ShellCommand};
 
 // Alias manager
 struct AliasManager {
@@ -39,9 +35,15 @@ struct AliasManager {
 
 impl AliasManager {
     fn new() -> io::Result<Self> {
+        Self::with_file(Self::default_file())
+    }
+    
+    fn default_file() -> PathBuf {
         let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
-        let alias_file = home_dir.join(".rustshell_aliases");
-        
+        home_dir.join(".rustshell_aliases")
+    }
+    
+    fn with_file(alias_file: PathBuf) -> io::Result<Self> {
         let mut alias_manager = AliasManager {
             aliases: HashMap::new(),
             alias_file,
@@ -53,11 +55,44 @@ impl AliasManager {
         Ok(alias_manager)
     }
     
+    // Open the lock file guarding the alias file. The alias file itself is replaced
+    // on every save, so a separate, stable file is locked instead. The lock is
+    // released when the returned handle is dropped.
+    fn lock(&self, exclusive: bool) -> io::Result<File> {
+        let mut lock_name = self.alias_file.file_name().unwrap_or_default().to_os_string();
+        lock_name.push(".lock");
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.alias_file.with_file_name(lock_name))?;
+        
+        if exclusive {
+            lock_file.lock_exclusive()?;
+        } else {
+            lock_file.lock_shared()?;
+        }
+        
+        Ok(lock_file)
+    }
+    
     fn load_aliases(&mut self) -> io::Result<()> {
         if !self.alias_file.exists() {
             return Ok(());
         }
         
+        let _lock = self.lock(false)?;
+        self.aliases = self.read_aliases()?;
+        
+        Ok(())
+    }
+    
+    fn read_aliases(&self) -> io::Result<HashMap<String, String>> {
+        let mut aliases = HashMap::new();
+        if !self.alias_file.exists() {
+            return Ok(aliases);
+        }
+        
         let file = File::open(&self.alias_file)?;
         let reader = std::io::BufReader::new(file);
         
@@ -70,35 +105,76 @@ impl AliasManager {
             if let Some(pos) = line.find('=') {
                 let name = line[..pos].trim().to_string();
                 let command = line[pos+1..].trim().to_string();
-                self.aliases.insert(name, command);
+                aliases.insert(name, command);
             }
         }
         
-        Ok(())
+        Ok(aliases)
+    }
+    
+    // Apply a change to the aliases while holding the exclusive lock. The file is
+    // re-read first so that aliases saved by other sessions since we loaded are
+    // merged in rather than overwritten.
+    fn update_aliases<R>(&mut self, change: impl FnOnce(&mut HashMap<String, String>) -> R) -> io::Result<R> {
+        let _lock = self.lock(true)?;
+        self.aliases = self.read_aliases()?;
+        let result = change(&mut self.aliases);
+        self.save_aliases()?;
+        Ok(result)
     }
     
     fn save_aliases(&self) -> io::Result<()> {
-        let mut file = File::create(&self.alias_file)?;
-        
-        writeln!(file, "# RustShell aliases")?;
+        let mut content = String::from("# RustShell aliases\n");
         for (name, command) in &self.aliases {
-            writeln!(file, "{}={}", name, command)?;
+            content.push_str(&format!("{}={}\n", name, command));
         }
         
-        Ok(())
+        utils::atomic_write(&self.alias_file, content.as_bytes())
+    }
+    
+    // Aliases expand once, so the alias's own command is what would run
+    fn is_dangerous(command: &str, config: &RustShellConfig) -> bool {
+        config.is_dangerous_command(command)
+    }
+    
+    // Warn before saving an alias that expands to a dangerous command and ask
+    // whether to keep it; returns false if the user declines.
+    fn confirm_dangerous(name: &str, command: &str) -> io::Result<bool> {
+        if !Self::is_dangerous(command, &current_config()) {
+            return Ok(true);
+        }
+        println!("⚠ Warning: alias '{}' expands to a dangerous command: {}", name, command);
+        utils::confirm("Save it anyway?", false)
     }
     
     fn add_alias(&mut self, name: String, command: String) -> io::Result<()> {
-        self.aliases.insert(name, command);
-        self.save_aliases()
+        self.update_aliases(|aliases| {
+            aliases.insert(name, command);
+        })
     }
     
     fn remove_alias(&mut self, name: &str) -> io::Result<bool> {
-        let existed = self.aliases.remove(name).is_some();
-        if existed {
-            self.save_aliases()?;
-        }
-        Ok(existed)
+        self.update_aliases(|aliases| aliases.remove(name).is_some())
+    }
+    
+    // Give an alias a new name, keeping its command. Returns false if `old` is not
+    // an alias; refuses to overwrite an existing alias called `new`.
+    fn rename_alias(&mut self, old: &str, new: &str) -> io::Result<bool> {
+        self.update_aliases(|aliases| {
+            if aliases.contains_key(new) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("alias '{}' already exists", new),
+                ));
+            }
+            match aliases.remove(old) {
+                Some(command) => {
+                    aliases.insert(new.to_string(), command);
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        })?
     }
     
     fn get_alias(&self, name: &str) -> Option<&String> {
@@ -111,17 +187,26 @@ impl AliasManager {
             return;
         }
         
+        let config = current_config();
         println!("Defined aliases:");
         for (name, command) in &self.aliases {
-            println!("  {} = '{}'", name, command);
+            let marker = if Self::is_dangerous(command, &config) { " ⚠ dangerous" } else { "" };
+            println!("  {} = '{}'{}", name, command, marker);
         }
     }
     
+    // Aliases take precedence over built-in commands. Expansion happens once, so an
+    // alias may refer to the command it shadows (`alias ls list -la`). A leading
+    // backslash (`\ls`) bypasses aliases, as in bash.
     fn expand_aliases(&self, args: &[String]) -> Vec<String> {
         if args.is_empty() {
             return Vec::new();
         }
         
+        if let Some(escaped) = unescape_command(args) {
+            return escaped;
+        }
+        
         // Check if the command is an alias
         if let Some(alias_cmd) = self.get_alias(&args[0]) {
             // Split the alias command into words
@@ -143,6 +228,9 @@ impl AliasManager {
     }
 }
 
+// Options accepted before the command in command mode
+pub const GLOBAL_FLAGS: &[&str] = &["--timeout", "--quiet", "--no-cache", "--script", "--print-prompt", "--print-prompt-only"];
+
 // Custom hint implementation for command suggestions
 struct CommandHint {
     display: String,
@@ -156,50 +244,195 @@ impl Hint for CommandHint {
 
     fn completion(&self) -> Option<&str> {
         if self.complete_up_to > 0 {
-            Some(&self.display[..self.complete_up_to])
+            self.display.get(..self.complete_up_to)
         } else {
             None
         }
     }
 }
 
+// Programs whose arguments are directories, so `run cd <Tab>` offers only those
+const DIRECTORY_PROGRAMS: &[&str] = &["cd", "pushd", "rmdir"];
+
+// With `ui.multiline_prompt`, the directory goes on a line of its own so input
+// always starts at the left margin
+fn prompt_for(dir: &Path, multiline: bool) -> String {
+    if multiline {
+        format!("{}\n❯ ", dir.display())
+    } else {
+        format!("{}> ", dir.display())
+    }
+}
+
+// Width of the input line's part of the current prompt, which the Ctrl-F
+// picker needs to put the cursor back
+static PROMPT_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+// Matches the Ctrl-F picker lists at once
+const FILE_PICKER_ROWS: usize = 9;
+const FILE_PICKER_PROMPT: &str = "  find: ";
+
+// Ctrl-F: a small fuzzy finder over the current directory tree, drawn below the
+// input line. Typing filters, Up/Down (or Ctrl-P/Ctrl-N, Tab) move the
+// selection, Enter inserts the selected path at the cursor, Ctrl-G or Ctrl-C
+// cancels.
+struct FilePicker;
+
+enum PickerKey {
+    Char(char),
+    Backspace,
+    Up,
+    Down,
+    Accept,
+    Cancel,
+    Ignored,
+}
+
+impl FilePicker {
+    fn read_key(input: &mut impl Read) -> PickerKey {
+        let mut byte = [0u8; 1];
+        let mut next = |input: &mut dyn Read| input.read_exact(&mut byte).ok().map(|_| byte[0]);
+
+        match next(input) {
+            None | Some(0x03 | 0x07) => PickerKey::Cancel,
+            Some(b'\r' | b'\n') => PickerKey::Accept,
+            Some(0x7f | 0x08) => PickerKey::Backspace,
+            Some(0x10) => PickerKey::Up,
+            Some(0x0e | b'\t') => PickerKey::Down,
+            // Arrow keys arrive as ESC [ A / ESC O A; a lone Esc cancels
+            Some(0x1b) => match next(input) {
+                Some(b'[' | b'O') => match next(input) {
+                    Some(b'A') => PickerKey::Up,
+                    Some(b'B') => PickerKey::Down,
+                    _ => PickerKey::Ignored,
+                },
+                _ => PickerKey::Cancel,
+            },
+            Some(first) if first < 0x20 => PickerKey::Ignored,
+            Some(first) => {
+                // Collect the rest of a multi-byte UTF-8 character
+                let len = match first {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => 1,
+                };
+                let mut bytes = vec![first];
+                while bytes.len() < len {
+                    match next(input) {
+                        Some(b) => bytes.push(b),
+                        None => break,
+                    }
+                }
+                match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                    Some(c) => PickerKey::Char(c),
+                    None => PickerKey::Ignored,
+                }
+            }
+        }
+    }
+
+    // Draw the query and matches starting at the current row, then put the
+    // cursor back at the end of the query
+    fn render(out: &mut impl Write, query: &str, matches: &[String], selected: usize, width: usize) {
+        let fit = |text: String| text.chars().take(width.saturating_sub(1)).collect::<String>();
+        let mut frame = format!("\r\x1b[J{}", fit(format!("{}{}", FILE_PICKER_PROMPT, query)));
+        for (i, path) in matches.iter().enumerate() {
+            let marker = if i == selected { ">" } else { " " };
+            frame.push_str(&format!("\r\n{}", fit(format!("{} {}", marker, path))));
+        }
+        if matches.is_empty() {
+            frame.push_str("\r\n  (no matches)");
+        }
+        frame.push_str(&format!("\x1b[{}A", matches.len().max(1)));
+        let column = (FILE_PICKER_PROMPT.len() + query.chars().count()).min(width.saturating_sub(1));
+        frame.push_str(&format!("\r\x1b[{}C", column));
+        let _ = out.write_all(frame.as_bytes());
+        let _ = out.flush();
+    }
+
+    fn pick(dir: &Path, width: usize) -> Option<String> {
+        let mut out = io::stdout();
+        let mut input = io::stdin();
+        let mut query = String::new();
+        let mut selected = 0;
+        // Hidden entries are offered once the query starts with a dot
+        let mut candidates = utils::fuzzy_candidates(dir, false);
+        let mut hidden = false;
+
+        let _ = out.write_all(b"\r\n");
+        let picked = loop {
+            if query.starts_with('.') != hidden {
+                hidden = !hidden;
+                candidates = utils::fuzzy_candidates(dir, hidden);
+            }
+            let matches = utils::fuzzy_rank(&query, &candidates, FILE_PICKER_ROWS);
+            selected = selected.min(matches.len().saturating_sub(1));
+            Self::render(&mut out, &query, &matches, selected, width);
+
+            match Self::read_key(&mut input) {
+                PickerKey::Char(c) => {
+                    query.push(c);
+                    selected = 0;
+                }
+                PickerKey::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                PickerKey::Up => selected = selected.checked_sub(1).unwrap_or(matches.len().saturating_sub(1)),
+                PickerKey::Down => selected = if selected + 1 < matches.len() { selected + 1 } else { 0 },
+                PickerKey::Accept => break matches.get(selected).cloned(),
+                PickerKey::Cancel => break None,
+                PickerKey::Ignored => {}
+            }
+        };
+
+        // Erase the picker and return to the input line
+        let _ = out.write_all(b"\r\x1b[J\x1b[A");
+        let _ = out.flush();
+        picked
+    }
+}
+
+impl rustyline::ConditionalEventHandler for FilePicker {
+    fn handle(
+        &self,
+        _: &rustyline::Event,
+        _: rustyline::RepeatCount,
+        _: bool,
+        ctx: &rustyline::EventContext,
+    ) -> Option<rustyline::Cmd> {
+        let dir = env::current_dir().ok()?;
+        let width = terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize).unwrap_or(80);
+
+        match Self::pick(&dir, width) {
+            // Inserting redraws the line
+            Some(path) => Some(rustyline::Cmd::Insert(1, path)),
+            None => {
+                // Nothing changed, so put the cursor back where it was ourselves
+                let before_cursor = PROMPT_WIDTH.load(Ordering::Relaxed) + ctx.line()[..ctx.pos()].chars().count();
+                let column = before_cursor % width.max(1);
+                let mut out = io::stdout();
+                let _ = if column > 0 { write!(out, "\r\x1b[{}C", column) } else { write!(out, "\r") };
+                let _ = out.flush();
+                Some(rustyline::Cmd::Noop)
+            }
+        }
+    }
+}
+
 // Helper struct for rustyline tab completion and other functionality
 #[derive(Helper, Highlighter, Validator)]
 struct RustShellHelper {
     completer: FilenameCompleter,
-    highlighter: MatchingBracketHighlighter,
-    hinter: Option<CommandHint>,
-    validator: MatchingBracketValidator,
     commands: Vec<String>,
     alias_manager: AliasManager,
-    colored_hint: bool,
     config: RustShellConfig,
 }
 
 impl RustShellHelper {
     fn new(alias_manager: AliasManager, config: RustShellConfig) -> Self {
-        let mut commands = vec![
-            "make_dir".to_string(), "mkdir".to_string(),
-            "create_file".to_string(), "touch".to_string(),
-            "copy".to_string(), 
-            "move".to_string(),
-            "delete_file".to_string(), "rm".to_string(),
-            "delete_dir".to_string(), "rmdir".to_string(),
-            "change_dir".to_string(), "cd".to_string(),
-            "list".to_string(), "ls".to_string(),
-            "where_am_i".to_string(), "pwd".to_string(),
-            "run".to_string(), "exec".to_string(),
-            "show".to_string(), "cat".to_string(),
-            "find".to_string(),
-            "compress".to_string(), "zip".to_string(),
-            "help".to_string(),
-            "exit".to_string(), "quit".to_string(),
-            "interactive".to_string(),
-            "alias".to_string(),
-            "unalias".to_string(),
-            "pipe".to_string(),
-            "showall".to_string(),
-        ];
+        let mut commands: Vec<String> = registry().names().map(String::from).collect();
         
         // Add aliases to command completions
         for alias in alias_manager.aliases.keys() {
@@ -211,63 +444,83 @@ impl RustShellHelper {
         
         RustShellHelper {
             completer: FilenameCompleter::new(),
-            highlighter: MatchingBracketHighlighter::new(),
-            hinter: None,
-            validator: MatchingBracketValidator::new(),
             commands,
             alias_manager,
-            colored_hint: true,
             config,
         }
     }
     
     fn update_commands(&mut self) {
         // Update command list with current aliases
-        let mut base_commands = vec![
-            "make_dir".to_string(), "mkdir".to_string(),
-            "create_file".to_string(), "touch".to_string(),
-            "copy".to_string(), 
-            "move".to_string(),
-            "delete_file".to_string(), "rm".to_string(),
-            "delete_dir".to_string(), "rmdir".to_string(),
-            "change_dir".to_string(), "cd".to_string(),
-            "list".to_string(), "ls".to_string(),
-            "where_am_i".to_string(), "pwd".to_string(),
-            "run".to_string(), "exec".to_string(),
-            "show".to_string(), "cat".to_string(),
-            "find".to_string(),
-            "compress".to_string(), "zip".to_string(),
-            "help".to_string(),
-            "exit".to_string(), "quit".to_string(),
-            "interactive".to_string(),
-            "alias".to_string(),
-            "unalias".to_string(),
-            "pipe".to_string(),
-        ];
+        let mut base_commands: Vec<String> = registry().names().map(String::from).collect();
         
         // Add aliases
         for alias in self.alias_manager.aliases.keys() {
             base_commands.push(alias.clone());
         }
         
+        base_commands.sort();
         self.commands = base_commands;
     }
 
-    // Find a matching command for autocomplete
-    fn find_command_match(&self, line: &str) -> Option<String> {
-        // If line is empty, just return first command
-        if line.is_empty() {
-            return self.commands.first().cloned();
+    // Completion inside `run`/`exec`: the program name completes from PATH, and
+    // its arguments to directories for cd-like programs and to files otherwise.
+    // Returns None for lines that aren't a `run` invocation.
+    fn complete_run(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<Option<(usize, Vec<Pair>)>, ReadlineError> {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+
+        // The words before the one being completed
+        let mut words = before[..start].split_whitespace();
+        if !matches!(words.next(), Some("run" | "exec")) {
+            return Ok(None);
         }
-
-        // Find command that starts with the current input
-        for cmd in &self.commands {
-            if cmd.starts_with(line) {
-                return Some(cmd.clone());
+        let mut words = words.peekable();
+        if words.peek() == Some(&"--log") {
+            words.next();
+            if words.next().is_none() {
+                // Completing the log file name
+                return Ok(None);
             }
         }
-        None
+
+        let Some(program) = words.next() else {
+            let candidates = utils::path_executables(&before[start..])
+                .into_iter()
+                .map(|name| Pair { display: name.clone(), replacement: name })
+                .collect();
+            return Ok(Some((start, candidates)));
+        };
+
+        let (start, mut candidates) = self.completer.complete(line, pos, ctx)?;
+        if DIRECTORY_PROGRAMS.contains(&program) {
+            candidates.retain(|pair| pair.replacement.ends_with(std::path::is_separator));
+        }
+        Ok(Some((start, candidates)))
+    }
+
+    // The rest of the first command that `line` is a prefix of. Matching is
+    // case-sensitive like dispatch, and the hint is whatever follows the prefix
+    // in `cmd` itself, so it always starts on a character boundary.
+    fn command_hint(&self, line: &str) -> Option<CommandHint> {
+        self.commands.iter().find_map(|cmd| {
+            let rest = cmd.strip_prefix(line)?;
+            (!rest.is_empty()).then(|| CommandHint {
+                display: rest.to_owned(),
+                complete_up_to: rest.len(),
+            })
+        })
     }
+
 }
 
 impl Hinter for RustShellHelper {
@@ -275,21 +528,11 @@ impl Hinter for RustShellHelper {
 
     fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<CommandHint> {
         // Only show hints at the end of line and for non-empty input
-        if pos < line.len() || line.is_empty() || line.contains(' ') {
+        if pos != line.len() || line.is_empty() || line.contains(' ') {
             return None;
         }
 
-        // Find a command that matches the current line
-        for cmd in &self.commands {
-            if cmd.starts_with(line) && cmd != line {
-                return Some(CommandHint {
-                    display: cmd[line.len()..].to_owned(),
-                    complete_up_to: cmd.len() - line.len(),
-                });
-            }
-        }
-
-        None
+        self.command_hint(line)
     }
 }
 
@@ -302,10 +545,18 @@ impl Completer for RustShellHelper {
         pos: usize,
         ctx: &Context<'_>,
     ) -> Result<(usize, Vec<Pair>), ReadlineError> {
+        // `pos` is a byte offset; never let one that falls inside a multi-byte
+        // character (e.g. in `café/`) reach a string slice
+        let pos = floor_char_boundary(line, pos);
+
+        if let Some(completion) = self.complete_run(line, pos, ctx)? {
+            return Ok(completion);
+        }
+
         // First try to complete the command
         if !line.contains(' ') || pos <= line.find(' ').unwrap_or(line.len()) {
             let mut command_matches = Vec::new();
-            let word = if pos < line.len() { &line[..pos] } else { line };
+            let word = &line[..pos];
             
             // Filter commands that match the current word
             for cmd in &self.commands {
@@ -313,7 +564,7 @@ impl Completer for RustShellHelper {
                     // Use the display field to add formatting
                     // This will show the command in a different color in the completion list
                     command_matches.push(Pair {
-                        display: format!("{}", cmd),
+                        display: cmd.clone(),
                         replacement: cmd.clone(),
                     });
                 }
@@ -336,924 +587,685 @@ impl Completer for RustShellHelper {
     }
 }
 
-// OS-specific command implementations
-mod commands {
-    use super::*;
+// The largest index <= `index` that lies on a char boundary of `s`
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
 
-    pub struct MakeDir {
-        pub path: String,
-        pub parents: bool,
+// Async function to process natural language commands
+async fn process_natural_language(input: &str, config: &RustShellConfig) -> Option<String> {
+    if !config.features.enable_llm || config.features.offline_mode {
+        return None;
     }
 
-    impl ShellCommand for MakeDir {
-        fn execute(&self) -> io::Result<()> {
-            // Use Rust's native functions for cross-platform compatibility
-            let path = Path::new(&self.path);
-            
-            if self.parents {
-                fs::create_dir_all(path)
-            } else {
-                fs::create_dir(path)
-            }
-        }
+    // Check if this looks like natural language
+    if !wants_translation(input) {
+        return None;
+    }
 
-        fn help(&self) -> String {
-            "Create a directory. Usage: mkdir [-p] <directory_name>".to_string()
+    // Without a key every translation fails, so say how to fix that once and
+    // let the line run as typed
+    if let Some(guidance) = missing_key_guidance(config, |name| std::env::var(name).ok()) {
+        if !KEY_GUIDANCE_SHOWN.swap(true, Ordering::Relaxed) {
+            eprintln!("{}", guidance);
         }
+        return None;
     }
 
-    pub struct MakeFile {
-        pub paths: Vec<String>,
+    let clean_input = unquote(input);
+    let mut result = translate_request(clean_input, config).await;
+    // An unusable answer gets one more try with the escalation model, if any
+    if let (Ok(command), Some(escalated)) = (&result, config.escalated()) {
+        if let Some(problem) = translation_problem(command) {
+            if config.ui.verbose_mode {
+                println!("🤖 {}; retrying with {}", problem, escalated.llm.model);
+            }
+            result = translate_request(clean_input, &escalated).await;
+        }
     }
 
-    impl ShellCommand for MakeFile {
-        fn execute(&self) -> io::Result<()> {
-            // Create multiple files
-            for path in &self.paths {
-                println!("Creating file: {}", path);
-                File::create(path)?;
+    match result {
+        Ok(command) => {
+            // Basic safety check
+            if config.is_dangerous_command(&command) {
+                println!("⚠️  Warning: This command appears to be potentially dangerous: {}", command);
+                if config.safety.block_destructive {
+                    println!("❌ Command blocked by safety settings");
+                    return None;
+                }
+            }
+            
+            if config.ui.verbose_mode {
+                println!("🤖 Translated '{}' to: {}", input, command);
             }
-            Ok(())
+            
+            Some(command)
         }
-
-        fn help(&self) -> String {
-            "Create one or more empty files. Usage: create_file <file1> <file2> ...".to_string()
+        Err(e) => {
+            if config.ui.verbose_mode {
+                eprintln!("{:#}", e);
+            }
+            None
         }
     }
+}
 
-    pub struct CopyFile {
-        pub src: String,
-        pub dst: String,
+static KEY_GUIDANCE_SHOWN: AtomicBool = AtomicBool::new(false);
+
+// How to set up the API key, if the configured provider needs one and none was
+// found. The key is looked up as the client does, falling back to the provider's
+// usual variable, read through `env`; configured fallback providers may still
+// answer without it.
+fn missing_key_guidance(config: &RustShellConfig, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let llm_config = config.to_llm_config().ok()?;
+    let default_env = match llm_config.provider {
+        LLMProvider::OpenAI => "OPENAI_API_KEY",
+        LLMProvider::Anthropic => "ANTHROPIC_API_KEY",
+        _ => return None,
+    };
+    if llm_config.api_key.is_some()
+        || env(default_env).is_some()
+        || !llm_config.fallback_providers.is_empty()
+    {
+        return None;
     }
 
-    impl ShellCommand for CopyFile {
-        fn execute(&self) -> io::Result<()> {
-            // Use native Rust for basic file operations
-            fs::copy(&self.src, &self.dst)?;
-            Ok(())
-        }
+    let env_var = config.llm.api_key_env.as_deref().filter(|name| !name.is_empty()).unwrap_or(default_env);
+    let config_path = RustShellConfig::config_file_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "~/.rustshell/config.toml".to_string());
+    Some(format!(
+        "No API key found for {}, so natural-language commands are unavailable. To enable them, either:\n  \
+         - set {} in your environment or a .env file, e.g. `export {}=...`\n  \
+         - save the key in a file and point llm.api_key_file at it in {}\n\
+         Or set features.offline_mode = true there to stop trying.",
+        llm_config.provider, env_var, env_var, config_path
+    ))
+}
 
-        fn help(&self) -> String {
-            "Copy a file. Usage: copy <source> <destination>".to_string()
-        }
+// A request typed in quotes is translated without them
+fn unquote(input: &str) -> &str {
+    if input.len() >= 2
+        && ((input.starts_with('"') && input.ends_with('"')) || (input.starts_with('\'') && input.ends_with('\'')))
+    {
+        &input[1..input.len() - 1]
+    } else {
+        input
     }
+}
 
-    pub struct MoveFile {
-        pub src: String,
-        pub dst: String,
+// Why a translated command can't be run as is, if it can't: the reply was
+// empty, or names a program that is neither a built-in nor on PATH
+fn translation_problem(command: &str) -> Option<String> {
+    let program = match command.split_whitespace().next() {
+        Some(program) => program,
+        None => return Some("the translation was empty".to_string()),
+    };
+    if registry().get(program).is_some() || utils::find_in_path(program).is_some() {
+        return None;
     }
+    Some(format!("the translation uses an unknown command '{}'", program))
+}
 
-    impl ShellCommand for MoveFile {
-        fn execute(&self) -> io::Result<()> {
-            fs::rename(&self.src, &self.dst)?;
-            Ok(())
-        }
+// Ask the LLM for the command that carries out `request` on this OS
+async fn translate_request(request: &str, config: &RustShellConfig) -> anyhow::Result<String> {
+    use anyhow::Context as _;
 
-        fn help(&self) -> String {
-            "Move a file or directory. Usage: move <source> <destination>".to_string()
-        }
+    let llm_config = config.to_llm_config().context("LLM configuration error")?;
+    let llm_request = translation_request(request, config);
+    if OPTIONS.get().is_some_and(|options| options.print_prompt) {
+        println!("{}", describe_llm_request(&llm_config, &llm_request));
     }
+    let client = LLMClient::new(llm_config).await.context("Failed to create LLM client")?;
+
+    let response = client.generate(&llm_request).await.context("LLM error")?;
+    Ok(response.content.trim().to_string())
+}
 
-    pub struct RemoveFile {
-        pub paths: Vec<String>,
+// The request sent to the LLM to translate `request`
+fn translation_request(request: &str, config: &RustShellConfig) -> LLMRequest {
+    let prompt_template = PromptTemplate::new();
+    LLMRequest {
+        prompt: prompt_template.build_prompt(request, &detect_os(), &detect_shell()),
+        max_tokens: config.llm.max_tokens,
+        temperature: config.llm.temperature,
+        context: Some(prompt_template.system_prompt.clone()),
     }
+}
 
-    impl ShellCommand for RemoveFile {
-        fn execute(&self) -> io::Result<()> {
-            // Remove multiple files
-            for path in &self.paths {
-                println!("Removing file: {}", path);
-                fs::remove_file(path)?;
-            }
-            Ok(())
-        }
+// The `--print-prompt` report: the provider, the request parameters and the
+// system and user prompts exactly as they are sent
+fn describe_llm_request(llm_config: &LLMConfig, request: &LLMRequest) -> String {
+    format!(
+        "--- LLM request ---\nProvider:    {}\nModel:       {}\nMax tokens:  {}\nTemperature: {}\n\
+         \n[system]\n{}\n\n[user]\n{}\n--- end of LLM request ---",
+        llm_config.provider,
+        llm_config.model,
+        request.max_tokens,
+        request.temperature,
+        request.context.as_deref().unwrap_or(""),
+        request.prompt
+    )
+}
 
-        fn help(&self) -> String {
-            "Remove one or more files. Usage: delete_file <file1> <file2> ...".to_string()
-        }
+// With `--print-prompt-only`, print the request `input` would be translated
+// with instead of sending it. True if it did, and nothing more should run.
+fn print_prompt_only(input: &str, config: &RustShellConfig) -> bool {
+    if !OPTIONS.get().is_some_and(|options| options.print_prompt_only) {
+        return false;
     }
-
-    pub struct RemoveDir {
-        pub path: String,
-        pub recursive: bool,
+    match config.to_llm_config() {
+        Ok(llm_config) => println!("{}", describe_llm_request(&llm_config, &translation_request(unquote(input), config))),
+        Err(e) => eprintln!("LLM configuration error: {}", e),
     }
+    true
+}
 
-    impl ShellCommand for RemoveDir {
-        fn execute(&self) -> io::Result<()> {
-            if self.recursive {
-                fs::remove_dir_all(&self.path)?;
-            } else {
-                fs::remove_dir(&self.path)?;
-            }
-            Ok(())
-        }
+// `repeat N ...` and `for x in ...: ...` read like English but are built-ins
+// whose inner command must run as typed, so they are never translated. Nor are
+// the `translate` commands, whose arguments are meant for the LLM.
+fn wants_translation(input: &str) -> bool {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    let exempt = match words.as_slice() {
+        ["repeat", count, _, ..] => count.parse::<usize>().is_ok(),
+        ["for", _, "in", ..] => input.contains(':'),
+        // Already an explicit request to the LLM
+        ["translate" | "translate-batch" | "ask", ..] => true,
+        _ => words.windows(2).any(|pair| pair == ["|", "ask"]),
+    };
+    !exempt && is_natural_language(input)
+}
 
-        fn help(&self) -> String {
-            "Remove a directory. Usage: rmdir [-r] <directory_name>".to_string()
+// The line with any `#` comment removed. As in sh, `#` only starts a comment
+// at the beginning of a word and outside quotes, so `page.html#intro` and
+// `"#1"` are left alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut word_start = true;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(open), _) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') if word_start => return line[..i].trim_end(),
+            _ => {}
         }
+        word_start = quote.is_none() && c.is_whitespace();
     }
+    line
+}
 
-    pub struct ChangeDir {
-        pub path: String,
-    }
+// The command a `!!` or `!prefix` line refers to, followed by any arguments
+// given after it: the last command, or the most recent one starting with
+// `prefix`. `history` runs newest first; earlier `!` lines in it are skipped,
+// since they were recorded as typed. Ok(None) if the line isn't a history
+// reference.
+fn expand_history<'a>(line: &str, history: impl IntoIterator<Item = &'a str>) -> Result<Option<String>, String> {
+    let (event, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let prefix = match event.strip_prefix('!') {
+        Some("") | None => return Ok(None),
+        Some("!") => "",
+        Some(prefix) => prefix,
+    };
 
-    impl ShellCommand for ChangeDir {
-        fn execute(&self) -> io::Result<()> {
-            env::set_current_dir(&self.path)?;
-            Ok(())
-        }
+    let found = history
+        .into_iter()
+        .find(|entry| !entry.starts_with('!') && entry.starts_with(prefix))
+        .ok_or_else(|| format!("{}: event not found", event))?;
+    let args = args.trim();
+    Ok(Some(if args.is_empty() { found.to_string() } else { format!("{} {}", found, args) }))
+}
+
+// For `\name args...`, the arguments with the escaping backslash removed
+fn unescape_command(args: &[String]) -> Option<Vec<String>> {
+    let name = args.first()?.strip_prefix('\\')?;
+    let mut unescaped = args.to_vec();
+    unescaped[0] = name.to_string();
+    Some(unescaped)
+}
 
-        fn help(&self) -> String {
-            "Change current directory. Usage: cd <directory_path>".to_string()
+fn parse_command(args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
+    if args.is_empty() {
+        return None;
+    }
+    
+    // `<command> | ask <question>` runs the command itself, aliases and all
+    if let Some(ask) = ask_pipeline(args, alias_manager) {
+        return Some(Box::new(ask));
+    }
+    
+    // Expand aliases if alias_manager is provided
+    let expanded_args = if let Some(manager) = alias_manager {
+        manager.expand_aliases(args)
+    } else {
+        unescape_command(args).unwrap_or_else(|| args.to_vec())
+    };
+    
+    if expanded_args.is_empty() {
+        return None;
+    }
+    
+    // Expand `{a,b}` and `{1..3}`, then `~` and `~user`, in every word, in
+    // the order a POSIX shell would. A quoted word keeps its braces, e.g. for
+    // `find '*.{rs,toml}'`; so do command-mode arguments, which the calling
+    // shell has already expanded unless they were quoted.
+    let expand_braces = |arg: &String| match alias_manager {
+        Some(_) if !arg.starts_with(['\'', '"']) => utils::expand_braces(arg),
+        _ => vec![arg.clone()],
+    };
+    let expanded_args: Vec<String> = expanded_args
+        .iter()
+        .flat_map(expand_braces)
+        .map(|arg| utils::expand_tilde(&arg))
+        .collect();
+
+    // The expansion of an alias such as `alias gl git log | head`, with any
+    // arguments given after it, is parsed again as the pipeline it stands for
+    if alias_is_pipeline(args, alias_manager) {
+        if let Some(ask) = commands::ask_pipeline(&expanded_args) {
+            return Some(Box::new(ask));
         }
+        let commands = expanded_args.split(|word| word == "|").map(<[String]>::to_vec).collect();
+        return Some(Box::new(commands::PipeCommand { commands }));
     }
+    
+    registry().build(&expanded_args, alias_manager)
+}
 
-    pub struct ListDir {
-        pub path: Option<String>,
+// `<command> | ask <question>` with any alias in the command expanded, as
+// the command is run without the alias manager
+fn ask_pipeline(args: &[String], alias_manager: Option<&AliasManager>) -> Option<commands::AskCommand> {
+    let mut ask = commands::ask_pipeline(args)?;
+    if let Some(manager) = alias_manager {
+        ask.command = manager.expand_aliases(&ask.command);
     }
+    Some(ask)
+}
 
-    impl ShellCommand for ListDir {
-        fn execute(&self) -> io::Result<()> {
-            let path = match &self.path {
-                Some(p) => p,
-                None => ".",
-            };
+// Whether the command `args` runs is an alias containing a `|`
+fn alias_is_pipeline(args: &[String], alias_manager: Option<&AliasManager>) -> bool {
+    alias_manager
+        .and_then(|manager| manager.get_alias(&args[0]))
+        .is_some_and(|command| command.split_whitespace().any(|word| word == "|"))
+}
 
-            // Use either native Rust or OS-specific commands based on complexity
-            if cfg!(windows) {
-                // On Windows, use dir command with formatting
-                // Use PowerShell to get better formatting and current directory resolution
-                let output = Command::new("powershell")
-                    .args(&["-Command", &format!("Get-ChildItem -Path \"{}\" | Format-Table -Property Mode, Name", path)])
-                    .output()?;
-                
-                println!("Contents of {}:", path);
-                print_output(output);
-            } else {
-                // On Unix-like systems, use ls command
-                let ls_arg = if cfg!(target_os = "macos") {
-                    "-la"
-                } else {
-                    "-la --color=auto"
-                };
-                
-                let output = Command::new("sh")
-                    .args(&["-c", &format!("ls {} \"{}\"", ls_arg, path)])
-                    .output()?;
-                
-                println!("Contents of {}:", path);
-                print_output(output);
-            }
-            
-            Ok(())
-        }
+// Global options given before the command, e.g. `rustshell --timeout 120 "..."`
+#[derive(Debug, Default, Clone)]
+struct CliOptions {
+    timeout: Option<u64>,
+    quiet: bool,
+    no_cache: bool,
+    script: Option<PathBuf>,
+    // Show each translation request before it is sent; `print_prompt_only`
+    // shows it instead of sending it
+    print_prompt: bool,
+    print_prompt_only: bool,
+}
 
-        fn help(&self) -> String {
-            "List directory contents. Usage: ls [directory_path]".to_string()
+impl CliOptions {
+    // Consume leading options and return them with the remaining arguments.
+    // Parsing stops at the first non-option so command flags pass through untouched.
+    fn parse(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut options = CliOptions::default();
+        let mut i = 0;
+        
+        while i < args.len() {
+            match args[i].as_str() {
+                "--timeout" => {
+                    let value = args.get(i + 1)
+                        .ok_or_else(|| "--timeout requires a number of seconds".to_string())?;
+                    let seconds = value.parse::<u64>()
+                        .ok()
+                        .filter(|s| *s > 0)
+                        .ok_or_else(|| format!("invalid --timeout value: {}", value))?;
+                    options.timeout = Some(seconds);
+                    i += 2;
+                }
+                "--quiet" | "-q" => {
+                    options.quiet = true;
+                    i += 1;
+                }
+                "--no-cache" => {
+                    options.no_cache = true;
+                    i += 1;
+                }
+                "--print-prompt" => {
+                    options.print_prompt = true;
+                    i += 1;
+                }
+                "--print-prompt-only" => {
+                    options.print_prompt_only = true;
+                    i += 1;
+                }
+                "--script" => {
+                    let path = args.get(i + 1)
+                        .ok_or_else(|| "--script requires a file".to_string())?;
+                    options.script = Some(PathBuf::from(utils::expand_tilde(path)));
+                    i += 2;
+                }
+                _ => break,
+            }
         }
+        
+        Ok((options, args[i..].to_vec()))
     }
-
-    // Execute OS command with arguments
-    pub struct ExecuteCommand {
-        pub command: String,
-        pub args: Vec<String>,
-    }
-
-    impl ShellCommand for ExecuteCommand {
-        fn execute(&self) -> io::Result<()> {
-            let output = if cfg!(windows) {
-                let cmd_args = format!("{} {}", self.command, self.args.join(" "));
-                Command::new("cmd")
-                    .args(&["/C", &cmd_args])
-                    .output()?
-            } else {
-                Command::new(&self.command)
-                    .args(&self.args)
-                    .output()?
-            };
-            
-            print_output(output);
-            Ok(())
+    
+    // Override configuration values for this invocation only
+    fn apply(&self, config: &mut RustShellConfig) {
+        if let Some(timeout) = self.timeout {
+            config.llm.timeout_seconds = timeout;
         }
-
-        fn help(&self) -> String {
-            format!("Execute command: {} {}", self.command, self.args.join(" "))
+        if self.quiet {
+            config.ui.quiet = true;
+        }
+        if self.no_cache {
+            config.llm.enable_cache = false;
         }
     }
+}
 
-    pub struct CurrentPath {}
+static OPTIONS: OnceLock<CliOptions> = OnceLock::new();
 
-    impl ShellCommand for CurrentPath {
-        fn execute(&self) -> io::Result<()> {
-            let current_dir = env::current_dir()?;
-            println!("Current directory: {}", current_dir.display());
-            Ok(())
-        }
+// The session's configuration, set by the driver when it starts and by `reload`
+static SESSION_CONFIG: RwLock<Option<RustShellConfig>> = RwLock::new(None);
 
-        fn help(&self) -> String {
-            "Print current working directory. Usage: pwd".to_string()
-        }
-    }
+fn set_session_config(config: &RustShellConfig) {
+    *SESSION_CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+}
 
-    // New command to show file contents
-    pub struct ShowFile {
-        pub path: String,
-    }
+// The configuration with this invocation's options applied, for commands such
+// as `translate` that read it themselves. Without a driver, as in tests, that
+// is the default configuration; nothing is read from or written to disk.
+fn current_config() -> RustShellConfig {
+    SESSION_CONFIG.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
 
-    impl ShellCommand for ShowFile {
-        fn execute(&self) -> io::Result<()> {
-            // Handle the file in a more robust way that works with non-UTF-8 content
-            if cfg!(windows) {
-                // On Windows, use PowerShell to display file content
-                let output = Command::new("powershell")
-                    .args(&["-Command", &format!("Get-Content -Path \"{}\"", self.path)])
-                    .output()?;
-                
-                println!("--- Contents of {} ---", self.path);
-                print_output(output);
+// Function to load .env files from multiple locations
+fn load_env_files() {
+    // Try to load .env files in order of preference:
+    // 1. Current directory (.env)
+    // 2. User's rustshell config directory (~/.rustshell/.env)
+    // 3. User's home directory (~/.env)
+    
+    let env_locations = vec![
+        std::env::current_dir().ok().map(|d| d.join(".env")),
+        dirs_next::home_dir().map(|d| d.join(".rustshell").join(".env")),
+        dirs_next::home_dir().map(|d| d.join(".env")),
+    ];
+    
+    for location in env_locations.into_iter().flatten() {
+        if location.exists() {
+            if let Err(e) = dotenv::from_path(&location) {
+                eprintln!("Warning: Failed to load .env from {}: {}", location.display(), e);
             } else {
-                // On Unix systems, use cat
-                let output = Command::new("sh")
-                    .args(&["-c", &format!("cat \"{}\"", self.path)])
-                    .output()?;
-                
-                println!("--- Contents of {} ---", self.path);
-                print_output(output);
+                // Successfully loaded, break to avoid overriding with lower priority files
+                break;
             }
-            
-            println!("--- End of file ---");
-            Ok(())
         }
+    }
+}
 
-        fn help(&self) -> String {
-            "Display the contents of a file. Usage: show <file_path>".to_string()
+// Describe how the alias set changed between two loads
+fn describe_alias_changes(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<String> {
+    let mut changes = Vec::new();
+    
+    for (name, command) in new {
+        match old.get(name) {
+            None => changes.push(format!("added alias '{}'", name)),
+            Some(previous) if previous != command => changes.push(format!("changed alias '{}'", name)),
+            _ => {}
         }
     }
-
-    // New command to find files
-    pub struct FindFiles {
-        pub pattern: String,
-        pub path: Option<String>,
+    for name in old.keys() {
+        if !new.contains_key(name) {
+            changes.push(format!("removed alias '{}'", name));
+        }
     }
+    
+    changes.sort();
+    changes
+}
 
-    impl ShellCommand for FindFiles {
-        fn execute(&self) -> io::Result<()> {
-            let root = match &self.path {
-                Some(p) => PathBuf::from(p),
-                None => env::current_dir()?,
-            };
-            
-            println!("Searching for files matching '{}' in {}...", 
-                     self.pattern, root.display());
-            
-            // Use native command for better performance and features
-            if cfg!(windows) {
-                // Windows - use PowerShell
-                let cmd = format!(
-                    "Get-ChildItem -Path \"{}\" -Recurse -File | Where-Object {{ $_.Name -like \"*{}*\" }} | Select-Object FullName",
-                    root.display(),
-                    self.pattern
-                );
-                
-                let output = Command::new("powershell")
-                    .args(&["-Command", &cmd])
-                    .output()?;
-                
-                print_output(output);
+// Re-read configuration and aliases from disk, replacing the session's copies
+fn reload_session(helper: &mut RustShellHelper, app_config: &mut RustShellConfig, options: &CliOptions) {
+    match get_config() {
+        Ok(mut new_config) => {
+            options.apply(&mut new_config);
+            let changed = app_config.changed_sections(&new_config);
+            if changed.is_empty() {
+                println!("Configuration unchanged.");
             } else {
-                // Unix - use find
-                let cmd = format!(
-                    "find \"{}\" -type f -name \"*{}*\"",
-                    root.display(),
-                    self.pattern
-                );
-                
-                let output = Command::new("sh")
-                    .args(&["-c", &cmd])
-                    .output()?;
-                
-                print_output(output);
+                println!("Configuration reloaded (changed: {}).", changed.join(", "));
             }
-            
-            Ok(())
-        }
-
-        fn help(&self) -> String {
-            "Find files matching a pattern. Usage: find <pattern> [directory]".to_string()
+            commands::context::set_quiet(new_config.ui.quiet);
+            commands::context::set_verbose(new_config.ui.verbose_mode);
+            set_session_config(&new_config);
+            *app_config = new_config.clone();
+            helper.config = new_config;
         }
+        Err(e) => eprintln!("Error reloading configuration: {}", e),
     }
-
-    // New command to compress files into a zip archive
-    pub struct CompressFiles {
-        pub source: String,
-        pub destination: String,
-    }
-
-    impl ShellCommand for CompressFiles {
-        fn execute(&self) -> io::Result<()> {
-            println!("Compressing {} to {}...", self.source, self.destination);
-            
-            if cfg!(windows) {
-                // Windows compression using PowerShell
-                let cmd = format!(
-                    "Compress-Archive -Path \"{}\" -DestinationPath \"{}\" -Force",
-                    self.source,
-                    self.destination
-                );
-                
-                let output = Command::new("powershell")
-                    .args(&["-Command", &cmd])
-                    .output()?;
-                
-                print_output(output);
+    
+    match AliasManager::new() {
+        Ok(new_aliases) => {
+            let changes = describe_alias_changes(&helper.alias_manager.aliases, &new_aliases.aliases);
+            if changes.is_empty() {
+                println!("Aliases unchanged.");
             } else {
-                // Unix compression using zip
-                let output = Command::new("sh")
-                    .args(&["-c", &format!("zip -r \"{}\" \"{}\"", 
-                                         self.destination, self.source)])
-                    .output()?;
-                
-                print_output(output);
+                for change in changes {
+                    println!("  {}", change);
+                }
             }
-            
-            println!("Compression complete.");
-            Ok(())
-        }
-
-        fn help(&self) -> String {
-            "Compress files into a zip archive. Usage: compress <source> <destination>".to_string()
+            helper.alias_manager = new_aliases;
+            helper.update_commands();
         }
+        Err(e) => eprintln!("Error reloading aliases: {}", e),
     }
+}
 
-    // Add new commands for aliases
-    pub struct AliasCommand {
-        pub name: Option<String>,
-        pub command: Option<String>,
+// Ask before running a translated command when the config requires it, after
+// spelling out what it will touch. Shared by interactive and command mode so
+// both prompt the same way, except that `safety.confirm_timeout_seconds` only
+// applies in command mode: the reader left behind by a timed-out prompt would
+// swallow the next interactive line.
+async fn confirm_translation(command: &str, config: &RustShellConfig) -> bool {
+    if !config.requires_confirmation(command) {
+        return true;
     }
-
-    impl super::ShellCommand for AliasCommand {
-        fn execute(&self) -> std::io::Result<()> {
-            let alias_manager = super::AliasManager::new()?;
-            
-            match (&self.name, &self.command) {
-                (Some(name), Some(command)) => {
-                    // Add or update alias
-                    let mut manager = alias_manager;
-                    manager.add_alias(name.clone(), command.clone())?;
-                    println!("Alias '{}' created for '{}'", name, command);
-                }
-                (None, None) => {
-                    // List all aliases
-                    alias_manager.list_aliases();
-                }
-                _ => {
-                    println!("Error: alias requires both name and command or no arguments");
-                }
-            }
-            
-            Ok(())
-        }
-
-        fn help(&self) -> String {
-            "Create or list aliases. Usage: alias [name='command']".to_string()
-        }
+    for effect in commands::effects::describe(command) {
+        println!("  - {}", effect);
     }
 
-    pub struct UnaliasCommand {
-        pub name: String,
-    }
+    let question = format!("Execute '{}' ?", command);
+    let answer = match config.confirm_timeout().filter(|_| !commands::context::is_interactive()) {
+        Some(timeout) => utils::confirm_within(&question, config.confirm_default_yes(), timeout).await,
+        None => utils::confirm(&question, config.confirm_default_yes()),
+    };
+    answer.unwrap_or(false)
+}
 
-    impl super::ShellCommand for UnaliasCommand {
-        fn execute(&self) -> std::io::Result<()> {
-            let mut alias_manager = super::AliasManager::new()?;
-            
-            match alias_manager.remove_alias(&self.name) {
-                Ok(true) => println!("Alias '{}' removed", self.name),
-                Ok(false) => println!("No such alias: {}", self.name),
-                Err(e) => return Err(e),
-            }
-            
-            Ok(())
-        }
+// Command history shared by every interactive session, whatever the working directory
+fn history_file() -> PathBuf {
+    let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".rustshell").join("history")
+}
 
-        fn help(&self) -> String {
-            "Remove an alias. Usage: unalias <name>".to_string()
-        }
-    }
-    
-    // Command for pipeline execution
-    pub struct PipeCommand {
-        pub commands: Vec<Vec<String>>,
-    }
+// Commands run at the start of every interactive session, like ~/.bashrc
+fn rc_file() -> PathBuf {
+    let home_dir = dirs_next::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    home_dir.join(".rustshell").join("rc")
+}
 
-    impl super::ShellCommand for PipeCommand {
-        fn execute(&self) -> std::io::Result<()> {
-            if self.commands.len() < 2 {
-                println!("Error: pipe requires at least two commands");
-                return Ok(());
-            }
-            
-            // Setup for piping
-            let mut previous_stdout = None;
-            
-            for (i, cmd_args) in self.commands.iter().enumerate() {
-                if cmd_args.is_empty() {
-                    println!("Error: empty command in pipeline");
-                    return Ok(());
-                }
-                
-                let is_last = i == self.commands.len() - 1;
-                
-                // Create the command
-                let mut cmd = if cfg!(windows) {
-                    let cmd_str = cmd_args.join(" ");
-                    let mut command = std::process::Command::new("cmd");
-                    command.args(&["/C", &cmd_str]);
-                    command
-                } else {
-                    let mut command = std::process::Command::new(&cmd_args[0]);
-                    if cmd_args.len() > 1 {
-                        command.args(&cmd_args[1..]);
-                    }
-                    command
-                };
-                
-                // Setup stdin from previous command's stdout if available
-                if let Some(stdout) = previous_stdout {
-                    cmd.stdin(stdout);
-                }
-                
-                // Setup stdout for piping to next command or capturing output
-                if !is_last {
-                    cmd.stdout(Stdio::piped());
-                }
-                
-                // Execute the command
-                let mut child = cmd.spawn()?;
-                
-                // Get stdout for the next command in the pipeline
-                previous_stdout = if !is_last {
-                    child.stdout.take()
-                } else {
-                    None
-                };
-                
-                // If it's the last command, wait for it to finish
-                if is_last {
-                    let status = child.wait()?;
-                    if !status.success() {
-                        println!("Pipeline command failed with exit code: {:?}", status.code());
-                    }
-                }
-            }
-            
-            Ok(())
+// Run each line of a script (the startup script, or one given with --script)
+// through the normal dispatch. Lines are taken literally, never translated by
+// the LLM. A failing line is reported and skipped so a broken startup script
+// can't keep the shell from starting. Returns the number of lines that failed.
+fn run_script(path: &Path, alias_manager: &AliasManager) -> usize {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return 0,
+        Err(e) => {
+            eprintln!("Error reading {}: {}", path.display(), e);
+            return 1;
         }
+    };
 
-        fn help(&self) -> String {
-            "Execute commands in a pipeline. Usage: pipe 'cmd1' 'cmd2' ...".to_string()
+    let mut failures = 0;
+    for (number, line) in content.lines().enumerate() {
+        let line = strip_comment(line.trim());
+        if line.is_empty() {
+            continue;
         }
-    }
 
-    // Helper function to print command output
-    fn print_output(output: Output) {
-        if !output.stdout.is_empty() {
-            print!("{}", String::from_utf8_lossy(&output.stdout));
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        let result = match parse_command(&args, Some(alias_manager)) {
+            Some(command) => command.execute(),
+            None => CommandResult {
+                exit_code: 1,
+                error: Some(io::Error::new(io::ErrorKind::InvalidInput, format!("could not run '{}'", line))),
+                ..Default::default()
+            },
+        };
+        print_output(&result);
+        if let Some(e) = &result.error {
+            eprintln!("{}:{}: {}", path.display(), number + 1, e);
         }
-        
-        if !output.stderr.is_empty() {
-            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+        if !result.success() {
+            failures += 1;
         }
     }
+    failures
 }
 
-// Async function to process natural language commands
-async fn process_natural_language(input: &str, config: &RustShellConfig) -> Option<String> {
-    if !config.features.enable_llm || config.features.offline_mode {
-        return None;
+// --script: run a file of commands (e.g. one written by `save-session`) and
+// exit non-zero if any of them failed
+fn run_script_file(path: &Path, options: &CliOptions) -> i32 {
+    if !path.is_file() {
+        eprintln!("Error: no such script: {}", path.display());
+        return 1;
     }
 
-    // Check if this looks like natural language
-    if !is_natural_language(input) {
-        return None;
-    }
+    let mut config = get_config().unwrap_or_default();
+    options.apply(&mut config);
+    set_session_config(&config);
+    commands::context::set_output(io::stdout().is_terminal());
+    commands::context::set_quiet(config.ui.quiet);
+    commands::context::set_verbose(config.ui.verbose_mode);
 
-    // Strip quotes if present for processing
-    let clean_input = if (input.starts_with('"') && input.ends_with('"')) || 
-                         (input.starts_with('\'') && input.ends_with('\'')) {
-        &input[1..input.len()-1]
-    } else {
-        input
+    let alias_manager = match AliasManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            eprintln!("Error loading alias manager: {}", e);
+            return 1;
+        }
     };
+    if run_script(path, &alias_manager) == 0 { 0 } else { 1 }
+}
 
-    match config.to_llm_config() {
-        Ok(llm_config) => {
-            match LLMClient::new(llm_config).await {
-                Ok(client) => {
-                    let prompt_template = PromptTemplate::new();
-                    let os = detect_os();
-                    let prompt = prompt_template.build_prompt(clean_input, &os);
-                    
-                    let request = LLMRequest {
-                        prompt,
-                        max_tokens: config.llm.max_tokens,
-                        temperature: config.llm.temperature,
-                        context: Some(prompt_template.system_prompt.clone()),
-                    };
+// Commands about the session itself, left out of `save-session` scripts
+const SESSION_META_COMMANDS: &[&str] = &["help", "showall", "nl-history", "save-session", "jobs", "fg"];
+
+// Usage counts for the summary printed on exit with `ui.session_summary`, and
+// the command lines `save-session` writes out
+struct SessionStats {
+    started: Instant,
+    tokens_at_start: u64,
+    commands: usize,
+    translations: usize,
+    command_counts: HashMap<String, usize>,
+    executed: Vec<String>,
+}
 
-                    match client.generate(&request).await {
-                        Ok(response) => {
-                            let command = response.content.trim().to_string();
-                            
-                            // Basic safety check
-                            if config.is_dangerous_command(&command) {
-                                println!("⚠️  Warning: This command appears to be potentially dangerous: {}", command);
-                                if config.safety.block_destructive {
-                                    println!("❌ Command blocked by safety settings");
-                                    return None;
-                                }
-                            }
-                            
-                            if config.ui.verbose_mode {
-                                println!("🤖 Translated '{}' to: {}", input, command);
-                            }
-                            
-                            Some(command)
-                        }
-                        Err(e) => {
-                            if config.ui.verbose_mode {
-                                eprintln!("LLM error: {}", e);
-                            }
-                            None
-                        }
-                    }
-                }
-                Err(e) => {
-                    if config.ui.verbose_mode {
-                        eprintln!("Failed to create LLM client: {}", e);
-                    }
-                    None
-                }
-            }
+impl SessionStats {
+    fn new() -> Self {
+        SessionStats {
+            started: Instant::now(),
+            tokens_at_start: LLMClient::tokens_used(),
+            commands: 0,
+            translations: 0,
+            command_counts: HashMap::new(),
+            executed: Vec::new(),
         }
-        Err(e) => {
-            if config.ui.verbose_mode {
-                eprintln!("LLM configuration error: {}", e);
+    }
+
+    // Record a command line as run, i.e. after any LLM translation
+    fn record_command(&mut self, line: &str) {
+        if let Some(name) = self.count(line) {
+            if !SESSION_META_COMMANDS.contains(&name) {
+                self.executed.push(line.trim().to_string());
             }
-            None
         }
     }
-}
 
-fn parse_command(args: &[String], alias_manager: Option<&AliasManager>) -> Option<Box<dyn ShellCommand>> {
-    if args.is_empty() {
-        return None;
+    // Record a command started in the background with `&`. `--script` runs
+    // every line in the foreground, so the saved session keeps it as a comment.
+    fn record_background_job(&mut self, line: &str) {
+        if self.count(line).is_some() {
+            self.executed.push(format!("# {} &  (background job, not replayed)", line.trim()));
+        }
     }
-    
-    // Expand aliases if alias_manager is provided
-    let expanded_args = if let Some(manager) = alias_manager {
-        manager.expand_aliases(args)
-    } else {
-        args.to_vec()
-    };
-    
-    if expanded_args.is_empty() {
-        return None;
+
+    // Count a command line towards the summary, returning the command's name
+    fn count<'a>(&mut self, line: &'a str) -> Option<&'a str> {
+        let name = line.split_whitespace().next()?;
+        self.commands += 1;
+        *self.command_counts.entry(name.to_string()).or_default() += 1;
+        Some(name)
     }
-    
-    match expanded_args[0].as_str() {
-        "showall" => {
-            // Handle the special 'showall' command to display all available commands
-            println!("Available commands:");
-            let commands = [
-                "make_dir/mkdir", "create_file/touch", "copy", "move", 
-                "delete_file/rm", "delete_dir/rmdir", "change_dir/cd", 
-                "list/ls", "where_am_i/pwd", "run/exec", "show/cat", 
-                "find", "compress/zip", "help", "exit/quit", 
-                "interactive", "alias", "unalias", "pipe", "showall"
-            ];
-            
-            // Display commands in columns
-            let mut count = 0;
-            for cmd in commands {
-                print!("{:<20}", cmd);
-                count += 1;
-                if count % 4 == 0 {
-                    println!();
-                }
-            }
-            if count % 4 != 0 {
-                println!();
-            }
-            
-            // Also display aliases if available
-            if let Some(manager) = alias_manager {
-                if !manager.aliases.is_empty() {
-                    println!("\nDefined aliases:");
-                    for (name, command) in &manager.aliases {
-                        println!("  {} = '{}'", name, command);
-                    }
-                }
-            }
-            
-            None
-        },
-        "make_dir" | "mkdir" => {
-            if expanded_args.len() < 2 {
-                println!("Error: make_dir requires a directory name");
-                return None;
-            }
-            
-            let mut parents = false;
-            let mut path_index = 1;
-            
-            if expanded_args.len() > 2 && expanded_args[1] == "-p" {
-                parents = true;
-                path_index = 2;
-            }
-            
-            Some(Box::new(commands::MakeDir {
-                path: expanded_args[path_index].clone(),
-                parents,
-            }))
-        },
-        "create_file" | "touch" => {
-            if expanded_args.len() < 2 {
-                println!("Error: create_file requires at least one file name");
-                return None;
-            }
-            
-            // Skip the command name and collect all file paths
-            let paths = expanded_args[1..].to_vec();
-            
-            Some(Box::new(commands::MakeFile { paths }))
-        },
-        "copy" => {
-            if expanded_args.len() < 3 {
-                println!("Error: copy requires source and destination paths");
-                return None;
-            }
-            
-            Some(Box::new(commands::CopyFile {
-                src: expanded_args[1].clone(),
-                dst: expanded_args[2].clone(),
-            }))
-        },
-        "move" => {
-            if expanded_args.len() < 3 {
-                println!("Error: move requires source and destination paths");
-                return None;
-            }
-            
-            Some(Box::new(commands::MoveFile {
-                src: expanded_args[1].clone(),
-                dst: expanded_args[2].clone(),
-            }))
-        },
-        "delete_file" | "rm" => {
-            if expanded_args.len() < 2 {
-                println!("Error: delete_file requires at least one file name");
-                return None;
-            }
-            
-            // Skip the command name and collect all file paths
-            let paths = expanded_args[1..].to_vec();
-            
-            Some(Box::new(commands::RemoveFile { paths }))
-        },
-        "delete_dir" | "rmdir" => {
-            if expanded_args.len() < 2 {
-                println!("Error: delete_dir requires a directory name");
-                return None;
-            }
-            
-            let mut recursive = false;
-            let mut path_index = 1;
-            
-            if expanded_args.len() > 2 && expanded_args[1] == "-r" {
-                recursive = true;
-                path_index = 2;
-            }
-            
-            Some(Box::new(commands::RemoveDir {
-                path: expanded_args[path_index].clone(),
-                recursive,
-            }))
-        },
-        "change_dir" | "cd" => {
-            if expanded_args.len() < 2 {
-                println!("Error: change_dir requires a directory path");
-                return None;
-            }
-            
-            Some(Box::new(commands::ChangeDir {
-                path: expanded_args[1].clone(),
-            }))
-        },
-        "list" | "ls" => {
-            let path = if expanded_args.len() > 1 {
-                Some(expanded_args[1].clone())
-            } else {
-                None
-            };
-            
-            Some(Box::new(commands::ListDir { path }))
-        },
-        "where_am_i" | "pwd" => {
-            Some(Box::new(commands::CurrentPath {}))
-        },
-        "run" | "exec" => {
-            if expanded_args.len() < 2 {
-                println!("Error: run requires a command to execute");
-                return None;
-            }
-            
-            let command = expanded_args[1].clone();
-            let command_args: Vec<String> = expanded_args[2..].to_vec();
-            
-            Some(Box::new(commands::ExecuteCommand {
-                command,
-                args: command_args,
-            }))
-        },
-        "show" | "cat" => {
-            if expanded_args.len() < 2 {
-                println!("Error: show requires a file path");
-                return None;
-            }
-            
-            Some(Box::new(commands::ShowFile {
-                path: expanded_args[1].clone(),
-            }))
-        },
-        "find" => {
-            if expanded_args.len() < 2 {
-                println!("Error: find requires a pattern to search for");
-                return None;
-            }
-            
-            let pattern = expanded_args[1].clone();
-            let path = if expanded_args.len() > 2 {
-                Some(expanded_args[2].clone())
-            } else {
-                None
-            };
-            
-            Some(Box::new(commands::FindFiles {
-                pattern,
-                path,
-            }))
-        },
-        "compress" | "zip" => {
-            if expanded_args.len() < 3 {
-                println!("Error: compress requires source and destination paths");
-                return None;
-            }
-            
-            Some(Box::new(commands::CompressFiles {
-                source: expanded_args[1].clone(),
-                destination: expanded_args[2].clone(),
-            }))
-        },
-        "alias" => {
-            if expanded_args.len() == 1 {
-                // List aliases
-                Some(Box::new(commands::AliasCommand {
-                    name: None,
-                    command: None,
-                }))
-            } else if expanded_args.len() >= 3 {
-                // Create alias: alias name command args...
-                let name = expanded_args[1].clone();
-                let command = expanded_args[2..].join(" ");
-                
-                Some(Box::new(commands::AliasCommand {
-                    name: Some(name),
-                    command: Some(command),
-                }))
-            } else {
-                println!("Error: invalid alias syntax. Use: alias <name> <command>");
-                None
-            }
-        },
-        "unalias" => {
-            if expanded_args.len() < 2 {
-                println!("Error: unalias requires an alias name");
-                return None;
-            }
-            
-            Some(Box::new(commands::UnaliasCommand {
-                name: expanded_args[1].clone(),
-            }))
-        },
-        "pipe" => {
-            if expanded_args.len() < 3 {
-                println!("Error: pipe requires at least two commands");
-                return None;
-            }
-            
-            // Parse pipe commands - each argument becomes a separate command in the pipeline
-            let commands: Vec<Vec<String>> = expanded_args[1..].iter()
-                .map(|cmd_str| {
-                    cmd_str.split_whitespace()
-                        .map(String::from)
-                        .collect()
-                })
-                .collect();
-            
-            Some(Box::new(commands::PipeCommand { commands }))
-        },
-        "help" => {
-            print_help();
-            None
-        },
-        _ => {
-            // If it's not a known rustshell command, try to execute it as a system command
-            let command = expanded_args[0].clone();
-            let command_args: Vec<String> = expanded_args[1..].to_vec();
-            
-            Some(Box::new(commands::ExecuteCommand {
-                command,
-                args: command_args,
-            }))
+
+    // The session's commands in the format `--script` replays
+    fn script(&self, saved_at: &str) -> String {
+        let mut script = format!("# RustShell session saved {}\n# Replay with: rustshell --script <file>\n", saved_at);
+        for line in &self.executed {
+            script.push_str(line);
+            script.push('\n');
         }
+        script
     }
-}
 
-fn print_help() {
-    let header = "Cross-Platform Shell - Available Commands:";
-    let commands = [
-        "  make_dir [-p] <directory>    Create a directory",
-        "  create_file <file>           Create a file",
-        "  copy <src> <dst>             Copy a file",
-        "  move <src> <dst>             Move a file or directory",
-        "  delete_file <file>           Delete a file",
-        "  delete_dir [-r] <directory>  Delete a directory",
-        "  change_dir <directory>       Change current directory",
-        "  list [directory]             List directory contents",
-        "  where_am_i                   Show current directory",
-        "  run <cmd> [args...]          Run a system command",
-        "  show <file>                  Display contents of a file",
-        "  find <pattern> [dir]         Find files matching a pattern",
-        "  compress <src> <dst>         Create a zip archive",
-        "  alias [name command]         Create or list aliases",
-        "  unalias <name>               Remove an alias",
-        "  pipe 'cmd1' 'cmd2' ...       Connect commands with pipes",
-        "  showall                      Display all available commands",
-        "  help                         Show this help message",
-    ];
-    
-    // Print each part separately to ensure everything is displayed
-    println!("{}", header);
-    for cmd in commands {
-        println!("{}", cmd);
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let saved_at = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
+        utils::atomic_write(path, self.script(&saved_at).as_bytes())
     }
-    
-    println!();
-    println!("Note: Traditional shell commands (mkdir, ls, etc.) also work.");
-    println!();
-    
-    // Print OS-specific information
-    let os_info = if cfg!(windows) {
-        "Windows"
-    } else if cfg!(target_os = "macos") {
-        "macOS"
-    } else if cfg!(target_os = "linux") {
-        "Linux"
-    } else {
-        "Unknown OS"
-    };
-    
-    println!("Current OS: {}", os_info);
-}
 
-// Function to load .env files from multiple locations
-fn load_env_files() {
-    // Try to load .env files in order of preference:
-    // 1. Current directory (.env)
-    // 2. User's rustshell config directory (~/.rustshell/.env)
-    // 3. User's home directory (~/.env)
-    
-    let env_locations = vec![
-        std::env::current_dir().ok().map(|d| d.join(".env")),
-        dirs_next::home_dir().map(|d| d.join(".rustshell").join(".env")),
-        dirs_next::home_dir().map(|d| d.join(".env")),
-    ];
-    
-    for location in env_locations.into_iter().flatten() {
-        if location.exists() {
-            if let Err(e) = dotenv::from_path(&location) {
-                eprintln!("Warning: Failed to load .env from {}: {}", location.display(), e);
-            } else {
-                // Successfully loaded, break to avoid overriding with lower priority files
-                break;
-            }
+    fn record_translation(&mut self) {
+        self.translations += 1;
+    }
+
+    fn format_duration(duration: Duration) -> String {
+        let secs = duration.as_secs();
+        match (secs / 3600, secs / 60 % 60, secs % 60) {
+            (0, 0, s) => format!("{}s", s),
+            (0, m, s) => format!("{}m {:02}s", m, s),
+            (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
         }
     }
+
+    fn summary(&self, tokens_used: u64, elapsed: Duration) -> Vec<String> {
+        // Ties go to the alphabetically first command so the summary is stable
+        let most_used = self
+            .command_counts
+            .iter()
+            .max_by(|(a_name, a_count), (b_name, b_count)| a_count.cmp(b_count).then(b_name.cmp(a_name)));
+
+        let mut lines = vec![
+            format!("Commands run:      {}", self.commands),
+            format!("LLM translations:  {}", self.translations),
+            format!("Tokens used:       {}", tokens_used.saturating_sub(self.tokens_at_start)),
+            format!("Session time:      {}", Self::format_duration(elapsed)),
+        ];
+        if let Some((name, count)) = most_used {
+            lines.push(format!("Most used command: {} ({}x)", name, count));
+        }
+        lines
+    }
 }
 
-// Function to run in interactive mode
-async fn run_interactive_mode() -> io::Result<()> {
+async fn run_interactive_mode(options: &CliOptions) -> io::Result<()> {
     // Create config with rustyline 11.0.0 compatible settings
     let config = Config::builder()
         .history_ignore_space(true)
@@ -1263,22 +1275,34 @@ async fn run_interactive_mode() -> io::Result<()> {
         .build();
 
     // Load configuration
-    let app_config = match get_config() {
+    let mut app_config = match get_config() {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Error loading configuration: {}", e);
             RustShellConfig::default()
         }
     };
+    options.apply(&mut app_config);
+    set_session_config(&app_config);
+    commands::context::set_interactive(true);
+    commands::context::set_output(io::stdout().is_terminal());
+    commands::context::set_quiet(app_config.ui.quiet);
+    commands::context::set_verbose(app_config.ui.verbose_mode);
 
     // Create editor and load alias manager
-    let alias_manager = match AliasManager::new() {
+    let mut alias_manager = match AliasManager::new() {
         Ok(am) => am,
         Err(e) => {
             eprintln!("Error loading alias manager: {}", e);
             return Err(e);
         }
     };
+
+    // The startup script may define aliases, so pick those up afterwards
+    run_script(&rc_file(), &alias_manager);
+    if let Err(e) = alias_manager.load_aliases() {
+        eprintln!("Error loading alias manager: {}", e);
+    }
     
     let helper = RustShellHelper::new(alias_manager, app_config.clone());
     
@@ -1287,7 +1311,7 @@ async fn run_interactive_mode() -> io::Result<()> {
         Ok(editor) => editor,
         Err(e) => {
             eprintln!("Error creating editor: {:?}", e);
-            return Err(io::Error::new(io::ErrorKind::Other, "Failed to create editor"));
+            return Err(io::Error::other("Failed to create editor"));
         }
     };
     
@@ -1298,10 +1322,18 @@ async fn run_interactive_mode() -> io::Result<()> {
     rl.bind_sequence(KeyEvent(KeyCode::Tab, Modifiers::NONE), rustyline::Cmd::Complete);
     // Shift+Tab cycles backward 
     rl.bind_sequence(KeyEvent(KeyCode::Tab, Modifiers::SHIFT), rustyline::Cmd::CompleteBackward);
+    // Ctrl-R / Ctrl-S search history incrementally, previewing the match as you type
+    rl.bind_sequence(KeyEvent::ctrl('R'), rustyline::Cmd::ReverseSearchHistory);
+    rl.bind_sequence(KeyEvent::ctrl('S'), rustyline::Cmd::ForwardSearchHistory);
+    // Ctrl-F picks a path with a fuzzy finder
+    rl.bind_sequence(
+        KeyEvent::ctrl('F'),
+        rustyline::EventHandler::Conditional(Box::new(FilePicker)),
+    );
     
-    // Try to load history
-    let history_path = Path::new(".rustshell_history");
-    if rl.load_history(history_path).is_err() {
+    // Try to load history, falling back to the per-directory file older versions wrote
+    let history_path = history_file();
+    if rl.load_history(&history_path).is_err() && rl.load_history(Path::new(".rustshell_history")).is_err() {
         println!("No previous history.");
     }
     
@@ -1320,18 +1352,51 @@ async fn run_interactive_mode() -> io::Result<()> {
     println!("Type 'help' for a list of commands or 'exit' to quit.");
     println!("Use Tab for command completion. Type 'showall' to display all available commands.");
     println!("Tab cycles forward through suggestions, Shift+Tab cycles backward.");
+    println!("Ctrl-R searches command history. Ctrl-F picks a file path.");
     
+    let mut stats = SessionStats::new();
+    let mut jobs = commands::jobs::Jobs::default();
+
+    // Ctrl-C at the prompt is handled by rustyline. While a command runs it
+    // stops that command instead of the shell: a child process still gets the
+    // signal and exits, and built-ins watch for it.
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            commands::context::set_interrupted(true);
+        }
+    });
+
     // Interactive loop
     loop {
+        for notice in jobs.finished() {
+            println!("{}", notice);
+        }
         let current_dir = env::current_dir()?;
-        let prompt = format!("{}> ", current_dir.display());
+        let prompt = prompt_for(&current_dir, app_config.ui.multiline_prompt);
+        let input_line = prompt.rsplit('\n').next().unwrap_or_default();
+        PROMPT_WIDTH.store(input_line.chars().count(), Ordering::Relaxed);
         
         match rl.readline(&prompt) {
             Ok(line) => {
-                // Skip empty lines
-                if line.trim().is_empty() {
+                // Skip empty lines and comments
+                let mut line = strip_comment(&line).trim().to_string();
+                if line.is_empty() {
                     continue;
                 }
+
+                // `!!` and `!prefix` rerun earlier commands, as in bash
+                match expand_history(&line, rl.history().iter().rev().map(String::as_str)) {
+                    Ok(Some(expanded)) => {
+                        println!("{}", expanded);
+                        let _ = rl.add_history_entry(expanded.as_str());
+                        line = expanded;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                }
                 
                 // Handle exit commands
                 if line == "exit" || line == "quit" {
@@ -1339,19 +1404,93 @@ async fn run_interactive_mode() -> io::Result<()> {
                     break;
                 }
                 
+                if line.trim() == "reload" {
+                    if let Some(helper) = rl.helper_mut() {
+                        reload_session(helper, &mut app_config, options);
+                    }
+                    continue;
+                }
+
+                let words: Vec<&str> = line.split_whitespace().collect();
+                if words[0] == "save-session" {
+                    match words.get(1) {
+                        Some(file) => {
+                            let path = PathBuf::from(utils::expand_tilde(file));
+                            match stats.save(&path) {
+                                Ok(()) => println!("Saved {} commands to {}", stats.executed.len(), path.display()),
+                                Err(e) => eprintln!("Error saving session to {}: {}", path.display(), e),
+                            }
+                        }
+                        None => println!("Error: save-session requires a file name"),
+                    }
+                    continue;
+                }
+
+                // Job control lives here because the jobs belong to this session
+                if words[0] == "jobs" {
+                    for line in jobs.list() {
+                        println!("{}", line);
+                    }
+                    continue;
+                }
+                if words[0] == "fg" {
+                    let id = match words.get(1).map(|id| id.trim_start_matches('%').parse::<usize>()) {
+                        Some(Ok(id)) => Some(id),
+                        Some(Err(_)) => {
+                            println!("Error: fg takes a job number, e.g. fg 1");
+                            continue;
+                        }
+                        None => None,
+                    };
+                    match jobs.wait(id) {
+                        Ok(status) => println!("{}", status),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    continue;
+                }
+                // A background job runs as typed, with aliases but never translated
+                if let Some(command) = commands::jobs::background_command(&line) {
+                    let args: Vec<String> = command.split_whitespace().map(String::from).collect();
+                    let Some(helper) = rl.helper() else {
+                        continue;
+                    };
+                    let Some(parsed) = parse_command(&args, Some(&helper.alias_manager)) else {
+                        continue;
+                    };
+                    let expanded = helper.alias_manager.expand_aliases(&args);
+                    match jobs.spawn(command, expanded, parsed, app_config.ui.colored_output) {
+                        Ok(started) => {
+                            stats.record_background_job(command);
+                            println!("{}", started);
+                        }
+                        Err(e) => eprintln!("Error starting background job: {}", e),
+                    }
+                    continue;
+                }
+                
+                commands::context::set_interrupted(false);
+
                 // Check for natural language first
-                let processed_line = if is_natural_language(&line) {
-                    if let Some(translated) = process_natural_language(&line, &app_config).await {
+                let processed_line = if wants_translation(&line) {
+                    if print_prompt_only(&line, &app_config) {
+                        continue;
+                    }
+                    let translation = tokio::select! {
+                        translation = process_natural_language(&line, &app_config) => translation,
+                        _ = tokio::signal::ctrl_c() => {
+                            println!("Translation cancelled.");
+                            continue;
+                        }
+                    };
+                    if let Some(translated) = translation {
+                        stats.record_translation();
+                        if app_config.features.enable_history {
+                            commands::nlhistory::record(&line, &translated);
+                        }
                         // Show confirmation if required
-                        if app_config.requires_confirmation(&translated) {
-                            print!("Execute '{}' ? (y/N): ", translated);
-                            io::stdout().flush().unwrap();
-                            let mut confirmation = String::new();
-                            io::stdin().read_line(&mut confirmation).unwrap();
-                            if !confirmation.trim().to_lowercase().starts_with('y') {
-                                println!("Command cancelled.");
-                                continue;
-                            }
+                        if !confirm_translation(&translated, &app_config).await {
+                            println!("Command cancelled.");
+                            continue;
                         }
                         translated
                     } else if app_config.features.fallback_to_traditional {
@@ -1369,12 +1508,16 @@ async fn run_interactive_mode() -> io::Result<()> {
                     .split_whitespace()
                     .map(String::from)
                     .collect();
+                stats.record_command(&processed_line);
                 
                 // Get alias manager from helper to handle aliases
                 if let Some(helper) = rl.helper_mut() {
                     if let Some(command) = parse_command(&args, Some(&helper.alias_manager)) {
-                        if let Err(e) = command.execute() {
-                            eprintln!("Error executing command: {}", e);
+                        let result = command.execute();
+                        print_output(&result);
+                        if let Some(e) = &result.error {
+                            let expanded = helper.alias_manager.expand_aliases(&args);
+                            commands::report::report(&expanded, e, app_config.ui.colored_output);
                         }
                     }
                     
@@ -1382,10 +1525,8 @@ async fn run_interactive_mode() -> io::Result<()> {
                     helper.update_commands();
                 }
             }
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                break;
-            }
+            // Ctrl-C discards the current line, as in other shells; Ctrl-D exits
+            Err(ReadlineError::Interrupted) => continue,
             Err(ReadlineError::Eof) => {
                 println!("CTRL-D");
                 break;
@@ -1397,8 +1538,30 @@ async fn run_interactive_mode() -> io::Result<()> {
         }
     }
     
+    if jobs.running() > 0 {
+        println!("{} background job(s) still running; they will finish on their own", jobs.running());
+    }
+
+    if app_config.features.collect_stats {
+        commands::usage::record(
+            &stats.command_counts,
+            stats.translations,
+            LLMClient::tokens_used().saturating_sub(stats.tokens_at_start),
+        );
+    }
+
+    if app_config.ui.session_summary {
+        println!("Session summary:");
+        for line in stats.summary(LLMClient::tokens_used(), stats.started.elapsed()) {
+            println!("  {}", line);
+        }
+    }
+
     // Save history
-    if let Err(e) = rl.save_history(history_path) {
+    if let Some(parent) = history_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Err(e) = rl.save_history(&history_path) {
         eprintln!("Error saving command history: {}", e);
     }
     
@@ -1411,41 +1574,63 @@ async fn main() {
     load_env_files();
     
     let args: Vec<String> = env::args().collect();
+    let (options, command_args) = match CliOptions::parse(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    let _ = OPTIONS.set(options.clone());
+    
+    if let Some(script) = &options.script {
+        if !command_args.is_empty() {
+            eprintln!("Error: --script runs a file and takes no command");
+            return;
+        }
+        std::process::exit(run_script_file(script, &options));
+    }
     
     // Check if we should run in interactive mode (no arguments or explicit "interactive" argument)
-    if args.len() <= 1 || (args.len() == 2 && args[1] == "interactive") {
-        if let Err(e) = run_interactive_mode().await {
+    if command_args.is_empty() || (command_args.len() == 1 && command_args[0] == "interactive") {
+        if let Err(e) = run_interactive_mode(&options).await {
             eprintln!("Error in interactive mode: {}", e);
         }
         return;
     }
     
     // Otherwise, run in command mode
-    let command_args: Vec<String> = args.iter().skip(1).cloned().collect();
     let input = command_args.join(" ");
 
     // Load configuration for command mode
-    let app_config = match get_config() {
+    let mut app_config = match get_config() {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Error loading configuration: {}", e);
             RustShellConfig::default()
         }
     };
+    options.apply(&mut app_config);
+    set_session_config(&app_config);
+    commands::context::set_output(io::stdout().is_terminal());
+    commands::context::set_quiet(app_config.ui.quiet);
+    commands::context::set_verbose(app_config.ui.verbose_mode);
 
     // Process natural language in command mode
-    let processed_command = if is_natural_language(&input) && app_config.features.enable_llm {
+    let mut translations = 0;
+    let processed_command = if wants_translation(&input) && app_config.features.enable_llm {
+        if print_prompt_only(&input, &app_config) {
+            return;
+        }
         if let Some(translated) = process_natural_language(&input, &app_config).await {
+            translations = 1;
+            if app_config.features.enable_history {
+                commands::nlhistory::record(&input, &translated);
+            }
             // Show confirmation if required
-            if app_config.requires_confirmation(&translated) {
-                print!("Execute '{}' ? (y/N): ", translated);
-                io::stdout().flush().unwrap();
-                let mut confirmation = String::new();
-                io::stdin().read_line(&mut confirmation).unwrap();
-                if !confirmation.trim().to_lowercase().starts_with('y') {
-                    println!("Command cancelled.");
-                    return;
-                }
+            if !confirm_translation(&translated, &app_config).await {
+                println!("Command cancelled.");
+                return;
             }
             translated.split_whitespace().map(String::from).collect()
         } else if app_config.features.fallback_to_traditional {
@@ -1459,9 +1644,374 @@ async fn main() {
     };
     
     // We can't use aliases in non-interactive mode
+    let mut exit_code = 0;
     if let Some(command) = parse_command(&processed_command, None) {
-        if let Err(e) = command.execute() {
-            eprintln!("Error executing command: {}", e);
+        let result = command.execute();
+        print_output(&result);
+        if let Some(e) = &result.error {
+            commands::report::report(&processed_command, e, app_config.ui.colored_output);
         }
+        exit_code = result.exit_code;
+    }
+
+    if app_config.features.collect_stats {
+        if let Some(name) = processed_command.first() {
+            commands::usage::record(&HashMap::from([(name.clone(), 1)]), translations, LLMClient::tokens_used());
+        }
+    }
+    std::process::exit(exit_code);
+}
+
+// Show what a command printed. A closed pipe (`rustshell find x | head`)
+// just cuts the output short.
+fn print_output(result: &CommandResult) {
+    let _ = io::stdout().write_all(result.stdout.as_bytes()).and_then(|()| io::stdout().flush());
+    let _ = io::stderr().write_all(result.stderr.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_saves_merge_across_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let alias_file = dir.path().join(".rustshell_aliases");
+        
+        let mut first = AliasManager::with_file(alias_file.clone()).unwrap();
+        let mut second = AliasManager::with_file(alias_file.clone()).unwrap();
+        
+        first.add_alias("ll".to_string(), "list -la".to_string()).unwrap();
+        second.add_alias("gs".to_string(), "git status".to_string()).unwrap();
+        
+        let reloaded = AliasManager::with_file(alias_file).unwrap();
+        assert_eq!(reloaded.get_alias("ll").map(String::as_str), Some("list -la"));
+        assert_eq!(reloaded.get_alias("gs").map(String::as_str), Some("git status"));
+    }
+
+    #[test]
+    fn test_aliases_shadow_builtins_unless_escaped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = AliasManager::with_file(dir.path().join(".rustshell_aliases")).unwrap();
+        manager.add_alias("ls".to_string(), "ls -la".to_string()).unwrap();
+
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        // Expanded once: the alias may use the built-in it shadows
+        assert_eq!(manager.expand_aliases(&args("ls src")), args("ls -la src"));
+        assert_eq!(manager.expand_aliases(&args("\\ls src")), args("ls src"));
+        assert_eq!(manager.expand_aliases(&args("pwd")), args("pwd"));
+    }
+
+    #[test]
+    fn test_ask_pipeline_expands_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = AliasManager::with_file(dir.path().join(".rustshell_aliases")).unwrap();
+        manager.add_alias("ll".to_string(), "list -la".to_string()).unwrap();
+
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let ask = ask_pipeline(&args("ll src | ask why"), Some(&manager)).unwrap();
+        assert_eq!(ask.command, args("list -la src"));
+        assert_eq!(ask.question, "why");
+        assert_eq!(ask_pipeline(&args("ll | ask why"), None).unwrap().command, args("ll"));
+    }
+
+    #[test]
+    fn test_pipeline_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = AliasManager::with_file(dir.path().join(".rustshell_aliases")).unwrap();
+        manager.add_alias("gl".to_string(), "git log --oneline | head".to_string()).unwrap();
+        manager.add_alias("ll".to_string(), "list -la".to_string()).unwrap();
+
+        let args = |line: &str| line.split_whitespace().map(String::from).collect::<Vec<_>>();
+        assert!(alias_is_pipeline(&args("gl -5"), Some(&manager)));
+        assert_eq!(manager.expand_aliases(&args("gl -5")), args("git log --oneline | head -5"));
+        assert!(!alias_is_pipeline(&args("ll src"), Some(&manager)));
+        assert!(!alias_is_pipeline(&args("\\gl"), Some(&manager)));
+        assert!(!alias_is_pipeline(&args("gl"), None));
+    }
+
+    #[test]
+    fn test_translation_problems() {
+        assert!(translation_problem("   ").unwrap().contains("empty"));
+        assert!(translation_problem("no-such-program-here --flag").unwrap().contains("'no-such-program-here'"));
+        assert_eq!(translation_problem("make_dir build"), None);
+    }
+
+    #[test]
+    fn test_multiline_prompt() {
+        let dir = Path::new("/home/me/projects/deep");
+        assert_eq!(prompt_for(dir, false), "/home/me/projects/deep> ");
+        assert_eq!(prompt_for(dir, true), "/home/me/projects/deep\n❯ ");
+    }
+
+    #[test]
+    fn test_history_expansion() {
+        // Newest first, including the `!` line being expanded
+        let history = ["!!", "show notes.txt", "list -la", "make_dir build"];
+        let expand = |line| expand_history(line, history.iter().copied());
+
+        assert_eq!(expand("!!"), Ok(Some("show notes.txt".to_string())));
+        assert_eq!(expand("!! other.txt"), Ok(Some("show notes.txt other.txt".to_string())));
+        assert_eq!(expand("!li"), Ok(Some("list -la".to_string())));
+        assert_eq!(expand("!ma src"), Ok(Some("make_dir build src".to_string())));
+        assert_eq!(expand("!nope"), Err("!nope: event not found".to_string()));
+        assert_eq!(expand("list"), Ok(None));
+        assert_eq!(expand("! list"), Ok(None));
+        assert_eq!(expand_history("!!", []), Err("!!: event not found".to_string()));
+    }
+
+    #[test]
+    fn test_comments_are_stripped() {
+        assert_eq!(strip_comment("# this is a note"), "");
+        assert_eq!(strip_comment("list -la  # long listing"), "list -la");
+        assert_eq!(strip_comment("run curl http://example.com/page#intro"), "run curl http://example.com/page#intro");
+        assert_eq!(strip_comment("run echo \"# not a comment\" '#' x"), "run echo \"# not a comment\" '#' x");
+        assert_eq!(strip_comment("show notes.txt"), "show notes.txt");
+    }
+
+    #[test]
+    fn test_loops_are_not_translated() {
+        assert!(!wants_translation("repeat 3 create_file part$i.txt"));
+        assert!(!wants_translation("for f in a.txt b.txt: show $f"));
+        assert!(!wants_translation("translate create a directory called test --output json"));
+        assert!(wants_translation("repeat the last command please"));
+        assert!(wants_translation("create a new directory"));
+    }
+
+    #[test]
+    fn test_dangerous_aliases_are_detected() {
+        let config = RustShellConfig::default();
+        assert!(AliasManager::is_dangerous("sudo rm -r build", &config));
+        assert!(AliasManager::is_dangerous("rm -rf /", &config));
+        assert!(!AliasManager::is_dangerous("list -la", &config));
+    }
+
+    #[test]
+    fn test_rename_alias() {
+        let dir = tempfile::tempdir().unwrap();
+        let alias_file = dir.path().join(".rustshell_aliases");
+        let mut manager = AliasManager::with_file(alias_file.clone()).unwrap();
+        manager.add_alias("ll".to_string(), "list -la".to_string()).unwrap();
+        manager.add_alias("la".to_string(), "list -a".to_string()).unwrap();
+
+        assert!(manager.rename_alias("ll", "l").unwrap());
+        assert!(!manager.rename_alias("missing", "x").unwrap());
+        assert_eq!(manager.rename_alias("l", "la").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+        let reloaded = AliasManager::with_file(alias_file).unwrap();
+        assert_eq!(reloaded.get_alias("l"), Some(&"list -la".to_string()));
+        assert!(reloaded.get_alias("ll").is_none());
+        assert_eq!(reloaded.get_alias("la"), Some(&"list -a".to_string()));
+    }
+
+    #[test]
+    fn test_completion_handles_multibyte_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let aliases = AliasManager::with_file(dir.path().join("aliases")).unwrap();
+        let helper = RustShellHelper::new(aliases, RustShellConfig::default());
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+
+        for line in ["café/", "show café/", "ñ", "日本語"] {
+            // Every byte offset, including ones inside a character
+            for pos in 0..=line.len() + 1 {
+                let _ = helper.complete(line, pos, &ctx);
+                let _ = helper.hint(line, pos, &ctx);
+            }
+        }
+
+        // Hints still complete ASCII prefixes
+        let hint = helper.hint("summ", 4, &ctx).unwrap();
+        assert_eq!(hint.completion(), Some("arize"));
+    }
+
+    #[test]
+    fn test_hints_for_mixed_case_and_non_ascii_prefixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let aliases = AliasManager::with_file(dir.path().join("aliases")).unwrap();
+        let mut helper = RustShellHelper::new(aliases, RustShellConfig::default());
+        helper.commands = vec!["Build".to_string(), "café".to_string(), "show".to_string(), "日本".to_string()];
+        let hint = |line: &str| helper.command_hint(line).map(|hint| hint.display);
+
+        assert_eq!(hint("Bu").as_deref(), Some("ild"));
+        assert_eq!(hint("bu"), None);
+        assert_eq!(hint("SH"), None);
+        assert_eq!(hint("ca").as_deref(), Some("fé"));
+        assert_eq!(hint("caf").as_deref(), Some("é"));
+        assert_eq!(hint("café"), None);
+        assert_eq!(hint("日").as_deref(), Some("本"));
+        assert_eq!(hint("é"), None);
+
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+        // Cursor not at the end of the line
+        assert!(helper.hint("caf", 2, &ctx).is_none());
+        assert!(helper.hint("caf", 10, &ctx).is_none());
+    }
+
+    #[test]
+    fn test_session_summary() {
+        let mut stats = SessionStats::new();
+        for name in ["list", "cd", "list", "cd", "show"] {
+            stats.record_command(name);
+        }
+        stats.record_translation();
+
+        let lines = stats.summary(stats.tokens_at_start + 42, Duration::from_secs(3723));
+        assert_eq!(lines[0], "Commands run:      5");
+        assert_eq!(lines[1], "LLM translations:  1");
+        assert_eq!(lines[2], "Tokens used:       42");
+        assert_eq!(lines[3], "Session time:      1h 02m 03s");
+        assert_eq!(lines[4], "Most used command: cd (2x)");
+
+        assert_eq!(SessionStats::format_duration(Duration::from_secs(75)), "1m 15s");
+        assert_eq!(SessionStats::new().summary(0, Duration::ZERO).len(), 4);
+    }
+
+    #[test]
+    fn test_saved_session_skips_meta_commands() {
+        let mut stats = SessionStats::new();
+        for line in ["make_dir build", "help", "cd build ", "showall", "create_file notes.txt"] {
+            stats.record_command(line);
+        }
+        stats.record_background_job("find *.log ");
+
+        assert_eq!(
+            stats.script("2024-05-01 10:00"),
+            "# RustShell session saved 2024-05-01 10:00\n# Replay with: rustshell --script <file>\n\
+             make_dir build\ncd build\ncreate_file notes.txt\n# find *.log &  (background job, not replayed)\n"
+        );
+        assert_eq!(stats.commands, 6);
+    }
+
+    #[test]
+    fn test_startup_script_errors_are_not_fatal() {
+        let dir = tempfile::tempdir().unwrap();
+        let aliases = AliasManager::with_file(dir.path().join("aliases")).unwrap();
+        let rc = dir.path().join("rc");
+        fs::write(
+            &rc,
+            "# startup\nexport RUSTSHELL_RC_TEST=before\n\ndelete_file /no/such/dir/file\nexport RUSTSHELL_RC_TEST=after\n",
+        )
+        .unwrap();
+
+        assert_eq!(run_script(&rc, &aliases), 1);
+        assert_eq!(env::var("RUSTSHELL_RC_TEST").unwrap(), "after");
+        assert_eq!(run_script(&dir.path().join("missing"), &aliases), 0);
+    }
+
+    #[test]
+    fn test_run_arguments_complete_by_position() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("some.txt"), "").unwrap();
+        let aliases = AliasManager::with_file(dir.path().join("aliases")).unwrap();
+        let helper = RustShellHelper::new(aliases, RustShellConfig::default());
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let replacements = |line: &str| {
+            let (_, pairs) = helper.complete(line, line.len(), &ctx).unwrap();
+            pairs.into_iter().map(|pair| pair.replacement).collect::<Vec<_>>()
+        };
+
+        let prefix = format!("{}{}s", dir.path().display(), std::path::MAIN_SEPARATOR);
+        assert_eq!(replacements(&format!("run cd {}", prefix)).len(), 1);
+        assert_eq!(replacements(&format!("exec cat {}", prefix)).len(), 2);
+        assert_eq!(replacements(&format!("run --log out.txt cd {}", prefix)).len(), 1);
+
+        #[cfg(unix)]
+        assert!(replacements("run s").contains(&"sh".to_string()));
+    }
+
+    #[test]
+    fn test_describe_alias_changes() {
+        let old: HashMap<String, String> = [("ll", "list -la"), ("gs", "git status")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let new: HashMap<String, String> = [("ll", "ls -l"), ("gd", "git diff")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        
+        assert_eq!(
+            describe_alias_changes(&old, &new),
+            vec!["added alias 'gd'", "changed alias 'll'", "removed alias 'gs'"]
+        );
+    }
+
+    #[test]
+    fn test_cli_options_timeout() {
+        let args: Vec<String> = ["--timeout", "120", "-q", "--no-cache", "run", "sleep", "--timeout", "5"]
+            .iter().map(|s| s.to_string()).collect();
+        let (options, rest) = CliOptions::parse(&args).unwrap();
+        
+        assert_eq!(options.timeout, Some(120));
+        assert!(options.quiet);
+        assert_eq!(rest, vec!["run", "sleep", "--timeout", "5"]);
+        
+        let mut config = RustShellConfig::default();
+        options.apply(&mut config);
+        assert_eq!(config.llm.timeout_seconds, 120);
+        assert!(config.ui.quiet);
+        assert!(!config.llm.enable_cache);
+        
+        let bad: Vec<String> = vec!["--timeout".to_string(), "soon".to_string()];
+        assert!(CliOptions::parse(&bad).is_err());
+    }
+
+    #[test]
+    fn test_missing_key_guidance() {
+        let mut config = RustShellConfig::default();
+        config.llm.api_key_env = Some("RUSTSHELL_TEST_UNSET_KEY".to_string());
+        let unset = |_: &str| None;
+        let guidance = missing_key_guidance(&config, unset).unwrap();
+        assert!(guidance.starts_with("No API key found for OpenAI"));
+        assert!(guidance.contains("`export RUSTSHELL_TEST_UNSET_KEY=...`"));
+        assert!(guidance.contains("features.offline_mode = true"));
+
+        // The client falls back to OPENAI_API_KEY
+        let openai = |name: &str| (name == "OPENAI_API_KEY").then(|| "sk-env".to_string());
+        assert_eq!(missing_key_guidance(&config, openai), None);
+
+        // A fallback provider may answer instead
+        config.llm.fallback_providers = vec!["mock:ls".to_string()];
+        assert_eq!(missing_key_guidance(&config, unset), None);
+        config.llm.fallback_providers.clear();
+
+        config.llm.api_key_env = Some("sk-inline".to_string());
+        assert_eq!(missing_key_guidance(&config, unset), None);
+        config.llm.provider = "mock".to_string();
+        assert_eq!(missing_key_guidance(&config, unset), None);
+    }
+
+    #[test]
+    fn test_print_prompt_report() {
+        let args: Vec<String> = ["--print-prompt", "--print-prompt-only", "list files"].iter().map(|s| s.to_string()).collect();
+        let (options, rest) = CliOptions::parse(&args).unwrap();
+        assert!(options.print_prompt && options.print_prompt_only);
+        assert_eq!(rest, vec!["list files"]);
+
+        let config = RustShellConfig::default();
+        let request = translation_request(unquote("\"list my files\""), &config);
+        let report = describe_llm_request(&config.to_llm_config().unwrap(), &request);
+        assert!(report.contains("Model:       gpt-3.5-turbo\nMax tokens:  150\nTemperature: 0.1\n"));
+        assert!(report.contains("[system]\nYou are a cross-platform command translator."));
+        assert!(report.contains("User Request: \"list my files\""));
+        assert!(report.ends_with("Provide only the command:\n--- end of LLM request ---"));
+    }
+
+    #[tokio::test]
+    async fn test_natural_language_through_mock_provider() {
+        let mut config = RustShellConfig::default();
+        config.llm.provider = "mock".to_string();
+        assert_eq!(
+            process_natural_language("please list files", &config).await,
+            Some("echo please list files".to_string())
+        );
+        
+        config.llm.provider = "mock:rm -rf /".to_string();
+        config.safety.block_destructive = true;
+        assert_eq!(process_natural_language("please clean up everything", &config).await, None);
     }
 }
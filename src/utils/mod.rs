@@ -0,0 +1,753 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// Write `contents` to `path` atomically: the data goes to a temporary file in the
+// same directory first and is renamed over the target only once fully written,
+// so a crash mid-save never leaves a truncated file behind.
+pub fn atomic_write<P: AsRef<Path>>(path: P, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+    let tmp_path = dir.join(format!(
+        ".{}.tmp{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+// Absolute, symlink-free form of `path`, which need not exist yet: the deepest
+// existing ancestor is canonicalized and the remaining components appended.
+pub fn resolve_path<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+
+    let mut resolved = fs::canonicalize(existing)?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved)
+}
+
+// Whether `path` is `dir` itself or lies somewhere beneath it
+pub fn is_within<P: AsRef<Path>, D: AsRef<Path>>(path: P, dir: D) -> io::Result<bool> {
+    Ok(resolve_path(path)?.starts_with(resolve_path(dir)?))
+}
+
+// Ask a yes/no question on stdin. The hint shows the default in capitals
+// ("(y/N)" or "(Y/n)"), and an empty reply selects it.
+pub fn confirm(question: &str, default_yes: bool) -> io::Result<bool> {
+    let hint = if default_yes { "(Y/n)" } else { "(y/N)" };
+    print!("{} {}: ", question, hint);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(interpret_confirmation(&answer, default_yes))
+}
+
+// `confirm` that gives up after `timeout` and takes the default. The answer is
+// read on a detached thread (so a pending read never delays exit), which keeps
+// waiting for a line after a timeout; only use this when nothing else reads
+// stdin afterwards.
+pub async fn confirm_within(question: &str, default_yes: bool, timeout: std::time::Duration) -> io::Result<bool> {
+    let hint = if default_yes { "(Y/n)" } else { "(y/N)" };
+    print!("{} {} [{}s]: ", question, hint, timeout.as_secs());
+    io::stdout().flush()?;
+
+    let (sender, read) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let mut answer = String::new();
+        let _ = sender.send(io::stdin().lock().read_line(&mut answer).map(|_| answer));
+    });
+    match tokio::time::timeout(timeout, read).await {
+        Ok(answer) => Ok(interpret_confirmation(&answer.map_err(io::Error::other)??, default_yes)),
+        Err(_) => {
+            println!();
+            println!("No answer after {}s; {}", timeout.as_secs(), if default_yes { "proceeding" } else { "cancelling" });
+            Ok(default_yes)
+        }
+    }
+}
+
+fn interpret_confirmation(answer: &str, default_yes: bool) -> bool {
+    let answer = answer.trim().to_lowercase();
+    if answer.is_empty() {
+        default_yes
+    } else {
+        answer.starts_with('y')
+    }
+}
+
+// Format a size in bytes. With `human`, use binary units like `ls -h`
+// ("1023", "1.0K", "15M"); otherwise print the exact byte count.
+pub fn format_size(bytes: u64, human: bool) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+
+    if !human || bytes < 1024 {
+        return bytes.to_string();
+    }
+
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 9.95 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else if value.round() < 1024.0 || unit == UNITS.len() - 1 {
+        format!("{:.0}{}", value, UNITS[unit])
+    } else {
+        // Rounds up to the next unit, e.g. 1023.9K
+        format!("1.0{}", UNITS[unit + 1])
+    }
+}
+
+pub struct DecodedText {
+    pub text: String,
+    // Name of the encoding the bytes were decoded from, e.g. "UTF-16LE"
+    pub encoding: &'static str,
+    // False when the encoding was a guess that may well be wrong
+    pub reliable: bool,
+}
+
+// Decode file contents for display. A byte-order mark wins, then valid UTF-8;
+// anything else is guessed with chardetng (e.g. Latin-1 / windows-1252).
+pub fn decode_text(bytes: &[u8]) -> DecodedText {
+    if let Some((encoding, bom_length)) = encoding_rs::Encoding::for_bom(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return DecodedText {
+            text: text.into_owned(),
+            encoding: encoding.name(),
+            reliable: !had_errors,
+        };
+    }
+
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: encoding_rs::UTF_8.name(),
+            reliable: true,
+        };
+    }
+
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    let (encoding, confident) = detector.guess_assess(None, true);
+    let (text, _, had_errors) = encoding.decode(bytes);
+    DecodedText {
+        text: text.into_owned(),
+        encoding: encoding.name(),
+        reliable: confident && !had_errors,
+    }
+}
+
+// How much of a file to inspect when guessing whether it is binary
+pub const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+// Heuristic used by grep and ripgrep: a NUL byte near the start of the data
+// means it is binary. Text encodings in practical use never contain one.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+// Match `name` against a shell wildcard pattern: `*` matches any run of
+// characters and `?` exactly one. Everything else is literal.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it is currently matched up to
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` absorb one more character and retry
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Whether `path`, relative to the directory being walked, matches one of the
+// gitignore-style `--exclude` patterns. A plain pattern such as `target` or
+// `*.log` matches a name at any depth; one containing `/` matches the whole
+// relative path (`docs/build`); a trailing `/` only matches directories.
+pub fn is_excluded(path: &Path, is_dir: bool, patterns: &[String]) -> bool {
+    let relative: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    let Some(name) = relative.last() else {
+        return false;
+    };
+
+    patterns.iter().any(|pattern| {
+        let (pattern, dirs_only) = match pattern.strip_suffix('/') {
+            Some(dir) => (dir, true),
+            None => (pattern.as_str(), false),
+        };
+        if dirs_only && !is_dir {
+            return false;
+        }
+        if pattern.contains('/') {
+            wildcard_match(pattern.trim_start_matches('/'), &relative.join("/"))
+        } else {
+            wildcard_match(pattern, name)
+        }
+    })
+}
+
+// A size in bytes, optionally with a binary unit as `format_size` prints them:
+// "512", "64K", "100M", "4G" (case-insensitive, and a trailing "B" is allowed)
+pub fn parse_size(text: &str) -> Option<u64> {
+    let upper = text.to_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let split = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    let (amount, unit) = digits.split_at(split);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    amount.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+// A duration such as "90s", "15m", "1h", "2d" or "1w"
+pub fn parse_duration(text: &str) -> Option<std::time::Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = text.split_at(split);
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let seconds = amount.parse::<u64>().ok()?.checked_mul(seconds_per_unit)?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+// Edits (insertions, deletions, substitutions or swaps of neighbouring
+// characters) needed to turn `a` into `b`, so `notse` is one edit from `notes`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+// Score `candidate` for fuzzy `query`: every query character must appear in
+// order (case-insensitively). Runs of consecutive characters and matches at
+// the start of a path component score higher; longer candidates score lower.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0i64;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let found = (next..candidate.len()).find(|&i| candidate[i].to_lowercase().eq(std::iter::once(q)))?;
+        score += 1;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if found == 0 || matches!(candidate[found - 1], '/' | '\\' | '_' | '-' | '.' | ' ') {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score * 100 - candidate.len() as i64)
+}
+
+// Entries scanned at most, so a picker opened at `/` still answers quickly
+const FUZZY_FIND_MAX_ENTRIES: usize = 50_000;
+
+// Paths under `root`, relative to it, for fuzzy finding. Hidden entries are
+// skipped unless `show_hidden`; directories end in a separator.
+pub fn fuzzy_candidates(root: &Path, show_hidden: bool) -> Vec<String> {
+    walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| show_hidden || !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(Result::ok)
+        .take(FUZZY_FIND_MAX_ENTRIES)
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?.to_string_lossy().to_string();
+            Some(if entry.file_type().is_dir() {
+                format!("{}{}", relative, std::path::MAIN_SEPARATOR)
+            } else {
+                relative
+            })
+        })
+        .collect()
+}
+
+// The `limit` best `candidates` for `query`
+pub fn fuzzy_rank(query: &str, candidates: &[String], limit: usize) -> Vec<String> {
+    let mut scored: Vec<(i64, &String)> = candidates
+        .iter()
+        .filter_map(|candidate| Some((fuzzy_score(query, candidate)?, candidate)))
+        .collect();
+    scored.sort_by(|(a_score, a_path), (b_score, b_path)| b_score.cmp(a_score).then(a_path.cmp(b_path)));
+    scored.into_iter().take(limit).map(|(_, path)| path.clone()).collect()
+}
+
+// Paths under `root` matching `query`, best first. Hidden entries are only
+// searched when the query asks for them with a leading dot.
+#[cfg(test)]
+pub fn fuzzy_find(root: &Path, query: &str, limit: usize) -> Vec<String> {
+    fuzzy_rank(query, &fuzzy_candidates(root, query.starts_with('.')), limit)
+}
+
+// Locate `program` on PATH like `which`, trying the usual extensions on Windows
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    find_in_dirs(program, std::env::split_paths(&std::env::var_os("PATH")?))
+}
+
+// The first executable named `program` in `dirs`. Extensions are appended, not
+// substituted, so dotted names such as `ld.gold` are found as they are.
+fn find_in_dirs(program: &str, dirs: impl IntoIterator<Item = PathBuf>) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &[".exe", ".cmd", ".bat"] } else { &[""] };
+    dirs.into_iter()
+        .flat_map(|dir| {
+            extensions.iter().map(move |ext| {
+                let mut name = std::ffi::OsString::from(program);
+                name.push(ext);
+                dir.join(name)
+            })
+        })
+        .find(|candidate| is_executable(candidate))
+}
+
+// Names of programs on PATH starting with `prefix`, sorted and deduplicated
+pub fn path_executables(prefix: &str) -> Vec<String> {
+    let Some(path) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = std::env::split_paths(&path)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    path.is_file() && matches!(extension.as_deref(), Some("exe" | "cmd" | "bat"))
+}
+
+// POSIX tilde expansion of one word: `~` and `~/x` use the current user's home,
+// `~name` and `~name/x` that user's home. A word naming an unknown user is left
+// unchanged, as sh does.
+pub fn expand_tilde(word: &str) -> String {
+    let Some(rest) = word.strip_prefix('~') else {
+        return word.to_string();
+    };
+    let end = rest.find(|c| c == '/' || (cfg!(windows) && c == '\\')).unwrap_or(rest.len());
+    let (user, tail) = rest.split_at(end);
+
+    let home = if user.is_empty() { dirs_next::home_dir() } else { user_home(user) };
+    match home {
+        Some(home) => format!("{}{}", home.display(), tail),
+        None => word.to_string(),
+    }
+}
+
+// Bash brace expansion of one word: `file{1,2}.txt` gives `file1.txt` and
+// `file2.txt`, `{1..3}` counts from 1 to 3 (`{01..10}` keeps the padding,
+// `{a..e}` runs through letters) and braces nest, as in `{src,test}/{a,b}`.
+// Braces without a comma or range inside, like `{}`, or without a match are
+// kept as typed.
+pub fn expand_braces(word: &str) -> Vec<String> {
+    for (open, _) in word.match_indices('{') {
+        let Some(close) = matching_brace(word, open) else {
+            continue;
+        };
+        let inner = &word[open + 1..close];
+        let alternatives = match split_alternatives(inner) {
+            Some(alternatives) => alternatives,
+            None => match brace_range(inner) {
+                Some(range) => range,
+                None => continue,
+            },
+        };
+        let (prefix, suffix) = (&word[..open], &word[close + 1..]);
+        return alternatives
+            .iter()
+            .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+            .collect();
+    }
+    vec![word.to_string()]
+}
+
+// Index of the `}` closing the `{` at `open`
+fn matching_brace(word: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in word[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => return Some(open + i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// The comma-separated parts of a brace body, if it has a comma outside nested braces
+fn split_alternatives(inner: &str) -> Option<Vec<String>> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(inner[start..i].to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    parts.push(inner[start..].to_string());
+    Some(parts)
+}
+
+// The words of a `{1..5}` or `{a..e}` range, counting down if the end is smaller
+fn brace_range(inner: &str) -> Option<Vec<String>> {
+    let (from, to) = inner.split_once("..")?;
+    if let (Ok(start), Ok(end)) = (from.parse::<i64>(), to.parse::<i64>()) {
+        // A leading zero on either end pads every number to the longer width
+        let padded = |s: &str| s.trim_start_matches('-').len() > 1 && s.trim_start_matches('-').starts_with('0');
+        let width = if padded(from) || padded(to) { from.len().max(to.len()) } else { 0 };
+        let numbers: Vec<i64> = if start <= end { (start..=end).collect() } else { (end..=start).rev().collect() };
+        return Some(numbers.iter().map(|n| format!("{:0width$}", n, width = width)).collect());
+    }
+
+    let (mut from_chars, mut to_chars) = (from.chars(), to.chars());
+    match (from_chars.next(), from_chars.next(), to_chars.next(), to_chars.next()) {
+        (Some(start), None, Some(end), None) if start.is_ascii_alphabetic() && end.is_ascii_alphabetic() => {
+            let letters: Vec<char> = if start <= end { (start..=end).collect() } else { (end..=start).rev().collect() };
+            Some(letters.iter().map(|c| c.to_string()).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+fn user_home(name: &str) -> Option<PathBuf> {
+    use users::os::unix::UserExt;
+    users::get_user_by_name(name).map(|user| user.home_dir().to_path_buf())
+}
+
+// Windows has no passwd database; assume the usual layout where every profile
+// sits next to the current user's (C:\Users\<name>)
+#[cfg(not(unix))]
+fn user_home(name: &str) -> Option<PathBuf> {
+    let home = dirs_next::home_dir()?.parent()?.join(name);
+    home.is_dir().then_some(home)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_write_replaces_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aliases");
+
+        atomic_write(&path, b"first").unwrap();
+        atomic_write(&path, b"second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        // Only the target should remain, no stray temporary files
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_is_within_resolves_missing_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir(&src).unwrap();
+
+        assert!(is_within(src.join("sub/new"), &src).unwrap());
+        assert!(is_within(src.join("../src/x"), &src).unwrap());
+        assert!(!is_within(dir.path().join("backup"), &src).unwrap());
+        // Sharing a name prefix is not containment
+        assert!(!is_within(dir.path().join("src2"), &src).unwrap());
+    }
+
+    #[test]
+    fn test_interpret_confirmation() {
+        assert!(!interpret_confirmation("\n", false));
+        assert!(interpret_confirmation("\n", true));
+        assert!(interpret_confirmation("Yes\n", false));
+        assert!(!interpret_confirmation("n\n", true));
+        assert!(!interpret_confirmation("maybe\n", true));
+    }
+
+    #[test]
+    fn test_format_size_boundaries() {
+        assert_eq!(format_size(0, true), "0");
+        assert_eq!(format_size(1023, true), "1023");
+        assert_eq!(format_size(1024, true), "1.0K");
+        assert_eq!(format_size(1536, true), "1.5K");
+        assert_eq!(format_size(10 * 1024, true), "10K");
+        assert_eq!(format_size(1024 * 1024 - 1, true), "1.0M");
+        assert_eq!(format_size(1024 * 1024, true), "1.0M");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024, true), "5.0G");
+        assert_eq!(format_size(1024, false), "1024");
+    }
+
+    #[test]
+    fn test_decode_text_encodings() {
+        let plain = decode_text("naïve".as_bytes());
+        assert_eq!((plain.text.as_str(), plain.encoding), ("naïve", "UTF-8"));
+
+        // "hi" in UTF-16LE with a byte-order mark, as Windows tools write it
+        let utf16 = decode_text(&[0xFF, 0xFE, b'h', 0, b'i', 0]);
+        assert_eq!((utf16.text.as_str(), utf16.encoding), ("hi", "UTF-16LE"));
+
+        // Latin-1 "café au lait, très bon" is not valid UTF-8
+        let latin1 = decode_text(b"caf\xe9 au lait, tr\xe8s bon");
+        assert_eq!(latin1.text, "café au lait, très bon");
+        assert_eq!(latin1.encoding, "windows-1252");
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.rs", "main.rs"));
+        assert!(!wildcard_match("*.rs", "main.rs.bak"));
+        assert!(wildcard_match("*main*", "main.rs"));
+        assert!(wildcard_match("*a*b*", "xxaxxbxx"));
+        assert!(wildcard_match("?.txt", "é.txt"));
+        assert!(!wildcard_match("?.txt", "ab.txt"));
+        assert!(wildcard_match("**", ""));
+        assert!(!wildcard_match("Main*", "main.rs"));
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let patterns: Vec<String> = ["target", "*.log", "docs/build", "cache/"].map(String::from).to_vec();
+        assert!(is_excluded(Path::new("target"), true, &patterns));
+        assert!(is_excluded(Path::new("web/node/target"), true, &patterns));
+        assert!(is_excluded(Path::new("logs/app.log"), false, &patterns));
+        assert!(is_excluded(Path::new("docs/build"), true, &patterns));
+        assert!(!is_excluded(Path::new("src/docs/build"), true, &patterns));
+        assert!(is_excluded(Path::new("cache"), true, &patterns));
+        assert!(!is_excluded(Path::new("cache"), false, &patterns));
+        assert!(!is_excluded(Path::new("src/main.rs"), false, &patterns));
+        assert!(!is_excluded(Path::new(""), true, &patterns));
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = dirs_next::home_dir().unwrap().display().to_string();
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/notes.txt"), format!("{}/notes.txt", home));
+        assert_eq!(expand_tilde("~no-such-user-here/x"), "~no-such-user-here/x");
+        assert_eq!(expand_tilde("a~b"), "a~b");
+
+        #[cfg(unix)]
+        {
+            let me = users::get_current_username().unwrap();
+            let my_home = user_home(me.to_str().unwrap()).unwrap();
+            let expected = format!("{}/src", my_home.display());
+            assert_eq!(expand_tilde(&format!("~{}/src", me.to_str().unwrap())), expected);
+        }
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(expand_braces("file{1,2,3}.txt"), ["file1.txt", "file2.txt", "file3.txt"]);
+        assert_eq!(expand_braces("src/{bin,lib}"), ["src/bin", "src/lib"]);
+        assert_eq!(expand_braces("{a,}b"), ["ab", "b"]);
+
+        assert_eq!(expand_braces("log{1..4}"), ["log1", "log2", "log3", "log4"]);
+        assert_eq!(expand_braces("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(expand_braces("{08..10}"), ["08", "09", "10"]);
+        assert_eq!(expand_braces("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(expand_braces("{a..c}"), ["a", "b", "c"]);
+
+        assert_eq!(expand_braces("{src,test}/{a,b}.rs"), ["src/a.rs", "src/b.rs", "test/a.rs", "test/b.rs"]);
+        assert_eq!(expand_braces("x{a,b{1,2}}"), ["xa", "xb1", "xb2"]);
+        assert_eq!(expand_braces("{x{1,2}}"), ["{x1}", "{x2}"]);
+
+        for literal in ["{}", "{x}", "a{b", "a}b", "{1..}", "{a..bc}", "plain"] {
+            assert_eq!(expand_braces(literal), [literal]);
+        }
+        assert_eq!(expand_braces("{}{1,2}"), ["{}1", "{}2"]);
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b"fn main() {}\n"));
+        assert!(!looks_binary("caf\u{e9}".as_bytes()));
+        assert!(looks_binary(b"\x7fELF\x02\x01\x01\x00\x00"));
+
+        // Only the first few KB are inspected
+        let mut late_nul = vec![b'a'; BINARY_SNIFF_LEN];
+        late_nul.push(0);
+        assert!(!looks_binary(&late_nul));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64k"), Some(65_536));
+        assert_eq!(parse_size("100M"), Some(100 * 1024 * 1024));
+        assert_eq!(parse_size("2GB"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("M"), None);
+        assert_eq!(parse_size("1.5G"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        use std::time::Duration;
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("2d"), Some(Duration::from_secs(172_800)));
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("10"), None);
+        assert_eq!(parse_duration("5y"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("notes", "notes"), 0);
+        assert_eq!(edit_distance("notse", "notes"), 1);
+        assert_eq!(edit_distance("shw", "show"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_in_dirs_keeps_dotted_names() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["ld", "ld.gold", "notes.txt"] {
+            let path = dir.path().join(name);
+            fs::write(&path, "").unwrap();
+            let mode = if name == "notes.txt" { 0o644 } else { 0o755 };
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let dirs = || vec![dir.path().to_path_buf()];
+
+        assert_eq!(find_in_dirs("ld.gold", dirs()), Some(dir.path().join("ld.gold")));
+        assert_eq!(find_in_dirs("ld", dirs()), Some(dir.path().join("ld")));
+        // Files that can't be run are not programs
+        assert_eq!(find_in_dirs("notes.txt", dirs()), None);
+        assert_eq!(find_in_dirs("perl5.36.0", dirs()), None);
+    }
+
+    #[test]
+    fn test_fuzzy_find() {
+        assert!(fuzzy_score("mrs", "src/main.rs").is_some());
+        assert!(fuzzy_score("rsm", "src/main.rs").is_none());
+        // Consecutive and component-start matches win over scattered ones
+        assert!(fuzzy_score("main", "src/main.rs") > fuzzy_score("main", "my_app/index.rs"));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src/commands")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        fs::write(dir.path().join("src/commands/mod.rs"), "").unwrap();
+        fs::write(dir.path().join(".git/config"), "").unwrap();
+
+        let sep = std::path::MAIN_SEPARATOR;
+        assert_eq!(fuzzy_find(dir.path(), "main", 5), vec![format!("src{}main.rs", sep)]);
+        assert_eq!(fuzzy_find(dir.path(), "cmd", 5), vec![format!("src{}commands{}", sep, sep), format!("src{0}commands{0}mod.rs", sep)]);
+        assert!(fuzzy_find(dir.path(), "config", 5).is_empty());
+        assert_eq!(fuzzy_find(dir.path(), ".gitconf", 5), vec![format!(".git{}config", sep)]);
+    }
+}